@@ -0,0 +1,62 @@
+use std::sync::Mutex;
+
+use perceive_core::cancel::CancellationToken;
+
+/// The token for the operation that Ctrl-C should currently cancel.
+static ACTIVE: Mutex<Option<CancellationToken>> = Mutex::new(None);
+
+/// Install a Ctrl-C handler that cancels the running scan, if there is one. Otherwise, or on a
+/// second press, it exits as usual.
+pub fn install_handler() -> Result<(), ctrlc::Error> {
+    ctrlc::set_handler(|| {
+        let active = ACTIVE.lock().unwrap();
+        match active.as_ref() {
+            Some(token) if !token.is_cancelled() => {
+                eprintln!("\nStopping after the current items. Press Ctrl-C again to exit now.");
+                token.cancel();
+            }
+            _ => std::process::exit(130),
+        }
+    })
+}
+
+/// A cancellation token that Ctrl-C will trigger until this is dropped. Operations started inside
+/// another one, like the scans run by the daemon, share the outer operation's token so that
+/// Ctrl-C stops all of them.
+pub struct ActiveCancel {
+    token: CancellationToken,
+    /// True if this is the outermost operation, which clears the active token when it finishes.
+    outermost: bool,
+}
+
+impl ActiveCancel {
+    pub fn begin() -> ActiveCancel {
+        let mut active = ACTIVE.lock().unwrap();
+        match active.as_ref() {
+            Some(token) => ActiveCancel {
+                token: token.clone(),
+                outermost: false,
+            },
+            None => {
+                let token = CancellationToken::new();
+                *active = Some(token.clone());
+                ActiveCancel {
+                    token,
+                    outermost: true,
+                }
+            }
+        }
+    }
+
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+impl Drop for ActiveCancel {
+    fn drop(&mut self) {
+        if self.outermost {
+            ACTIVE.lock().unwrap().take();
+        }
+    }
+}
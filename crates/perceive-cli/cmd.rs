@@ -1,12 +1,15 @@
 use clap::Subcommand;
-use eyre::{eyre, Result};
+use eyre::Result;
 
-use self::{hide::HideArgs, model::ModelArgs, print::PrintArgs, search::SearchArgs};
+use self::{
+    hide::HideArgs, model::ModelArgs, print::PrintArgs, refresh::RefreshArgs, search::SearchArgs,
+};
 use crate::AppState;
 
 pub mod hide;
 pub mod model;
 pub mod print;
+pub mod refresh;
 pub mod search;
 pub mod source;
 
@@ -14,8 +17,10 @@ pub mod source;
 pub enum Commands {
     /// Manage data sources
     Source(source::SourceArgs),
-    /// Refresh data
-    Refresh,
+    /// Scan the sources that are due to be reindexed
+    Refresh(RefreshArgs),
+    /// Keep running, and scan each source when it is due to be reindexed
+    Daemon,
     /// Do a search
     Search(SearchArgs),
     /// Configure the model
@@ -28,7 +33,8 @@ pub enum Commands {
 
 pub fn handle_command(state: &mut AppState, cmd: Commands) -> Result<()> {
     match cmd {
-        Commands::Refresh => return Err(eyre!("Not implemented yet")),
+        Commands::Refresh(args) => refresh::refresh(state, args),
+        Commands::Daemon => refresh::daemon(state),
         Commands::Search(args) => search::search(state, args),
         Commands::Source(args) => source::handle_source_command(state, args),
         Commands::Model(args) => model::handle_model_command(state, args),
@@ -0,0 +1,96 @@
+use std::time::{Duration, Instant};
+
+use clap::Args;
+use eyre::{eyre, Result};
+use perceive_core::sources::scheduler::{due_sources, next_due_time};
+use time::OffsetDateTime;
+
+use super::source::{scan_source, ScanSourceArgs};
+use crate::{cancel::ActiveCancel, AppState};
+
+/// The longest the daemon will sleep before checking the sources again.
+const MAX_DAEMON_SLEEP: Duration = Duration::from_secs(60);
+/// How often the daemon checks for Ctrl-C while it's waiting for the next source.
+const DAEMON_CANCEL_CHECK: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Args)]
+pub struct RefreshArgs {
+    /// Scan every source, not just the ones that are due
+    #[clap(short, long)]
+    pub all: bool,
+}
+
+/// Scan the sources that are due to be reindexed, one after another.
+pub fn refresh(state: &mut AppState, args: RefreshArgs) -> Result<()> {
+    let indexes = if args.all {
+        (0..state.sources.len()).collect()
+    } else {
+        due_sources(&state.sources, OffsetDateTime::now_utc())
+    };
+
+    if indexes.is_empty() {
+        println!("No sources are due for indexing");
+        return Ok(());
+    }
+
+    let names = indexes
+        .into_iter()
+        .map(|i| state.sources[i].name.clone())
+        .collect::<Vec<_>>();
+
+    // The scans share this token, so once one of them is cancelled the rest aren't started.
+    let cancel = ActiveCancel::begin();
+    for name in names {
+        if cancel.token().is_cancelled() {
+            break;
+        }
+
+        println!("Scanning {name}");
+        let result = scan_source(
+            state,
+            ScanSourceArgs {
+                name: name.clone(),
+                by_content: false,
+                force: false,
+                event_log: None,
+            },
+        );
+
+        // Keep going so that one broken source doesn't block the others.
+        if let Err(e) = result {
+            println!("Failed to scan {name}: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Run until Ctrl-C is pressed, scanning each source when it is due.
+pub fn daemon(state: &mut AppState) -> Result<()> {
+    if next_due_time(&state.sources).is_none() {
+        return Err(eyre!(
+            "No sources have an index interval. Set one with `source edit --index-interval`"
+        ));
+    }
+
+    // Like in `refresh`, the scans share this token, so Ctrl-C stops the daemon too.
+    let cancel = ActiveCancel::begin();
+
+    while !cancel.token().is_cancelled() {
+        refresh(state, RefreshArgs { all: false })?;
+
+        let now = OffsetDateTime::now_utc();
+        let sleep_time = next_due_time(&state.sources)
+            .map(|next| (next - now).try_into().unwrap_or(Duration::ZERO))
+            .unwrap_or(MAX_DAEMON_SLEEP)
+            .min(MAX_DAEMON_SLEEP);
+
+        let wake_at = Instant::now() + sleep_time;
+        while !cancel.token().is_cancelled() && Instant::now() < wake_at {
+            let remaining = wake_at.saturating_duration_since(Instant::now());
+            std::thread::sleep(remaining.min(DAEMON_CANCEL_CHECK));
+        }
+    }
+
+    Ok(())
+}
@@ -2,8 +2,10 @@ use clap::Args;
 use eyre::{eyre, Result};
 use owo_colors::OwoColorize;
 use perceive_core::{
+    links::backlinks,
     search::{self, deserialize_embedding},
     sources::SourceTypeTag,
+    tags::items_with_tag,
 };
 
 use crate::AppState;
@@ -31,6 +33,10 @@ pub struct SearchArgs {
     )]
     pub source_type: Option<SourceTypeTag>,
 
+    /// Search only items with this tag
+    #[arg(long)]
+    pub tag: Option<String>,
+
     /// Return this number of search results
     #[arg(short, long, default_value_t = 20)]
     pub num_results: usize,
@@ -89,16 +95,30 @@ pub fn search(state: &mut AppState, args: SearchArgs) -> Result<()> {
         }
     };
 
+    let only_items = args
+        .tag
+        .as_deref()
+        .map(|tag| items_with_tag(&state.database, tag))
+        .transpose()?;
+
     let results = state.searcher.search_vector_and_retrieve(
         &state.database,
         &sources,
+        only_items.as_ref(),
         args.num_results,
         input_vec,
     )?;
 
+    // Look for the highlight within the passage that matched, when there is one.
     let result_docs = results
         .iter()
-        .map(|r| r.0.content.as_deref().unwrap_or_default())
+        .map(|(item, result)| {
+            let content = item.content.as_deref().unwrap_or_default();
+            content
+                .get(result.passage.range())
+                .filter(|passage| !passage.is_empty())
+                .unwrap_or(content)
+        })
         .collect::<Vec<_>>();
 
     let highlights = state.highlights_model.highlight(&query, &result_docs)?;
@@ -118,6 +138,23 @@ pub fn search(state: &mut AppState, args: SearchArgs) -> Result<()> {
             item.id,
             desc.bold()
         );
+
+        let linked_from = backlinks(&state.database, item.id)?;
+        if !linked_from.is_empty() {
+            // Items from the same document, like other highlights from a book, share a name,
+            // so show what sets them apart instead.
+            let names = linked_from
+                .iter()
+                .map(|l| match (&l.name, &l.description) {
+                    (Some(name), Some(desc)) if result.metadata.name.as_ref() == Some(name) => {
+                        desc.as_str()
+                    }
+                    (Some(name), _) => name.as_str(),
+                    (None, _) => &l.external_id,
+                })
+                .collect::<Vec<_>>();
+            println!("    Linked from: {}", names.join(", ").dimmed());
+        }
     }
 
     Ok(())
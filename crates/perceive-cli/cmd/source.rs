@@ -1,15 +1,29 @@
-use std::{path::Path, sync::atomic::AtomicBool, time::Duration};
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::atomic::AtomicBool,
+    time::Duration,
+};
 
 use clap::{Args, Subcommand};
 use eyre::{eyre, Result};
 use indicatif::ProgressBar;
 use perceive_core::sources::{
-    db::update_source, pipeline::ScanStats, ChromiumBookmarksConfig, ChromiumHistoryConfig,
-    FsSourceConfig, ItemCompareStrategy, Source, SourceConfig,
+    db::update_source,
+    item_errors::{count_item_errors, list_item_errors, ItemErrorKind},
+    pipeline::{events::write_event_log, ScanEvent, ScanStats},
+    scheduler::index_source,
+    web_fetcher::FetchConfig,
+    ChatConfig, ChatPlatform, ChromiumBookmarksConfig, ChromiumHistoryConfig, EmailConfig,
+    EvernoteConfig, FeedsConfig, FirefoxBookmarksConfig, FirefoxHistoryConfig, FsSourceConfig,
+    GitConfig, HighlightsConfig, HighlightsFormat, ItemCompareStrategy, NetscapeBookmarksConfig,
+    NotionConfig, Shell, ShellHistoryConfig, SocialArchiveConfig, SocialPlatform, Source,
+    SourceConfig, VaultConfig, WebArchiveConfig,
 };
 use time::OffsetDateTime;
 
-use crate::AppState;
+use crate::{cancel::ActiveCancel, AppState};
 
 #[derive(Debug, Args)]
 pub struct SourceArgs {
@@ -21,9 +35,13 @@ pub struct SourceArgs {
 pub enum SourceCommand {
     Add(AddSourceArgs),
     Edit(EditSourceArgs),
+    /// List the items that failed to read during scans of a source
+    Errors(SourceErrorsArgs),
     RebuildSearch(RebuildSearchArgs),
     Reprocess(ReprocessArgs),
     Scan(ScanSourceArgs),
+    /// Watch a filesystem source and index changes as they happen
+    Watch(WatchSourceArgs),
 }
 
 #[derive(Debug, Args)]
@@ -31,11 +49,35 @@ pub struct AddSourceArgs {
     /// The name of the source
     pub name: String,
 
+    /// How often to reindex the source automatically, e.g. "30m", "6h", or "1d"
+    #[clap(long, value_parser = parse_interval)]
+    pub index_interval: Option<i64>,
+
     #[clap(subcommand)]
     /// The type of the source, and additional information specific to each source
     pub source_type: SourceTypeArgs,
 }
 
+/// Parse an interval like "90", "45m", "12h", or "7d" into seconds.
+fn parse_interval(value: &str) -> Result<i64, String> {
+    let value = value.trim();
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((i, 's')) => (&value[..i], 1),
+        Some((i, 'm')) => (&value[..i], 60),
+        Some((i, 'h')) => (&value[..i], 60 * 60),
+        Some((i, 'd')) => (&value[..i], 24 * 60 * 60),
+        _ => (value, 1),
+    };
+
+    number
+        .trim()
+        .parse::<i64>()
+        .ok()
+        .filter(|n| *n > 0)
+        .map(|n| n * multiplier)
+        .ok_or_else(|| format!("Invalid interval {value}"))
+}
+
 #[derive(Debug, Subcommand)]
 pub enum SourceTypeArgs {
     /// A filesystem scanner
@@ -43,6 +85,34 @@ pub enum SourceTypeArgs {
     /// Read items from the browser history
     BrowserHistory(BrowserHistorySourceTypeArgs),
     Bookmarks(BookmarksSourceTypeArgs),
+    /// Read items from the Firefox history, given the profile directory
+    FirefoxHistory(BrowserHistorySourceTypeArgs),
+    /// Read items from the Firefox bookmarks, given the profile directory
+    FirefoxBookmarks(FirefoxBookmarksSourceTypeArgs),
+    /// Read items from a bookmarks.html file exported from a browser or bookmarking service
+    BookmarksFile(BookmarksFileSourceTypeArgs),
+    /// Read email from an mbox file or a Maildir directory
+    Email(EmailSourceTypeArgs),
+    /// Read notes from an Obsidian or Logseq vault
+    Vault(VaultSourceTypeArgs),
+    /// Read commands from a shell history file
+    ShellHistory(ShellHistorySourceTypeArgs),
+    /// Read entries from RSS and Atom feeds
+    Feeds(FeedsSourceTypeArgs),
+    /// Read commit messages and files from a git repository
+    Git(GitSourceTypeArgs),
+    /// Read pages saved in WARC, SingleFile, or MHTML archives
+    WebArchive(WebArchiveSourceTypeArgs),
+    /// Read messages from a Slack export zip or DiscordChatExporter JSON files
+    Chat(ChatSourceTypeArgs),
+    /// Read posts, bookmarks, and likes from a Mastodon or Twitter/X archive
+    SocialArchive(SocialArchiveSourceTypeArgs),
+    /// Read highlights and notes from Kindle, Readwise, or Hypothesis exports
+    Highlights(HighlightsSourceTypeArgs),
+    /// Read notes from Evernote ENEX exports
+    Evernote(EvernoteSourceTypeArgs),
+    /// Read pages and database rows from a Notion export
+    Notion(NotionSourceTypeArgs),
 }
 
 #[derive(Debug, Args)]
@@ -64,6 +134,9 @@ pub struct BrowserHistorySourceTypeArgs {
     /// Domains that should be skipped.
     #[clap(long)]
     pub skip: Vec<String>,
+
+    #[clap(flatten)]
+    pub fetch: FetchArgs,
 }
 
 #[derive(Debug, Args)]
@@ -76,12 +149,224 @@ pub struct BookmarksSourceTypeArgs {
     /// Domains that should be skipped.
     #[clap(long)]
     pub skip: Vec<String>,
+
+    #[clap(flatten)]
+    pub fetch: FetchArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct FirefoxBookmarksSourceTypeArgs {
+    /// The Firefox profile directory
+    pub location: String,
+
+    /// Domains that should be skipped.
+    #[clap(long)]
+    pub skip: Vec<String>,
+
+    /// Tag each bookmark with the names of the folders that contain it
+    #[clap(long)]
+    pub folder_tags: bool,
+
+    #[clap(flatten)]
+    pub fetch: FetchArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct BookmarksFileSourceTypeArgs {
+    /// The path to the bookmarks file
+    pub location: String,
+
+    /// Domains that should be skipped.
+    #[clap(long)]
+    pub skip: Vec<String>,
+
+    /// Tag each bookmark with the names of the folders that contain it
+    #[clap(long)]
+    pub folder_tags: bool,
+
+    #[clap(flatten)]
+    pub fetch: FetchArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct EmailSourceTypeArgs {
+    /// The path to the mbox file or Maildir directory
+    pub location: String,
+}
+
+#[derive(Debug, Args)]
+pub struct VaultSourceTypeArgs {
+    /// The vault directory
+    pub location: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ShellHistorySourceTypeArgs {
+    /// The shell that wrote the history file
+    #[clap(value_enum)]
+    pub shell: Shell,
+
+    /// The path to the history file. Defaults to the shell's usual location.
+    pub location: Option<String>,
+
+    /// Commands run more than this many minutes apart go into separate sessions
+    #[clap(long, default_value_t = 30)]
+    pub session_gap: u32,
+}
+
+#[derive(Debug, Args)]
+pub struct FeedsSourceTypeArgs {
+    /// The URLs of the feeds
+    pub urls: Vec<String>,
+
+    /// An OPML file listing feeds to read, as exported by feed readers
+    #[clap(long)]
+    pub opml: Option<String>,
+
+    /// Domains that should be skipped.
+    #[clap(long)]
+    pub skip: Vec<String>,
+
+    #[clap(flatten)]
+    pub fetch: FetchArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct GitSourceTypeArgs {
+    /// The path to the repository
+    pub location: String,
+
+    /// The ref whose commits should be read. Defaults to HEAD.
+    #[clap(long)]
+    pub commits_ref: Option<String>,
+
+    /// Also read the files at this ref, such as a branch or tag name
+    #[clap(long)]
+    pub files_ref: Option<String>,
+
+    /// Only read files matching this glob. Can be given more than once.
+    #[clap(long = "glob", requires("files_ref"))]
+    pub globs: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct WebArchiveSourceTypeArgs {
+    /// An archive file, or a directory containing archives
+    pub location: String,
+
+    /// Domains that should be skipped.
+    #[clap(long)]
+    pub skip: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ChatSourceTypeArgs {
+    /// The chat service that the export came from
+    #[clap(value_enum)]
+    pub platform: ChatPlatform,
+
+    /// The Slack export zip, or a DiscordChatExporter JSON file or directory of them
+    pub location: String,
+
+    /// Messages sent more than this many minutes apart go into separate conversations
+    #[clap(long, default_value_t = 30)]
+    pub conversation_gap: u32,
+
+    /// The URL of the Slack workspace, like https://example.slack.com, for linking to messages
+    #[clap(long)]
+    pub workspace_url: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct SocialArchiveSourceTypeArgs {
+    /// The service that the archive came from
+    #[clap(value_enum)]
+    pub platform: SocialPlatform,
+
+    /// The archive zip, or the directory it was extracted to
+    pub location: String,
+
+    /// Fetch the pages linked from posts, and bookmarked posts that the archive doesn't include
+    #[clap(long)]
+    pub fetch_links: bool,
+
+    /// Domains that should be skipped.
+    #[clap(long)]
+    pub skip: Vec<String>,
+
+    #[clap(flatten)]
+    pub fetch: FetchArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct HighlightsSourceTypeArgs {
+    /// The format of the export
+    #[clap(value_enum)]
+    pub format: HighlightsFormat,
+
+    /// The export file, or a directory of Readwise Markdown files
+    pub location: String,
+}
+
+#[derive(Debug, Args)]
+pub struct EvernoteSourceTypeArgs {
+    /// An ENEX file, or a directory of them
+    pub location: String,
+}
+
+#[derive(Debug, Args)]
+pub struct NotionSourceTypeArgs {
+    /// The export zip, or the directory it was extracted to
+    pub location: String,
+}
+
+#[derive(Debug, Args)]
+pub struct FetchArgs {
+    /// The most requests to make at once to a single host
+    #[clap(long, default_value_t = FetchConfig::default().max_per_host)]
+    pub max_per_host: usize,
+
+    /// The minimum time between requests to the same host, in milliseconds
+    #[clap(long, default_value_t = FetchConfig::default().host_delay_ms)]
+    pub host_delay_ms: u64,
+
+    /// Fetch pages even if the site's robots.txt disallows it
+    #[clap(long)]
+    pub ignore_robots_txt: bool,
+}
+
+impl From<FetchArgs> for FetchConfig {
+    fn from(args: FetchArgs) -> Self {
+        FetchConfig {
+            max_per_host: args.max_per_host,
+            host_delay_ms: args.host_delay_ms,
+            respect_robots_txt: !args.ignore_robots_txt,
+        }
+    }
 }
 
 #[derive(Debug, Args)]
 pub struct EditSourceArgs {
     /// The name of the source
     pub name: String,
+
+    /// How often to reindex the source automatically, e.g. "30m", "6h", or "1d"
+    #[clap(long, value_parser = parse_interval)]
+    pub index_interval: Option<i64>,
+
+    /// Only reindex the source manually
+    #[clap(long, conflicts_with("index_interval"))]
+    pub no_index_interval: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct SourceErrorsArgs {
+    /// The name of the source
+    pub name: String,
+
+    /// Only show errors of this kind
+    #[clap(long, value_enum)]
+    pub kind: Option<ItemErrorKind>,
 }
 
 #[derive(Debug, Args)]
@@ -106,50 +391,63 @@ pub struct ScanSourceArgs {
     /// Reindex all items, even if they haven't changed.
     #[clap(short, long, conflicts_with("by_content"))]
     pub force: bool,
+    /// Write the events from the scan to this file, as one line of JSON per event.
+    #[clap(long)]
+    pub event_log: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct WatchSourceArgs {
+    /// The name of the source
+    pub name: String,
 }
 
 pub fn handle_source_command(state: &mut AppState, cmd: SourceArgs) -> eyre::Result<()> {
     match cmd.command {
         SourceCommand::Add(args) => add_source(state, args),
-        SourceCommand::Edit(args) => Err(eyre!("Not implemented yet")),
+        SourceCommand::Edit(args) => edit_source(state, args),
+        SourceCommand::Errors(args) => source_errors(state, args),
         SourceCommand::RebuildSearch(args) => rebuild_search(state, args),
         SourceCommand::Reprocess(args) => reprocess_source(state, args),
         SourceCommand::Scan(args) => scan_source(state, args),
+        SourceCommand::Watch(args) => watch_source(state, args),
     }
 }
 
 fn add_source(state: &mut AppState, args: AddSourceArgs) -> eyre::Result<()> {
-    match args.source_type {
-        SourceTypeArgs::Fs(cmdargs) => add_fs_source(state, args.name, cmdargs),
-        SourceTypeArgs::BrowserHistory(cmdargs) => {
-            add_browser_history_source(state, args.name, cmdargs)
-        }
-        SourceTypeArgs::Bookmarks(cmdargs) => add_bookmarks_source(state, args.name, cmdargs),
-    }
-}
-
-fn add_fs_source(state: &mut AppState, name: String, args: FsSourceTypeArgs) -> eyre::Result<()> {
-    let now = OffsetDateTime::now_utc();
-    let location = shellexpand::tilde(&args.location).into_owned();
-    let is_dir = std::fs::metadata(Path::new(&location))
-        .map(|m| m.is_dir())
-        .unwrap_or(false);
-
-    if !is_dir {
-        return Err(eyre!("Location must be a directory"));
-    }
+    let (location, config) = match args.source_type {
+        SourceTypeArgs::Fs(cmdargs) => fs_source_config(cmdargs)?,
+        SourceTypeArgs::BrowserHistory(cmdargs) => browser_history_source_config(cmdargs)?,
+        SourceTypeArgs::Bookmarks(cmdargs) => bookmarks_source_config(cmdargs)?,
+        SourceTypeArgs::FirefoxHistory(cmdargs) => firefox_history_source_config(cmdargs)?,
+        SourceTypeArgs::FirefoxBookmarks(cmdargs) => firefox_bookmarks_source_config(cmdargs)?,
+        SourceTypeArgs::BookmarksFile(cmdargs) => bookmarks_file_source_config(cmdargs)?,
+        SourceTypeArgs::Email(cmdargs) => email_source_config(cmdargs)?,
+        SourceTypeArgs::Vault(cmdargs) => vault_source_config(cmdargs)?,
+        SourceTypeArgs::ShellHistory(cmdargs) => shell_history_source_config(cmdargs)?,
+        SourceTypeArgs::Feeds(cmdargs) => feeds_source_config(cmdargs)?,
+        SourceTypeArgs::Git(cmdargs) => git_source_config(cmdargs)?,
+        SourceTypeArgs::WebArchive(cmdargs) => web_archive_source_config(cmdargs)?,
+        SourceTypeArgs::Chat(cmdargs) => chat_source_config(cmdargs)?,
+        SourceTypeArgs::SocialArchive(cmdargs) => social_archive_source_config(cmdargs)?,
+        SourceTypeArgs::Highlights(cmdargs) => highlights_source_config(cmdargs)?,
+        SourceTypeArgs::Evernote(cmdargs) => evernote_source_config(cmdargs)?,
+        SourceTypeArgs::Notion(cmdargs) => notion_source_config(cmdargs)?,
+    };
 
     let source = Source {
         id: 0, // filled in by add_source
-        name,
+        name: args.name,
         location,
-        config: SourceConfig::Fs(FsSourceConfig { globs: args.globs }),
+        config,
         compare_strategy: perceive_core::sources::ItemCompareStrategy::MTimeAndContent,
         status: perceive_core::sources::SourceStatus::Indexing {
-            started_at: now.unix_timestamp(),
+            started_at: OffsetDateTime::now_utc().unix_timestamp(),
         },
-        last_indexed: now,
+        // The source has never been indexed.
+        last_indexed: OffsetDateTime::UNIX_EPOCH,
         index_version: 0,
+        index_interval: args.index_interval,
     };
 
     let source = perceive_core::sources::db::add_source(&state.database, source)?;
@@ -157,12 +455,23 @@ fn add_fs_source(state: &mut AppState, name: String, args: FsSourceTypeArgs) ->
     Ok(())
 }
 
-fn add_browser_history_source(
-    state: &mut AppState,
-    name: String,
+fn fs_source_config(args: FsSourceTypeArgs) -> eyre::Result<(String, SourceConfig)> {
+    let location = shellexpand::tilde(&args.location).into_owned();
+    let is_dir = std::fs::metadata(Path::new(&location))
+        .map(|m| m.is_dir())
+        .unwrap_or(false);
+
+    if !is_dir {
+        return Err(eyre!("Location must be a directory"));
+    }
+
+    let config = SourceConfig::Fs(FsSourceConfig { globs: args.globs });
+    Ok((location, config))
+}
+
+fn browser_history_source_config(
     args: BrowserHistorySourceTypeArgs,
-) -> eyre::Result<()> {
-    let now = OffsetDateTime::now_utc();
+) -> eyre::Result<(String, SourceConfig)> {
     let location = shellexpand::tilde(&args.location).into_owned();
     let has_history = std::fs::metadata(Path::new(&location).join("History"))
         .map(|m| m.is_file())
@@ -174,84 +483,312 @@ fn add_browser_history_source(
         ));
     }
 
-    let source = Source {
-        id: 0, // filled in by add_source
-        name,
+    let config = SourceConfig::ChromiumHistory(ChromiumHistoryConfig {
+        skip: args.skip,
+        fetch: args.fetch.into(),
+    });
+    Ok((location, config))
+}
+
+fn firefox_history_source_config(
+    args: BrowserHistorySourceTypeArgs,
+) -> eyre::Result<(String, SourceConfig)> {
+    let location = firefox_profile_location(&args.location)?;
+    let config = SourceConfig::FirefoxHistory(FirefoxHistoryConfig {
+        skip: args.skip,
+        fetch: args.fetch.into(),
+    });
+    Ok((location, config))
+}
+
+fn firefox_bookmarks_source_config(
+    args: FirefoxBookmarksSourceTypeArgs,
+) -> eyre::Result<(String, SourceConfig)> {
+    let location = firefox_profile_location(&args.location)?;
+    let config = SourceConfig::FirefoxBookmarks(FirefoxBookmarksConfig {
+        skip: args.skip,
+        folder_tags: args.folder_tags,
+        fetch: args.fetch.into(),
+    });
+    Ok((location, config))
+}
+
+fn bookmarks_file_source_config(
+    args: BookmarksFileSourceTypeArgs,
+) -> eyre::Result<(String, SourceConfig)> {
+    let location = shellexpand::tilde(&args.location).into_owned();
+    let is_file = std::fs::metadata(Path::new(&location))
+        .map(|m| m.is_file())
+        .unwrap_or(false);
+
+    if !is_file {
+        return Err(eyre!("Location must be a bookmarks file"));
+    }
+
+    let config = SourceConfig::NetscapeBookmarks(NetscapeBookmarksConfig {
+        skip: args.skip,
+        folder_tags: args.folder_tags,
+        fetch: args.fetch.into(),
+    });
+    Ok((location, config))
+}
+
+fn email_source_config(args: EmailSourceTypeArgs) -> eyre::Result<(String, SourceConfig)> {
+    let location = shellexpand::tilde(&args.location).into_owned();
+    if !Path::new(&location).exists() {
+        return Err(eyre!(
+            "Location must be an mbox file or a Maildir directory"
+        ));
+    }
+
+    Ok((location, SourceConfig::Email(EmailConfig::default())))
+}
+
+fn vault_source_config(args: VaultSourceTypeArgs) -> eyre::Result<(String, SourceConfig)> {
+    let location = shellexpand::tilde(&args.location).into_owned();
+    let is_dir = std::fs::metadata(Path::new(&location))
+        .map(|m| m.is_dir())
+        .unwrap_or(false);
+
+    if !is_dir {
+        return Err(eyre!("Location must be a directory"));
+    }
+
+    Ok((location, SourceConfig::Vault(VaultConfig::default())))
+}
+
+fn shell_history_source_config(
+    args: ShellHistorySourceTypeArgs,
+) -> eyre::Result<(String, SourceConfig)> {
+    let location = args
+        .location
+        .as_deref()
+        .unwrap_or_else(|| args.shell.default_history_path());
+    let location = shellexpand::tilde(location).into_owned();
+    let is_file = std::fs::metadata(Path::new(&location))
+        .map(|m| m.is_file())
+        .unwrap_or(false);
+
+    if !is_file {
+        return Err(eyre!("Could not find history file {location}"));
+    }
+
+    Ok((
         location,
-        config: SourceConfig::ChromiumHistory(ChromiumHistoryConfig { skip: args.skip }),
-        compare_strategy: perceive_core::sources::ItemCompareStrategy::MTimeAndContent,
-        status: perceive_core::sources::SourceStatus::Indexing {
-            started_at: now.unix_timestamp(),
-        },
-        last_indexed: now,
-        index_version: 0,
+        SourceConfig::ShellHistory(ShellHistoryConfig {
+            shell: args.shell,
+            session_gap: args.session_gap,
+        }),
+    ))
+}
+
+fn feeds_source_config(args: FeedsSourceTypeArgs) -> eyre::Result<(String, SourceConfig)> {
+    let location = args
+        .opml
+        .map(|opml| shellexpand::tilde(&opml).into_owned())
+        .unwrap_or_default();
+
+    if location.is_empty() && args.urls.is_empty() {
+        return Err(eyre!("Provide at least one feed URL or an OPML file"));
+    }
+
+    if !location.is_empty() && !Path::new(&location).is_file() {
+        return Err(eyre!("OPML file {location} does not exist"));
+    }
+
+    let config = SourceConfig::Feeds(FeedsConfig {
+        feeds: args.urls,
+        skip: args.skip,
+        fetch: args.fetch.into(),
+    });
+    Ok((location, config))
+}
+
+fn git_source_config(args: GitSourceTypeArgs) -> eyre::Result<(String, SourceConfig)> {
+    let location = shellexpand::tilde(&args.location).into_owned();
+    let is_dir = std::fs::metadata(Path::new(&location))
+        .map(|m| m.is_dir())
+        .unwrap_or(false);
+
+    if !is_dir {
+        return Err(eyre!("Location must be a git repository"));
+    }
+
+    let config = SourceConfig::Git(GitConfig {
+        commits_ref: args.commits_ref,
+        files_ref: args.files_ref,
+        globs: args.globs,
+    });
+    Ok((location, config))
+}
+
+fn web_archive_source_config(
+    args: WebArchiveSourceTypeArgs,
+) -> eyre::Result<(String, SourceConfig)> {
+    let location = shellexpand::tilde(&args.location).into_owned();
+    if !Path::new(&location).exists() {
+        return Err(eyre!("Location must be an archive file or a directory"));
+    }
+
+    let config = SourceConfig::WebArchive(WebArchiveConfig { skip: args.skip });
+    Ok((location, config))
+}
+
+fn chat_source_config(args: ChatSourceTypeArgs) -> eyre::Result<(String, SourceConfig)> {
+    let location = shellexpand::tilde(&args.location).into_owned();
+    let valid = match args.platform {
+        ChatPlatform::Slack => Path::new(&location).is_file(),
+        ChatPlatform::Discord => Path::new(&location).exists(),
     };
 
-    let source = perceive_core::sources::db::add_source(&state.database, source)?;
-    state.sources.push(source);
-    Ok(())
+    if !valid {
+        return Err(eyre!(
+            "Location must be a Slack export zip, or a DiscordChatExporter JSON file or directory"
+        ));
+    }
+
+    let config = SourceConfig::Chat(ChatConfig {
+        platform: args.platform,
+        conversation_gap: args.conversation_gap,
+        workspace_url: args.workspace_url,
+    });
+    Ok((location, config))
+}
+
+fn social_archive_source_config(
+    args: SocialArchiveSourceTypeArgs,
+) -> eyre::Result<(String, SourceConfig)> {
+    let location = shellexpand::tilde(&args.location).into_owned();
+    if !Path::new(&location).exists() {
+        return Err(eyre!("Location must be an archive zip or a directory"));
+    }
+
+    let config = SourceConfig::SocialArchive(SocialArchiveConfig {
+        platform: args.platform,
+        fetch_links: args.fetch_links,
+        skip: args.skip,
+        fetch: args.fetch.into(),
+    });
+    Ok((location, config))
+}
+
+fn highlights_source_config(
+    args: HighlightsSourceTypeArgs,
+) -> eyre::Result<(String, SourceConfig)> {
+    let location = shellexpand::tilde(&args.location).into_owned();
+    let valid = match args.format {
+        HighlightsFormat::Readwise => Path::new(&location).exists(),
+        HighlightsFormat::Kindle | HighlightsFormat::Hypothesis => Path::new(&location).is_file(),
+    };
+
+    if !valid {
+        return Err(eyre!(
+            "Location must be an export file, or a directory of Readwise Markdown files"
+        ));
+    }
+
+    let config = SourceConfig::Highlights(HighlightsConfig {
+        format: args.format,
+    });
+    Ok((location, config))
 }
 
-fn add_bookmarks_source(
-    state: &mut AppState,
-    name: String,
-    args: BookmarksSourceTypeArgs,
-) -> eyre::Result<()> {
-    let now = OffsetDateTime::now_utc();
+fn evernote_source_config(args: EvernoteSourceTypeArgs) -> eyre::Result<(String, SourceConfig)> {
     let location = shellexpand::tilde(&args.location).into_owned();
-    let has_history = std::fs::metadata(Path::new(&location).join("Bookmarks"))
+    if !Path::new(&location).exists() {
+        return Err(eyre!("Location must be an ENEX file or a directory"));
+    }
+
+    Ok((location, SourceConfig::Evernote(EvernoteConfig::default())))
+}
+
+fn notion_source_config(args: NotionSourceTypeArgs) -> eyre::Result<(String, SourceConfig)> {
+    let location = shellexpand::tilde(&args.location).into_owned();
+    if !Path::new(&location).exists() {
+        return Err(eyre!("Location must be an export zip or a directory"));
+    }
+
+    Ok((location, SourceConfig::Notion(NotionConfig::default())))
+}
+
+fn firefox_profile_location(location: &str) -> eyre::Result<String> {
+    let location = shellexpand::tilde(location).into_owned();
+    let has_places = std::fs::metadata(Path::new(&location).join("places.sqlite"))
         .map(|m| m.is_file())
         .unwrap_or(false);
 
-    if !has_history {
+    if !has_places {
+        return Err(eyre!(
+            "Location must be a Firefox profile directory containing a places.sqlite file"
+        ));
+    }
+
+    Ok(location)
+}
+
+fn bookmarks_source_config(args: BookmarksSourceTypeArgs) -> eyre::Result<(String, SourceConfig)> {
+    let location = shellexpand::tilde(&args.location).into_owned();
+    let has_bookmarks = std::fs::metadata(Path::new(&location).join("Bookmarks"))
+        .map(|m| m.is_file())
+        .unwrap_or(false);
+
+    if !has_bookmarks {
         return Err(eyre!(
             "Location must be a directory containing a Bookmarks file"
         ));
     }
 
-    let source = Source {
-        id: 0, // filled in by add_source
-        name,
-        location,
-        config: SourceConfig::ChromiumBookmarks(ChromiumBookmarksConfig { skip: args.skip }),
-        compare_strategy: perceive_core::sources::ItemCompareStrategy::MTimeAndContent,
-        status: perceive_core::sources::SourceStatus::Indexing {
-            started_at: now.unix_timestamp(),
-        },
-        last_indexed: now,
-        index_version: 0,
-    };
+    let config = SourceConfig::ChromiumBookmarks(ChromiumBookmarksConfig {
+        skip: args.skip,
+        fetch: args.fetch.into(),
+    });
+    Ok((location, config))
+}
 
-    let source = perceive_core::sources::db::add_source(&state.database, source)?;
-    state.sources.push(source);
+fn edit_source(state: &mut AppState, args: EditSourceArgs) -> eyre::Result<()> {
+    let source = state
+        .sources
+        .iter_mut()
+        .find(|s| s.name == args.name)
+        .ok_or_else(|| eyre!("Source not found"))?;
+
+    if args.no_index_interval {
+        source.index_interval = None;
+    } else if let Some(interval) = args.index_interval {
+        source.index_interval = Some(interval);
+    }
+
+    update_source(&state.database, source)?;
     Ok(())
 }
 
-fn scan_source(state: &mut AppState, args: ScanSourceArgs) -> eyre::Result<()> {
+pub(crate) fn scan_source(state: &mut AppState, args: ScanSourceArgs) -> eyre::Result<()> {
     let source_pos = state
         .sources
         .iter_mut()
         .position(|s| s.name == args.name)
         .ok_or_else(|| eyre!("Source not found"))?;
 
-    let now = OffsetDateTime::now_utc();
-
-    {
-        let source = &mut state.sources[source_pos];
-        source.index_version += 1;
-        source.last_indexed = now;
-        source.status = perceive_core::sources::SourceStatus::Indexing {
-            started_at: now.unix_timestamp(),
-        };
-    }
-
-    update_source(&state.database, &state.sources[source_pos])?;
-
     let times = ScanStats::default();
     let start_time = std::time::Instant::now();
+    let cancel = ActiveCancel::begin();
+
+    let events = times.events.subscribe();
+    let event_log = args
+        .event_log
+        .as_ref()
+        .map(|path| Ok::<_, std::io::Error>((times.events.subscribe(), File::create(path)?)))
+        .transpose()?;
+
+    let result = std::thread::scope(|scope| {
+        if let Some((log_events, file)) = event_log {
+            scope.spawn(move || {
+                if let Err(e) = write_event_log(log_events, BufWriter::new(file)) {
+                    eprintln!("Failed to write event log: {e}");
+                }
+            });
+        }
 
-    let done = AtomicBool::new(false);
-    std::thread::scope(|scope| {
         scope.spawn(|| {
             let scanned_progress = ProgressBar::new_spinner();
 
@@ -264,16 +801,31 @@ fn scan_source(state: &mut AppState, args: ScanSourceArgs) -> eyre::Result<()> {
                 let added = times.added.load(std::sync::atomic::Ordering::Relaxed);
                 let changed = times.changed.load(std::sync::atomic::Ordering::Relaxed);
                 let unchanged = times.unchanged.load(std::sync::atomic::Ordering::Relaxed);
+                let removed = times.removed.load(std::sync::atomic::Ordering::Relaxed);
+                let stale = times.stale.load(std::sync::atomic::Ordering::Relaxed);
+                let errored = times.errored.load(std::sync::atomic::Ordering::Relaxed);
 
                 scanned_progress.set_message(format!(
-                    "Scanned: {scanned} Fetched: {fetched} Fetching: {reading} Encoding: {embedding} Encoded: {encoded} Added: {added} Changed: {changed} Unchanged: {unchanged}",
+                    "Scanned: {scanned} Fetched: {fetched} Fetching: {reading} Encoding: {embedding} Encoded: {encoded} Added: {added} Changed: {changed} Unchanged: {unchanged} Removed: {removed} Stale: {stale} Errors: {errored}",
                 ));
             };
 
-            while !done.load(std::sync::atomic::Ordering::Relaxed) {
+            loop {
+                match events.recv_timeout(Duration::from_millis(100)) {
+                    Ok(ScanEvent::StageFinished {
+                        stage, duration_ms, ..
+                    }) => {
+                        scanned_progress.println(format!(
+                            "{stage:?} stage finished in {:.1} seconds",
+                            duration_ms as f64 / 1000.0
+                        ));
+                    }
+                    Ok(_) | Err(flume::RecvTimeoutError::Timeout) => {}
+                    Err(flume::RecvTimeoutError::Disconnected) => break,
+                }
+
                 update_progress();
                 scanned_progress.tick();
-                std::thread::sleep(std::time::Duration::from_millis(100));
             }
 
             update_progress();
@@ -288,32 +840,124 @@ fn scan_source(state: &mut AppState, args: ScanSourceArgs) -> eyre::Result<()> {
             None
         };
 
-        let result = perceive_core::sources::scan_source(
+        let result = index_source(
             &times,
             &state.database,
             &state.model,
             state.model_id,
             state.model_version,
-            &state.sources[source_pos],
+            &mut state.sources[source_pos],
             compare_strategy,
+            cancel.token(),
         );
 
-        done.store(true, std::sync::atomic::Ordering::Relaxed);
+        // This ends the progress and log threads.
+        times.events.close();
         result
     })?;
 
-    let source = &mut state.sources[source_pos];
-    source.status = perceive_core::sources::SourceStatus::Ready {
-        scanned: times.scanned.load(std::sync::atomic::Ordering::Relaxed) as u32,
-        duration: (OffsetDateTime::now_utc().unix_timestamp() - now.unix_timestamp()) as u32,
-    };
-    update_source(&state.database, source)?;
+    if result.cancelled {
+        println!("Scan cancelled. Scan the source again to resume where it left off.");
+    } else if !result.completed {
+        println!("Scan did not complete, so removed items were not checked");
+    }
 
     println!("Finished in {} seconds", start_time.elapsed().as_secs());
 
     rebuild_search(state, RebuildSearchArgs { name: args.name })
 }
 
+fn source_errors(state: &mut AppState, args: SourceErrorsArgs) -> Result<()> {
+    let source = state
+        .sources
+        .iter()
+        .find(|s| s.name == args.name)
+        .ok_or_else(|| eyre!("Source not found"))?;
+
+    let errors = list_item_errors(&state.database, source.id, args.kind)?;
+    if errors.is_empty() {
+        println!("No errors");
+        return Ok(());
+    }
+
+    for error in &errors {
+        let next_retry = error
+            .next_retry_at
+            .map(|t| format!(", next retry {}", format_time(t)))
+            .unwrap_or_default();
+        println!(
+            "{} - {} (attempts: {}, last seen {}{next_retry})\n  {}",
+            error.kind,
+            error.external_id,
+            error.attempts,
+            format_time(error.last_seen),
+            error.message
+        );
+    }
+
+    if args.kind.is_none() {
+        let counts = count_item_errors(&state.database, source.id)?
+            .into_iter()
+            .map(|(kind, count)| format!("{kind}: {count}"))
+            .collect::<Vec<_>>();
+        println!("\n{}", counts.join(", "));
+    }
+
+    Ok(())
+}
+
+fn format_time(t: OffsetDateTime) -> String {
+    format!("{} {:02}:{:02}", t.date(), t.hour(), t.minute())
+}
+
+fn watch_source(state: &mut AppState, args: WatchSourceArgs) -> Result<()> {
+    let source = state
+        .sources
+        .iter()
+        .find(|s| s.name == args.name)
+        .ok_or_else(|| eyre!("Source not found"))?;
+
+    let times = ScanStats::default();
+    let cancel = ActiveCancel::begin();
+
+    let done = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            let progress = ProgressBar::new_spinner();
+
+            while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                let scanned = times.scanned.load(std::sync::atomic::Ordering::Relaxed);
+                let added = times.added.load(std::sync::atomic::Ordering::Relaxed);
+                let changed = times.changed.load(std::sync::atomic::Ordering::Relaxed);
+                let removed = times.removed.load(std::sync::atomic::Ordering::Relaxed);
+
+                progress.set_message(format!(
+                    "Watching {}... Seen: {scanned} Added: {added} Changed: {changed} Removed: {removed}",
+                    source.name
+                ));
+                progress.tick();
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+
+            progress.finish();
+        });
+
+        let result = perceive_core::sources::pipeline::watch_source(
+            &times,
+            &state.database,
+            &state.model,
+            state.model_id,
+            state.model_version,
+            source,
+            || Some(&state.searcher),
+            cancel.token(),
+        );
+
+        done.store(true, std::sync::atomic::Ordering::Relaxed);
+        result
+    })
+}
+
 fn rebuild_search(state: &mut AppState, args: RebuildSearchArgs) -> Result<()> {
     let source = state
         .sources
@@ -349,6 +993,7 @@ fn reprocess_source(state: &mut AppState, args: ReprocessArgs) -> Result<()> {
         .ok_or_else(|| eyre!("Source not found"))?;
 
     let times = ScanStats::default();
+    let cancel = ActiveCancel::begin();
 
     let done = AtomicBool::new(false);
     std::thread::scope(|scope| {
@@ -387,6 +1032,7 @@ fn reprocess_source(state: &mut AppState, args: ReprocessArgs) -> Result<()> {
             state.model_id,
             state.model_version,
             &state.sources[source_pos],
+            cancel.token(),
         );
         done.store(true, std::sync::atomic::Ordering::Relaxed);
 
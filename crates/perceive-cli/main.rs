@@ -1,3 +1,4 @@
+mod cancel;
 mod cmd;
 mod repl;
 mod state;
@@ -23,6 +24,7 @@ fn main() -> Result<()> {
     color_eyre::install().unwrap();
 
     let args = Args::parse();
+    cancel::install_handler()?;
     let mut state = AppState::new(args.database.clone())?;
 
     match args.command {
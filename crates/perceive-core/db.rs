@@ -7,7 +7,7 @@ use rusqlite::{params, Connection};
 use thiserror::Error;
 use time::OffsetDateTime;
 
-use crate::{paths::PROJECT_DIRS, Item, ItemMetadata};
+use crate::{paths::PROJECT_DIRS, HttpCache, Item, ItemMetadata};
 
 #[derive(Debug, Error)]
 pub enum DbError {
@@ -102,6 +102,14 @@ impl DatabaseInner {
             rusqlite_migration::M::up(include_str!("./migrations/00001_init.sql")),
             rusqlite_migration::M::up(include_str!("./migrations/00002_tags.sql")),
             rusqlite_migration::M::up(include_str!("./migrations/00003_model_7.sql")),
+            rusqlite_migration::M::up(include_str!("./migrations/00004_stale_items.sql")),
+            rusqlite_migration::M::up(include_str!("./migrations/00005_item_passages.sql")),
+            rusqlite_migration::M::up(include_str!("./migrations/00006_item_errors.sql")),
+            rusqlite_migration::M::up(include_str!("./migrations/00007_item_error_retry.sql")),
+            rusqlite_migration::M::up(include_str!("./migrations/00008_http_cache.sql")),
+            rusqlite_migration::M::up(include_str!("./migrations/00009_item_links.sql")),
+            rusqlite_migration::M::up(include_str!("./migrations/00010_item_created.sql")),
+            rusqlite_migration::M::up(include_str!("./migrations/00011_source_index_version.sql")),
         ]);
 
         migrations.to_latest(conn)?;
@@ -144,10 +152,23 @@ impl DatabaseInner {
 pub const ITEM_COLUMNS: &str = r##"id, source_id,
             external_id, hash, content, raw_content, process_version,
             name, author, description,
-            modified, last_accessed, skipped"##;
+            modified, last_accessed, skipped,
+            etag, last_modified, expires_at, created"##;
 
 /// Deserialize a row selected by `ITEM_COLUMNS` into an `Item`.
 pub fn deserialize_item_row(row: &rusqlite::Row) -> Result<Item> {
+    let http_cache = HttpCache {
+        etag: row.get(13)?,
+        last_modified: row.get(14)?,
+        expires_at: row
+            .get::<_, Option<i64>>(15)?
+            .map(OffsetDateTime::from_unix_timestamp)
+            .transpose()?,
+    };
+    let has_http_cache = http_cache.etag.is_some()
+        || http_cache.last_modified.is_some()
+        || http_cache.expires_at.is_some();
+
     Ok(Item {
         id: row.get(0)?,
         source_id: row.get(1)?,
@@ -168,6 +189,13 @@ pub fn deserialize_item_row(row: &rusqlite::Row) -> Result<Item> {
                 .get::<_, Option<i64>>(11)?
                 .map(OffsetDateTime::from_unix_timestamp)
                 .transpose()?,
+            created: row
+                .get::<_, Option<i64>>(16)?
+                .map(OffsetDateTime::from_unix_timestamp)
+                .transpose()?,
+            http_cache: has_http_cache.then_some(http_cache),
+            tags: None,
+            links: None,
         },
         skipped: row
             .get_ref(12)?
@@ -1,9 +1,12 @@
 pub mod batch_sender;
+pub mod cancel;
 pub mod db;
+pub mod links;
 pub mod model;
 pub mod paths;
 pub mod search;
 pub mod sources;
+pub mod tags;
 pub mod time_tracker;
 
 use serde::{Deserialize, Serialize};
@@ -18,9 +21,27 @@ pub struct ItemMetadata {
     pub description: Option<String>,
     pub mtime: Option<OffsetDateTime>,
     pub atime: Option<OffsetDateTime>,
+    /// When the item was created, for sources that record it
+    pub created: Option<OffsetDateTime>,
+    /// Caching headers, for items fetched over HTTP
+    pub http_cache: Option<HttpCache>,
+    /// Tags from the source. When this is set, it replaces the item's tags.
+    pub tags: Option<Vec<String>>,
+    /// The external IDs of other items in the same source that this item links to. When this is
+    /// set, it replaces the item's links.
+    pub links: Option<Vec<String>>,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Display, EnumString, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct HttpCache {
+    pub etag: Option<String>,
+    /// The Last-Modified header, exactly as the server sent it
+    pub last_modified: Option<String>,
+    /// When the content stops being fresh, from the Cache-Control or Expires headers
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Display, EnumString, Deserialize)]
 #[strum(serialize_all = "snake_case")]
 pub enum SkipReason {
     NotFound,
@@ -32,6 +53,11 @@ pub enum SkipReason {
     /// were redirected to a login page.
     Redirected,
     NoContent,
+    /// The item has never been read successfully because of errors, and will be tried again
+    /// later.
+    Retrying,
+    /// The site's robots.txt does not allow us to fetch the item.
+    Disallowed,
 }
 
 impl SkipReason {
@@ -42,6 +68,9 @@ impl SkipReason {
             SkipReason::Unauthorized => true,
             SkipReason::Redirected => true,
             SkipReason::NoContent => false,
+            SkipReason::Retrying => false,
+            // Check again in case the robots.txt changes.
+            SkipReason::Disallowed => false,
         }
     }
 }
@@ -0,0 +1,85 @@
+use rusqlite::{params, Connection};
+
+use crate::db::{Database, DbError};
+
+/// An item at the other end of a link.
+#[derive(Debug, Clone)]
+pub struct LinkedItem {
+    pub id: i64,
+    pub external_id: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Get the items that link to an item.
+pub fn backlinks(database: &Database, item_id: i64) -> Result<Vec<LinkedItem>, DbError> {
+    let conn = database.read_pool.get()?;
+    let mut stmt = conn.prepare_cached(
+        r##"SELECT from_item.id, from_item.external_id, from_item.name, from_item.description
+        FROM items target
+        JOIN item_links ON item_links.target_external_id = target.external_id
+        JOIN items from_item ON from_item.id = item_links.item_id
+            AND from_item.source_id = target.source_id
+        WHERE target.id = ?
+        ORDER BY from_item.name"##,
+    )?;
+
+    let rows = stmt
+        .query_map([item_id], |row| {
+            Ok(LinkedItem {
+                id: row.get(0)?,
+                external_id: row.get(1)?,
+                name: row.get(2)?,
+                description: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(rows)
+}
+
+/// Get the items that an item links to. Links to items that haven't been indexed are left out.
+pub fn outgoing_links(database: &Database, item_id: i64) -> Result<Vec<LinkedItem>, DbError> {
+    let conn = database.read_pool.get()?;
+    let mut stmt = conn.prepare_cached(
+        r##"SELECT target.id, target.external_id, target.name, target.description
+        FROM items from_item
+        JOIN item_links ON item_links.item_id = from_item.id
+        JOIN items target ON target.external_id = item_links.target_external_id
+            AND target.source_id = from_item.source_id
+        WHERE from_item.id = ?
+        ORDER BY target.name"##,
+    )?;
+
+    let rows = stmt
+        .query_map([item_id], |row| {
+            Ok(LinkedItem {
+                id: row.get(0)?,
+                external_id: row.get(1)?,
+                name: row.get(2)?,
+                description: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(rows)
+}
+
+/// Replace an item's links with links to the given external IDs.
+pub(crate) fn set_item_links(
+    conn: &Connection,
+    item_id: i64,
+    targets: &[String],
+) -> Result<(), rusqlite::Error> {
+    let mut clear_stmt = conn.prepare_cached("DELETE FROM item_links WHERE item_id = ?")?;
+    let mut add_stmt = conn.prepare_cached(
+        "INSERT OR IGNORE INTO item_links (item_id, target_external_id) VALUES (?, ?)",
+    )?;
+
+    clear_stmt.execute([item_id])?;
+    for target in targets {
+        add_stmt.execute(params![item_id, target])?;
+    }
+
+    Ok(())
+}
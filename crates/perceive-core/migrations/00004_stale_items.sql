@@ -0,0 +1,3 @@
+-- Set when an item was not found in the latest scan of a source that keeps removed items
+-- around instead of deleting them.
+ALTER TABLE items ADD COLUMN stale_at BIGINT;
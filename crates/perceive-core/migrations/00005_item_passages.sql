@@ -0,0 +1,28 @@
+-- Embeddings for overlapping passages of each item, so that long items can be matched
+-- by any part of their content.
+CREATE TABLE item_passages (
+  -- The search index can't remove passages, so IDs must never be reused for a different passage.
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  item_id BIGINT NOT NULL REFERENCES items(id) ON DELETE CASCADE,
+  model_id INT NOT NULL,
+  model_version INT NOT NULL,
+  passage_index INT NOT NULL,
+  -- The byte range of the passage within the item's content
+  start_offset BIGINT NOT NULL,
+  end_offset BIGINT NOT NULL,
+  item_index_version BIGINT NOT NULL,
+  embedding BLOB NOT NULL,
+  FOREIGN KEY(model_id, model_version) REFERENCES model_versions(model_id, version) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX item_passages_item_idx
+  ON item_passages(model_id, model_version, item_id, passage_index);
+
+-- Existing embeddings were calculated from the start of the entire item, so carry them over
+-- as a single passage until the item is encoded again.
+INSERT INTO item_passages (item_id, model_id, model_version, passage_index, start_offset,
+    end_offset, item_index_version, embedding)
+  SELECT ie.item_id, ie.model_id, ie.model_version, 0, 0, length(CAST(items.content AS BLOB)),
+    ie.item_index_version, ie.embedding
+  FROM item_embeddings ie
+  JOIN items ON items.id = ie.item_id;
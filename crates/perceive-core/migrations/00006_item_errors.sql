@@ -0,0 +1,16 @@
+-- Errors encountered while reading items from a source. Keyed by external ID since items that
+-- fail on their first read never make it into the items table.
+CREATE TABLE item_errors (
+  source_id INTEGER NOT NULL REFERENCES sources(id) ON DELETE CASCADE,
+  external_id TEXT NOT NULL,
+  -- ItemErrorKind
+  kind TEXT NOT NULL,
+  message TEXT NOT NULL,
+  -- How many scans in a row have failed to read this item
+  attempts INTEGER NOT NULL DEFAULT 1,
+  first_seen BIGINT NOT NULL,
+  last_seen BIGINT NOT NULL,
+  PRIMARY KEY (source_id, external_id)
+);
+
+CREATE INDEX item_errors_source_kind_idx ON item_errors(source_id, kind);
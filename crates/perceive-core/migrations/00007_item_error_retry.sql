@@ -0,0 +1,3 @@
+-- When an item that failed with a transient error should next be tried again.
+-- NULL for errors that won't go away by retrying.
+ALTER TABLE item_errors ADD COLUMN next_retry_at BIGINT;
@@ -0,0 +1,8 @@
+-- HTTP validators and freshness for items fetched from the web.
+ALTER TABLE items ADD COLUMN etag TEXT;
+ALTER TABLE items ADD COLUMN last_modified TEXT;
+-- When the fetched content stops being fresh, according to Cache-Control or Expires.
+ALTER TABLE items ADD COLUMN expires_at BIGINT;
+
+-- The ETag used to be stored in the hash column, which is only set for web items.
+UPDATE items SET etag = hash, hash = '' WHERE hash <> '';
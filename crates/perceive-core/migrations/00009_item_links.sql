@@ -0,0 +1,10 @@
+-- Links from an item to other items in the same source, such as wikilinks between notes.
+-- The target is stored by external ID so that a link can point to an item that hasn't been
+-- indexed yet.
+CREATE TABLE item_links (
+  item_id BIGINT NOT NULL REFERENCES items(id) ON DELETE CASCADE DEFERRABLE,
+  target_external_id TEXT NOT NULL,
+  PRIMARY KEY (item_id, target_external_id)
+);
+
+CREATE INDEX item_links_target_idx ON item_links(target_external_id);
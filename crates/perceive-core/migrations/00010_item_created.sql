@@ -0,0 +1,2 @@
+-- When the item was created, for sources that record it separately from the modified time.
+ALTER TABLE items ADD COLUMN created BIGINT;
@@ -0,0 +1,8 @@
+-- Older versions didn't save the index version after a scan, so bring it up to date with the
+-- items. Otherwise the next scan would think it was resuming a scan at the items' version and
+-- skip all of them.
+UPDATE sources
+SET index_version = MAX(
+  index_version,
+  COALESCE((SELECT MAX(version) FROM items WHERE items.source_id = sources.id), 0)
+);
@@ -2,6 +2,7 @@
 // https://github.com/guillaume-be/rust-bert/blob/master/src/pipelines/sentence_embeddings/pipeline.rs,
 // with some modifications to work with longer text passages.
 
+mod chunk;
 mod configs;
 mod highlight;
 pub mod tokenize;
@@ -0,0 +1,240 @@
+use std::ops::Range;
+
+use rust_tokenizers::TokenizedInput;
+
+use super::Model;
+
+/// Room left in each passage for its text to tokenize a little differently once it's split
+/// from the rest of the document.
+const PASSAGE_MARGIN_TOKENS: usize = 8;
+
+/// Figure out how many tokens of a passage's header to keep, and how many tokens of content fit
+/// after it. `header_tokens` includes the model's special tokens. Long headers are cut down to a
+/// quarter of the sequence so that there's still room for the content.
+fn passage_budget(max_seq_length: usize, header_tokens: usize) -> (usize, usize) {
+    let header_tokens = header_tokens.min(max_seq_length / 4);
+    let passage_size = max_seq_length
+        .saturating_sub(header_tokens + PASSAGE_MARGIN_TOKENS)
+        .max(1);
+    (header_tokens, passage_size)
+}
+
+/// Split a tokenized document into overlapping windows of tokens, i.e. with a chunk size of 10 and
+/// overlap of 2, the windows will be 0..10, 8..18, 16..26.
+///
+/// Special tokens are trimmed from each window, and windows with fewer than `min_chunk_size` tokens
+/// remaining are dropped.
+pub(super) fn chunk_token_ranges(
+    tokens: &TokenizedInput,
+    chunk_size: usize,
+    chunk_overlap: usize,
+    min_chunk_size: usize,
+) -> Vec<Range<usize>> {
+    let chunk_index_inc = chunk_size - chunk_overlap;
+    let num_tokens = tokens.token_ids.len();
+    let mut chunks = Vec::with_capacity(num_tokens / chunk_index_inc + 1);
+
+    let mut i = 0;
+    while i + chunk_overlap < num_tokens {
+        let start_index = i;
+        let end_index = std::cmp::min(i + chunk_size, num_tokens);
+        let special_tokens_mask = &tokens.special_tokens_mask[start_index..end_index];
+
+        // Find the longest consecutive sequence of non-special tokens
+        let mut longest_start = 0;
+        let mut longest_length = 0;
+        let mut current_start = 0;
+        let mut current_length = 0;
+
+        for (index, &is_special) in special_tokens_mask.iter().enumerate() {
+            if is_special == 0 {
+                current_length += 1;
+            } else {
+                if current_length > longest_length {
+                    longest_start = current_start;
+                    longest_length = current_length;
+                }
+
+                // The run starts after this special token, so that it's not included in the
+                // window.
+                current_start = index + 1;
+                current_length = 0;
+            }
+        }
+
+        if current_length > longest_length {
+            longest_start = current_start;
+            longest_length = current_length;
+        }
+
+        let start_index = start_index + longest_start;
+        let end_index = std::cmp::min(start_index + longest_length, end_index);
+
+        if end_index - start_index >= min_chunk_size {
+            chunks.push(start_index..end_index);
+        }
+
+        i += chunk_index_inc;
+    }
+
+    chunks
+}
+
+/// Find the byte range of the text in `doc` that produced a range of tokens.
+pub(super) fn token_range_to_text_range(
+    doc: &str,
+    tokens: &TokenizedInput,
+    token_range: Range<usize>,
+) -> Option<Range<usize>> {
+    // The token offsets are in characters, not bytes.
+    let (text_start, text_end) = tokens.token_offsets[token_range]
+        .iter()
+        .filter_map(|o| o.as_ref())
+        .fold(None, |acc, offset| match acc {
+            None => Some((offset.begin, offset.end)),
+            Some((begin, end)) => Some((
+                std::cmp::min(begin, offset.begin),
+                std::cmp::max(end, offset.end),
+            )),
+        })?;
+
+    let char_to_byte = |char_index: u32| {
+        doc.char_indices()
+            .nth(char_index as usize)
+            .map(|c| c.0)
+            .unwrap_or(doc.len())
+    };
+
+    Some(char_to_byte(text_start)..char_to_byte(text_end))
+}
+
+impl Model {
+    /// Split a document into overlapping passages that fit into the model's maximum sequence
+    /// length once `header` is prepended to each one. Returns the length in bytes of the part of
+    /// the header that fits, and the byte range of each passage within the document. A document
+    /// with any content has at least one passage.
+    pub fn passages(&self, header: &str, document: &str) -> (usize, Vec<Range<usize>>) {
+        let tokenized = self.tokenizer.encode_list(
+            &[header, document],
+            // Don't truncate here since we're chunking below.
+            1_000_000,
+            &self.tokenizer_truncation_strategy,
+            0,
+        );
+        let (header_tokens, tokens) = (&tokenized[0], &tokenized[1]);
+
+        let (header_size, passage_size) = passage_budget(
+            self.sentence_bert_config.max_seq_length,
+            header_tokens.token_ids.len(),
+        );
+        let header_len = if header_size < header_tokens.token_ids.len() {
+            // Leave room for the special token at the end.
+            token_range_to_text_range(header, header_tokens, 0..header_size.saturating_sub(1))
+                .map_or(0, |range| range.end)
+        } else {
+            header.len()
+        };
+
+        let passage_overlap = passage_size / 8;
+        let mut passages = chunk_token_ranges(tokens, passage_size, passage_overlap, 1)
+            .into_iter()
+            .filter_map(|range| token_range_to_text_range(document, tokens, range))
+            .collect::<Vec<_>>();
+
+        if passages.is_empty() && !document.trim().is_empty() {
+            // Very short documents may not produce a full chunk.
+            passages.push(0..document.len());
+        }
+
+        (header_len, passages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_tokenizers::{Offset, TokenizedInput};
+
+    use super::*;
+
+    /// Tokenize each word of `text` as one token, between `[CLS]` and `[SEP]` special tokens.
+    fn tokenize(text: &str) -> TokenizedInput {
+        let mut offsets = vec![None];
+        let mut position = 0;
+        for word in text.split(' ') {
+            let begin = position as u32;
+            let end = begin + word.chars().count() as u32;
+            offsets.push(Some(Offset::new(begin, end)));
+            position = end as usize + 1;
+        }
+        offsets.push(None);
+
+        let special_tokens_mask = offsets
+            .iter()
+            .map(|o| if o.is_some() { 0 } else { 1 })
+            .collect::<Vec<_>>();
+
+        TokenizedInput {
+            token_ids: (0..offsets.len() as i64).collect(),
+            segment_ids: vec![0; offsets.len()],
+            special_tokens_mask,
+            overflowing_tokens: Vec::new(),
+            num_truncated_tokens: 0,
+            token_offsets: offsets,
+            reference_offsets: Vec::new(),
+            mask: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn overlapping_windows() {
+        let tokens = tokenize("a b c d e f g h i j k l m n o p q r s t");
+        assert_eq!(tokens.token_ids.len(), 22);
+
+        let ranges = chunk_token_ranges(&tokens, 10, 2, 1);
+        assert_eq!(ranges, vec![1..10, 8..18, 16..21]);
+    }
+
+    #[test]
+    fn special_tokens_excluded() {
+        // The whole document fits in one window, which should hold every word but not the
+        // special tokens around them.
+        let tokens = tokenize("a b c");
+        assert_eq!(chunk_token_ranges(&tokens, 10, 2, 1), vec![1..4]);
+    }
+
+    #[test]
+    fn short_windows_dropped() {
+        let tokens = tokenize("a b c d e f g h i j k l");
+        // The last window only has 5 words in it.
+        assert_eq!(chunk_token_ranges(&tokens, 10, 2, 6), vec![1..10]);
+        assert_eq!(chunk_token_ranges(&tokens, 10, 2, 1), vec![1..10, 8..13]);
+    }
+
+    #[test]
+    fn passage_sizes() {
+        assert_eq!(passage_budget(256, 2), (2, 256 - 2 - PASSAGE_MARGIN_TOKENS));
+        assert_eq!(
+            passage_budget(256, 40),
+            (40, 256 - 40 - PASSAGE_MARGIN_TOKENS)
+        );
+        // A very long title doesn't push the content out of the passage.
+        assert_eq!(
+            passage_budget(256, 500),
+            (64, 256 - 64 - PASSAGE_MARGIN_TOKENS)
+        );
+    }
+
+    #[test]
+    fn text_range() {
+        let doc = "héllo wörld again";
+        let tokens = tokenize(doc);
+
+        let range = token_range_to_text_range(doc, &tokens, 1..3).unwrap();
+        assert_eq!(&doc[range], "héllo wörld");
+
+        let range = token_range_to_text_range(doc, &tokens, 2..5).unwrap();
+        assert_eq!(&doc[range], "wörld again");
+
+        assert_eq!(token_range_to_text_range(doc, &tokens, 0..1), None);
+    }
+}
@@ -1,8 +1,9 @@
-use std::rc::Rc;
+use std::{ops::Range, rc::Rc};
 
-use ahash::HashSet;
+use ahash::{HashMap, HashSet};
+use parking_lot::RwLock;
 use rayon::prelude::*;
-use rusqlite::Connection;
+use rusqlite::{params, Connection};
 use time::OffsetDateTime;
 
 use crate::{
@@ -15,15 +16,46 @@ use crate::{
 // https://github.com/rust-ndarray/ndarray#how-to-enable-blas-integration
 extern crate blas_src;
 
+/// Searches return this many passages for each requested result, since an item may match
+/// with multiple passages.
+const PASSAGES_PER_RESULT: usize = 4;
+
 #[derive(Debug, Copy, Clone)]
 pub struct SearchItem {
     pub id: i64,
     pub score: f32,
+    /// The passage of the item that best matched the search.
+    pub passage: Passage,
+}
+
+/// The location of a passage within an item.
+#[derive(Debug, Copy, Clone)]
+pub struct Passage {
+    pub item_id: i64,
+    /// The position of the passage in the item.
+    pub index: u32,
+    /// The byte offset in the item's content where the passage starts.
+    pub start: usize,
+    /// The byte offset in the item's content where the passage ends.
+    pub end: usize,
+}
+
+impl Passage {
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
 }
 
 struct SourceSearch {
     id: i64,
+    /// The index of passage embeddings, keyed by passage ID.
     hnsw: hnsw_rs::hnsw::Hnsw<f32, NdArrayDistance>,
+    /// The passages that are currently live in the index. The HNSW index doesn't support
+    /// deletion, so passages that are replaced or removed are dropped from here instead, and
+    /// filtered out of the results.
+    passages: RwLock<HashMap<usize, Passage>>,
+    /// Items that were removed from the source after the search was built.
+    removed: RwLock<HashSet<i64>>,
 }
 
 pub struct Searcher {
@@ -78,6 +110,68 @@ impl Searcher {
         Ok(())
     }
 
+    /// Remove items from the search results without rebuilding the source's index.
+    pub fn remove_items(&self, source_id: i64, ids: &[i64]) {
+        if let Some(source) = self.sources.iter().find(|s| s.id == source_id) {
+            source.removed.write().extend(ids.iter().copied());
+        }
+    }
+
+    /// Load the latest passages for the given items from the database, and add them to the
+    /// source's index in place of the passages that were there before.
+    pub fn update_items(
+        &self,
+        database: &Database,
+        source_id: i64,
+        model_id: u32,
+        model_version: u32,
+        ids: &[i64],
+    ) -> Result<(), eyre::Report> {
+        let Some(source) = self.sources.iter().find(|s| s.id == source_id) else {
+            return Ok(());
+        };
+
+        let conn = database.read_pool.get()?;
+        let mut stmt = conn.prepare_cached(
+            r##"SELECT ip.id, passage_index, start_offset, end_offset, embedding
+            FROM item_passages ip
+            JOIN items ON items.id = ip.item_id
+            WHERE ip.item_id = ? AND model_id = ? AND model_version = ?
+                AND skipped IS NULL AND hidden_at IS NULL"##,
+        )?;
+
+        for &item_id in ids {
+            let rows = stmt
+                .query_and_then(params![item_id, model_id, model_version], |row| {
+                    let value: (usize, Passage, Vec<f32>) = (
+                        row.get(0)?,
+                        Passage {
+                            item_id,
+                            index: row.get(1)?,
+                            start: row.get(2)?,
+                            end: row.get(3)?,
+                        },
+                        deserialize_embedding(row.get_ref(4)?.as_blob().map_err(DbError::query)?),
+                    );
+
+                    Ok::<_, DbError>(value)
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut passages = source.passages.write();
+            passages.retain(|_, passage| passage.item_id != item_id);
+            for (passage_id, passage, embedding) in rows {
+                source.hnsw.insert_slice((&embedding, passage_id));
+                passages.insert(passage_id, passage);
+            }
+            drop(passages);
+
+            source.removed.write().remove(&item_id);
+        }
+
+        Ok(())
+    }
+
     fn build_sources(
         conn: &Connection,
         model_id: u32,
@@ -85,29 +179,36 @@ impl Searcher {
         sources: &[i64],
     ) -> Result<Vec<SourceSearch>, eyre::Report> {
         let mut stmt = conn.prepare(
-            r##"SELECT items.id, source_id, embedding
+            r##"SELECT ip.id, items.id, source_id, passage_index, start_offset, end_offset, embedding
         FROM items
-        JOIN item_embeddings ie ON model_id=? AND model_version=? AND ie.item_id=items.id
+        JOIN item_passages ip ON model_id=? AND model_version=? AND ip.item_id=items.id
         WHERE skipped IS NULL AND hidden_at IS NULL"##,
         )?;
 
         let rows = stmt
             .query_and_then([model_id, model_version], |row| {
-                let value: (usize, i64, Vec<f32>) = (
+                let item_id = row.get(1)?;
+                let value: (usize, i64, Passage, Vec<f32>) = (
                     row.get(0)?,
-                    row.get(1)?,
-                    deserialize_embedding(row.get_ref(2)?.as_blob().map_err(DbError::query)?),
+                    row.get(2)?,
+                    Passage {
+                        item_id,
+                        index: row.get(3)?,
+                        start: row.get(4)?,
+                        end: row.get(5)?,
+                    },
+                    deserialize_embedding(row.get_ref(6)?.as_blob().map_err(DbError::query)?),
                 );
 
                 Ok::<_, DbError>(value)
             })?
             .map(|row| {
-                let row = row?;
+                let (passage_id, source_id, passage, embedding) = row?;
 
                 Ok(sources
                     .iter()
-                    .position(|&s| s == row.1)
-                    .map(|source_idx| (row.0, source_idx, row.2)))
+                    .position(|&s| s == source_id)
+                    .map(|source_idx| (passage_id, source_idx, passage, embedding)))
             })
             .filter_map(|r| r.transpose())
             .collect::<Result<Vec<_>, eyre::Report>>()?;
@@ -116,7 +217,7 @@ impl Searcher {
             .par_iter()
             .fold(
                 || vec![0; sources.len()],
-                |mut items_per_source, (_, source_idx, _)| {
+                |mut items_per_source, (_, source_idx, _, _)| {
                     items_per_source[*source_idx] += 1;
                     items_per_source
                 },
@@ -135,17 +236,30 @@ impl Searcher {
             .iter()
             .zip(items_per_source.into_iter())
             .map(|(&id, num_elements)| {
-                let num_layers = 16.min((num_elements as f32).ln().trunc() as usize);
+                let num_layers = ((num_elements as f32).ln().trunc() as usize).clamp(1, 16);
                 let hnsw =
                     hnsw_rs::hnsw::Hnsw::new(64, num_elements, num_layers, 800, NdArrayDistance {});
 
-                SourceSearch { id, hnsw }
+                SourceSearch {
+                    id,
+                    hnsw,
+                    passages: RwLock::new(HashMap::default()),
+                    removed: RwLock::new(HashSet::default()),
+                }
             })
             .collect::<Vec<_>>();
 
-        rows.into_par_iter().for_each(|(id, source_idx, point)| {
-            sources[source_idx].hnsw.insert_slice((&point, id));
-        });
+        for (passage_id, source_idx, passage, _) in &rows {
+            sources[*source_idx]
+                .passages
+                .get_mut()
+                .insert(*passage_id, *passage);
+        }
+
+        rows.into_par_iter()
+            .for_each(|(passage_id, source_idx, _, point)| {
+                sources[source_idx].hnsw.insert_slice((&point, passage_id));
+            });
 
         for source in sources.iter_mut() {
             source.hnsw.set_searching_mode(true);
@@ -154,9 +268,12 @@ impl Searcher {
         Ok(sources)
     }
 
+    /// Find the items closest to `vector`. If `only_items` is given, results are limited to
+    /// those items.
     pub fn search_vector(
         &self,
         sources: &[i64],
+        only_items: Option<&HashSet<i64>>,
         num_results: usize,
         vector: Vec<f32>,
     ) -> Vec<SearchItem> {
@@ -165,18 +282,56 @@ impl Searcher {
             .par_iter()
             .filter(|source| sources.contains(&source.id))
             .flat_map_iter(|source| {
-                source
-                    .hnsw
-                    .search(&vector, num_results, 24)
-                    .into_iter()
-                    .map(|n| SearchItem {
-                        id: n.d_id as i64,
-                        score: n.distance,
-                    })
+                let passages = source.passages.read();
+                let removed = source.removed.read();
+
+                // Filtered out passages still take up places in the nearest neighbors, so when
+                // few items are allowed, keep widening the search until enough of them are
+                // found or the whole index has been searched.
+                let mut num_neighbors = num_results * PASSAGES_PER_RESULT;
+                loop {
+                    let neighbors =
+                        source
+                            .hnsw
+                            .search(&vector, num_neighbors, num_neighbors.max(24));
+                    let exhausted = neighbors.len() < num_neighbors;
+
+                    let results = neighbors
+                        .into_iter()
+                        .filter_map(|n| {
+                            let passage = passages.get(&n.d_id)?;
+                            if removed.contains(&passage.item_id) {
+                                return None;
+                            }
+
+                            if only_items.map_or(false, |only| !only.contains(&passage.item_id)) {
+                                return None;
+                            }
+
+                            Some(SearchItem {
+                                id: passage.item_id,
+                                score: n.distance,
+                                passage: *passage,
+                            })
+                        })
+                        .collect::<Vec<_>>();
+
+                    let num_items = results.iter().map(|r| r.id).collect::<HashSet<_>>().len();
+                    if exhausted || num_items >= num_results {
+                        break results;
+                    }
+
+                    num_neighbors *= 4;
+                }
             })
             .collect::<Vec<_>>();
 
         results.sort_unstable_by(|a, b| a.score.partial_cmp(&b.score).unwrap());
+
+        // Rank each item by its best passage.
+        let mut seen = HashSet::default();
+        results.retain(|result| seen.insert(result.id));
+
         results.truncate(num_results);
         results
     }
@@ -189,17 +344,18 @@ impl Searcher {
         query: &str,
     ) -> Vec<SearchItem> {
         let term_embedding = encode_query(model, query);
-        self.search_vector(sources, num_results, term_embedding)
+        self.search_vector(sources, None, num_results, term_embedding)
     }
 
     pub fn search_vector_and_retrieve(
         &self,
         database: &Database,
         sources: &[i64],
+        only_items: Option<&HashSet<i64>>,
         num_results: usize,
         vector: Vec<f32>,
     ) -> Result<Vec<(Item, SearchItem)>, DbError> {
-        let items = self.search_vector(sources, num_results, vector);
+        let items = self.search_vector(sources, only_items, num_results, vector);
 
         let values = items
             .iter()
@@ -208,7 +364,7 @@ impl Searcher {
 
         let conn = database.read_pool.get()?;
         let mut stmt = conn.prepare_cached(
-            r##"SELECT id, source_id, external_id, content, name, author, description, modified, last_accessed
+            r##"SELECT id, source_id, external_id, content, name, author, description, modified, last_accessed, created
             FROM items WHERE skipped is NULL AND hidden_at IS NULL AND id IN rarray(?)"##)?;
 
         let mut rows = stmt
@@ -232,6 +388,12 @@ impl Searcher {
                         atime: row
                             .get::<_, Option<i64>>(8)?
                             .map(|t| OffsetDateTime::from_unix_timestamp(t).unwrap()),
+                        created: row
+                            .get::<_, Option<i64>>(9)?
+                            .map(|t| OffsetDateTime::from_unix_timestamp(t).unwrap()),
+                        http_cache: None,
+                        tags: None,
+                        links: None,
                     },
                 })
             })?
@@ -255,7 +417,7 @@ impl Searcher {
         query: &str,
     ) -> Result<Vec<(Item, SearchItem)>, DbError> {
         let vector = encode_query(model, query);
-        self.search_vector_and_retrieve(database, sources, num_results, vector)
+        self.search_vector_and_retrieve(database, sources, None, num_results, vector)
     }
 }
 
@@ -1,21 +1,73 @@
+mod chat;
 mod chromium_bookmarks;
 #[cfg(feature = "browser-history")]
 mod chromium_history;
 pub mod db;
+#[cfg(feature = "email")]
+mod email;
+#[cfg(feature = "evernote")]
+mod evernote;
+pub mod extract;
+#[cfg(feature = "feeds")]
+mod feeds;
+#[cfg(feature = "browser-history")]
+mod firefox_bookmarks;
+#[cfg(feature = "browser-history")]
+mod firefox_history;
 mod fs;
+#[cfg(feature = "git")]
+mod git;
+#[cfg(feature = "highlights")]
+mod highlights;
+pub mod item_errors;
+#[cfg(feature = "browser-history")]
+mod netscape_bookmarks;
+#[cfg(feature = "notion")]
+mod notion;
 pub mod parse_html;
 pub mod pipeline;
+mod robots;
+pub mod scheduler;
+mod shell_history;
+#[cfg(feature = "social")]
+mod social;
+mod timeline;
+mod vault;
+#[cfg(feature = "web-archive")]
+mod web_archive;
+pub mod web_fetcher;
 
+pub use chat::{ChatConfig, ChatPlatform};
+#[cfg(feature = "email")]
+pub use email::EmailConfig;
+#[cfg(feature = "evernote")]
+pub use evernote::EvernoteConfig;
+#[cfg(feature = "feeds")]
+pub use feeds::FeedsConfig;
 pub use fs::FsSourceConfig;
+#[cfg(feature = "git")]
+pub use git::GitConfig;
+#[cfg(feature = "highlights")]
+pub use highlights::{HighlightsConfig, HighlightsFormat};
+#[cfg(feature = "notion")]
+pub use notion::NotionConfig;
 pub use pipeline::scan_source;
 use serde::{Deserialize, Serialize};
+pub use shell_history::{Shell, ShellHistoryConfig};
+#[cfg(feature = "social")]
+pub use social::{SocialArchiveConfig, SocialPlatform};
 use strum::{Display, EnumString};
 use time::OffsetDateTime;
+pub use vault::VaultConfig;
+#[cfg(feature = "web-archive")]
+pub use web_archive::WebArchiveConfig;
 
 use self::pipeline::SourceScanner;
 #[cfg(feature = "browser-history")]
 pub use self::{
     chromium_bookmarks::ChromiumBookmarksConfig, chromium_history::ChromiumHistoryConfig,
+    firefox_bookmarks::FirefoxBookmarksConfig, firefox_history::FirefoxHistoryConfig,
+    netscape_bookmarks::NetscapeBookmarksConfig,
 };
 
 #[derive(Debug, Copy, Clone, strum::EnumString)]
@@ -38,25 +90,134 @@ pub enum SourceConfig {
     ChromiumHistory(ChromiumHistoryConfig),
     #[cfg(feature = "browser-history")]
     ChromiumBookmarks(ChromiumBookmarksConfig),
+    #[cfg(feature = "browser-history")]
+    FirefoxHistory(FirefoxHistoryConfig),
+    #[cfg(feature = "browser-history")]
+    FirefoxBookmarks(FirefoxBookmarksConfig),
+    /// A bookmarks.html file, as exported by browsers and bookmarking services
+    #[cfg(feature = "browser-history")]
+    NetscapeBookmarks(NetscapeBookmarksConfig),
+    /// An mbox file or a Maildir directory
+    #[cfg(feature = "email")]
+    Email(EmailConfig),
+    /// An Obsidian or Logseq vault
+    Vault(VaultConfig),
+    /// A zsh, bash, or fish history file
+    ShellHistory(ShellHistoryConfig),
+    /// RSS and Atom feeds, from a list of URLs or an OPML file
+    #[cfg(feature = "feeds")]
+    Feeds(FeedsConfig),
+    /// Commit messages and files from a git repository
+    #[cfg(feature = "git")]
+    Git(GitConfig),
+    /// Pages saved in WARC, SingleFile, or MHTML archives
+    #[cfg(feature = "web-archive")]
+    WebArchive(WebArchiveConfig),
+    /// A Slack workspace export or DiscordChatExporter JSON
+    Chat(ChatConfig),
+    /// A Mastodon or Twitter/X account archive
+    #[cfg(feature = "social")]
+    SocialArchive(SocialArchiveConfig),
+    /// Highlights and notes from Kindle, Readwise, or Hypothesis exports
+    #[cfg(feature = "highlights")]
+    Highlights(HighlightsConfig),
+    /// Notes exported from Evernote as ENEX files
+    #[cfg(feature = "evernote")]
+    Evernote(EvernoteConfig),
+    /// Pages and databases from a Notion export zip
+    #[cfg(feature = "notion")]
+    Notion(NotionConfig),
 }
 
 impl SourceConfig {
     pub fn matches_tag(&self, tag: SourceTypeTag) -> bool {
         match (self, tag) {
-            (Self::Fs(_), SourceTypeTag::Local) => true,
+            (
+                Self::Fs(_) | Self::Vault(_) | Self::ShellHistory(_) | Self::Chat(_),
+                SourceTypeTag::Local,
+            ) => true,
             #[cfg(feature = "browser-history")]
             (Self::ChromiumHistory(_), SourceTypeTag::Web) => true,
             #[cfg(feature = "browser-history")]
             (Self::ChromiumBookmarks(_), SourceTypeTag::Web | SourceTypeTag::Bookmarks) => true,
+            #[cfg(feature = "browser-history")]
+            (Self::FirefoxHistory(_), SourceTypeTag::Web) => true,
+            #[cfg(feature = "browser-history")]
+            (Self::FirefoxBookmarks(_), SourceTypeTag::Web | SourceTypeTag::Bookmarks) => true,
+            #[cfg(feature = "browser-history")]
+            (Self::NetscapeBookmarks(_), SourceTypeTag::Web | SourceTypeTag::Bookmarks) => true,
+            #[cfg(feature = "email")]
+            (Self::Email(_), SourceTypeTag::Local) => true,
+            #[cfg(feature = "feeds")]
+            (Self::Feeds(_), SourceTypeTag::Web) => true,
+            #[cfg(feature = "git")]
+            (Self::Git(_), SourceTypeTag::Local) => true,
+            #[cfg(feature = "web-archive")]
+            (Self::WebArchive(_), SourceTypeTag::Local | SourceTypeTag::Web) => true,
+            #[cfg(feature = "social")]
+            (Self::SocialArchive(_), SourceTypeTag::Local | SourceTypeTag::Bookmarks) => true,
+            #[cfg(feature = "highlights")]
+            (Self::Highlights(_), SourceTypeTag::Local) => true,
+            #[cfg(feature = "evernote")]
+            (Self::Evernote(_), SourceTypeTag::Local) => true,
+            #[cfg(feature = "notion")]
+            (Self::Notion(_), SourceTypeTag::Local) => true,
             _ => false,
         }
     }
+
+    /// How to handle items that were indexed previously, but did not show up in the latest scan.
+    pub fn removed_item_policy(&self) -> RemovedItemPolicy {
+        match self {
+            Self::Fs(_) | Self::Vault(_) => RemovedItemPolicy::Delete,
+            // Shells drop old commands once the history file reaches its size limit.
+            Self::ShellHistory(_) => RemovedItemPolicy::MarkStale,
+            // Exports can cover a limited date range, so conversations missing from a newer
+            // export may still exist.
+            Self::Chat(_) => RemovedItemPolicy::MarkStale,
+            #[cfg(feature = "social")]
+            Self::SocialArchive(_) => RemovedItemPolicy::Delete,
+            #[cfg(feature = "highlights")]
+            Self::Highlights(_) => RemovedItemPolicy::Delete,
+            #[cfg(feature = "evernote")]
+            Self::Evernote(_) => RemovedItemPolicy::Delete,
+            #[cfg(feature = "notion")]
+            Self::Notion(_) => RemovedItemPolicy::Delete,
+            // Browsers expire old history entries, but the pages are still worth searching.
+            #[cfg(feature = "browser-history")]
+            Self::ChromiumHistory(_) | Self::FirefoxHistory(_) => RemovedItemPolicy::MarkStale,
+            #[cfg(feature = "browser-history")]
+            Self::ChromiumBookmarks(_) | Self::FirefoxBookmarks(_) | Self::NetscapeBookmarks(_) => {
+                RemovedItemPolicy::Delete
+            }
+            #[cfg(feature = "email")]
+            Self::Email(_) => RemovedItemPolicy::Delete,
+            // Feeds only list their most recent entries.
+            #[cfg(feature = "feeds")]
+            Self::Feeds(_) => RemovedItemPolicy::MarkStale,
+            #[cfg(feature = "git")]
+            Self::Git(_) => RemovedItemPolicy::Delete,
+            #[cfg(feature = "web-archive")]
+            Self::WebArchive(_) => RemovedItemPolicy::Delete,
+        }
+    }
+}
+
+/// What to do with items that are no longer present in a source.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RemovedItemPolicy {
+    /// Delete the item and its embeddings.
+    Delete,
+    /// Keep the item searchable, but mark it as stale.
+    MarkStale,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "status", rename_all = "snake_case")]
 pub enum SourceStatus {
     Indexing { started_at: i64 },
+    // The last scan was cancelled before it finished. The next scan will resume it.
+    Interrupted { started_at: i64, scanned: u32 },
     Ready { scanned: u32, duration: u32 },
     Error { error: String },
 }
@@ -104,9 +265,17 @@ pub struct Source {
     pub status: SourceStatus,
     pub last_indexed: OffsetDateTime,
     pub index_version: i64,
+    /// How often to reindex the source, in seconds. If None, the source is only indexed manually.
+    pub index_interval: Option<i64>,
 }
 
 impl Source {
+    /// When the source should next be indexed, for sources that are indexed automatically.
+    pub fn next_index_time(&self) -> Option<OffsetDateTime> {
+        self.index_interval
+            .map(|interval| self.last_indexed + time::Duration::seconds(interval))
+    }
+
     fn create_scanner(&self) -> Result<Box<dyn SourceScanner>, eyre::Report> {
         let scanner: Box<dyn SourceScanner> = match &self.config {
             SourceConfig::Fs(config) => Box::new(fs::FileScanner {
@@ -130,6 +299,93 @@ impl Source {
                     config.clone(),
                 )?)
             }
+            #[cfg(feature = "browser-history")]
+            SourceConfig::FirefoxBookmarks(config) => {
+                Box::new(firefox_bookmarks::FirefoxBookmarksScanner::new(
+                    self.id,
+                    self.location.clone(),
+                    config.clone(),
+                )?)
+            }
+            #[cfg(feature = "browser-history")]
+            SourceConfig::NetscapeBookmarks(config) => {
+                Box::new(netscape_bookmarks::NetscapeBookmarksScanner::new(
+                    self.id,
+                    self.location.clone(),
+                    config.clone(),
+                )?)
+            }
+            #[cfg(feature = "browser-history")]
+            SourceConfig::FirefoxHistory(config) => {
+                Box::new(firefox_history::FirefoxHistoryScanner::new(
+                    self.id,
+                    self.location.clone(),
+                    config.clone(),
+                )?)
+            }
+            #[cfg(feature = "email")]
+            SourceConfig::Email(config) => Box::new(email::EmailScanner {
+                source_id: self.id,
+                location: self.location.clone(),
+                config: config.clone(),
+            }),
+            SourceConfig::Vault(config) => Box::new(vault::VaultScanner {
+                source_id: self.id,
+                location: self.location.clone(),
+                config: config.clone(),
+            }),
+            SourceConfig::ShellHistory(config) => Box::new(shell_history::ShellHistoryScanner {
+                source_id: self.id,
+                location: self.location.clone(),
+                config: config.clone(),
+            }),
+            SourceConfig::Chat(config) => Box::new(chat::ChatScanner {
+                source_id: self.id,
+                location: self.location.clone(),
+                config: config.clone(),
+            }),
+            #[cfg(feature = "social")]
+            SourceConfig::SocialArchive(config) => Box::new(social::SocialArchiveScanner::new(
+                self.id,
+                self.location.clone(),
+                config.clone(),
+            )?),
+            #[cfg(feature = "highlights")]
+            SourceConfig::Highlights(config) => Box::new(highlights::HighlightsScanner {
+                source_id: self.id,
+                location: self.location.clone(),
+                config: config.clone(),
+            }),
+            #[cfg(feature = "evernote")]
+            SourceConfig::Evernote(config) => Box::new(evernote::EvernoteScanner {
+                source_id: self.id,
+                location: self.location.clone(),
+                config: config.clone(),
+            }),
+            #[cfg(feature = "notion")]
+            SourceConfig::Notion(config) => Box::new(notion::NotionScanner {
+                source_id: self.id,
+                location: self.location.clone(),
+                config: config.clone(),
+            }),
+            #[cfg(feature = "feeds")]
+            SourceConfig::Feeds(config) => Box::new(feeds::FeedsScanner::new(
+                self.id,
+                self.location.clone(),
+                config.clone(),
+            )?),
+            #[cfg(feature = "git")]
+            SourceConfig::Git(config) => Box::new(git::GitScanner {
+                source_id: self.id,
+                location: self.location.clone(),
+                config: config.clone(),
+            }),
+            #[cfg(feature = "web-archive")]
+            SourceConfig::WebArchive(config) => Box::new(web_archive::WebArchiveScanner {
+                source_id: self.id,
+                location: self.location.clone(),
+                config: config.clone(),
+            }),
         };
 
         Ok(scanner)
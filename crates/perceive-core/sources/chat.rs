@@ -0,0 +1,252 @@
+mod discord;
+mod slack;
+
+use ahash::HashMap;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use time::OffsetDateTime;
+
+pub use self::{discord::parse_discord_export, slack::parse_slack_export};
+use super::{
+    pipeline::{
+        prefilled_read, CountingVecSender, FoundItem, SourceScanner, SourceScannerReadResult,
+        SCAN_BATCH_SIZE,
+    },
+    timeline::{format_time, split_by_gap},
+    ItemCompareStrategy,
+};
+use crate::{batch_sender::BatchSender, cancel::CancellationToken, Item, ItemMetadata};
+
+#[derive(Debug, Display, EnumString, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ChatPlatform {
+    /// A Slack workspace export zip
+    Slack,
+    /// A JSON file from DiscordChatExporter, or a directory of them
+    Discord,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChatConfig {
+    pub platform: ChatPlatform,
+    /// Messages more than this many minutes apart go into separate conversations.
+    #[serde(default = "default_conversation_gap")]
+    pub conversation_gap: u32,
+    /// The URL of the Slack workspace, like `https://example.slack.com`, used to build
+    /// permalinks. Without it, links go through slack.com, which redirects to the workspace.
+    #[serde(default)]
+    pub workspace_url: Option<String>,
+}
+
+fn default_conversation_gap() -> u32 {
+    30
+}
+
+/// Reads the messages from a chat export. Threads become a single item each, and the other
+/// messages in a channel are grouped into conversations of messages sent close together, so
+/// that each item has enough context to embed well.
+pub struct ChatScanner {
+    pub source_id: i64,
+    pub location: String,
+    pub config: ChatConfig,
+}
+
+/// A channel read from an export.
+#[derive(Debug)]
+pub struct ChatChannel {
+    pub name: String,
+    pub messages: Vec<ChatMessage>,
+}
+
+impl ChatChannel {
+    /// A value that changes whenever messages in the channel are added, removed, or edited. This
+    /// lets a newer export skip over the channels that haven't changed.
+    fn fingerprint(&self) -> String {
+        let last = self.messages.iter().map(|m| m.time).max();
+        let last_edit = self.messages.iter().filter_map(|m| m.edited).max();
+        format!(
+            "{}:{}:{}",
+            self.messages.len(),
+            last.map_or(0, |t| t.unix_timestamp_nanos()),
+            last_edit.map_or(0, |t| t.unix_timestamp_nanos())
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatMessage {
+    /// A permalink to the message.
+    pub permalink: String,
+    pub time: OffsetDateTime,
+    pub edited: Option<OffsetDateTime>,
+    pub author: String,
+    pub text: String,
+    /// Identifies the thread that this message is part of, if any. Thread parents have this set
+    /// as well as the replies.
+    pub thread: Option<String>,
+}
+
+/// Group a channel's messages into threads, and conversations where no message is more than
+/// `gap` after the previous one.
+pub fn group_conversations(
+    messages: Vec<ChatMessage>,
+    gap: time::Duration,
+) -> Vec<Vec<ChatMessage>> {
+    let (threaded, unthreaded): (Vec<_>, Vec<_>) =
+        messages.into_iter().partition(|m| m.thread.is_some());
+
+    let mut threads: HashMap<String, Vec<ChatMessage>> = HashMap::default();
+    for message in threaded {
+        let thread = message.thread.clone().unwrap_or_default();
+        threads.entry(thread).or_default().push(message);
+    }
+
+    let mut conversations = threads.into_values().collect::<Vec<_>>();
+    conversations.extend(split_by_gap(unthreaded, gap, |m| Some(m.time)));
+
+    for conversation in conversations.iter_mut() {
+        conversation.sort_by_key(|m| m.time);
+    }
+    conversations.sort_by_key(|c| c.first().map(|m| m.time));
+
+    conversations
+}
+
+/// Create an item for a conversation. The ID is the permalink of the first message, so that new
+/// messages at the end of a conversation update the same item.
+fn conversation_item(
+    source_id: i64,
+    channel_name: &str,
+    fingerprint: &str,
+    conversation: Vec<ChatMessage>,
+) -> Option<Item> {
+    let first = conversation.first()?;
+    let external_id = first.permalink.clone();
+    let end = conversation.iter().map(|m| m.time).max();
+
+    let participants = conversation
+        .iter()
+        .map(|m| m.author.as_str())
+        .unique()
+        .join(", ");
+
+    let content = conversation
+        .iter()
+        .map(|m| format!("{} {}: {}", format_time(m.time), m.author, m.text))
+        .join("\n");
+
+    Some(Item {
+        id: -1,
+        source_id,
+        external_id,
+        hash: Some(fingerprint.to_string()),
+        content: Some(content),
+        raw_content: None,
+        skipped: None,
+        process_version: 0,
+        metadata: ItemMetadata {
+            name: Some(channel_name.to_string()),
+            author: Some(participants),
+            mtime: end,
+            ..Default::default()
+        },
+    })
+}
+
+impl SourceScanner for ChatScanner {
+    fn scan(
+        &self,
+        output: CountingVecSender<Item>,
+        cancel: &CancellationToken,
+    ) -> Result<(), eyre::Report> {
+        let channels = match self.config.platform {
+            ChatPlatform::Slack => {
+                parse_slack_export(&self.location, self.config.workspace_url.as_deref())?
+            }
+            ChatPlatform::Discord => parse_discord_export(&self.location)?,
+        };
+
+        let gap = time::Duration::minutes(self.config.conversation_gap as i64);
+        let sender = BatchSender::new(SCAN_BATCH_SIZE, output);
+        for channel in channels {
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            let fingerprint = channel.fingerprint();
+            for conversation in group_conversations(channel.messages, gap) {
+                if let Some(item) =
+                    conversation_item(self.source_id, &channel.name, &fingerprint, conversation)
+                {
+                    sender.add(item)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read(
+        &self,
+        existing: Option<&FoundItem>,
+        compare_strategy: ItemCompareStrategy,
+        item: &mut Item,
+    ) -> Result<SourceScannerReadResult, eyre::Report> {
+        // Conversations are built while scanning, since the messages can only be grouped after
+        // reading the whole channel.
+        Ok(prefilled_read(existing, compare_strategy, item))
+    }
+
+    fn latest_process_version(&self) -> i32 {
+        0
+    }
+
+    fn reprocess(&self, _item: &mut Item) -> Result<SourceScannerReadResult, eyre::Report> {
+        Ok(SourceScannerReadResult::Unchanged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::timeline::test_time;
+
+    fn message(minute: i64, text: &str, thread: Option<&str>) -> ChatMessage {
+        ChatMessage {
+            permalink: format!("https://example.com/{minute}"),
+            time: test_time(minute),
+            edited: None,
+            author: "Alice".to_string(),
+            text: text.to_string(),
+            thread: thread.map(String::from),
+        }
+    }
+
+    #[test]
+    fn conversations() {
+        let messages = vec![
+            message(0, "one", None),
+            message(50, "three", None),
+            message(5, "two", None),
+            message(1, "parent", Some("t")),
+            message(100, "reply", Some("t")),
+        ];
+
+        let conversations = group_conversations(messages, time::Duration::minutes(30))
+            .into_iter()
+            .map(|c| c.into_iter().map(|m| m.text).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            conversations,
+            vec![
+                vec!["one".to_string(), "two".to_string()],
+                vec!["parent".to_string(), "reply".to_string()],
+                vec!["three".to_string()],
+            ]
+        );
+    }
+}
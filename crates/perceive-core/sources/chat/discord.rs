@@ -0,0 +1,182 @@
+use std::path::Path;
+
+use eyre::{eyre, Context};
+use serde::Deserialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use super::{ChatChannel, ChatMessage};
+
+#[derive(Debug, Deserialize)]
+struct DiscordExport {
+    guild: DiscordGuild,
+    channel: DiscordChannel,
+    messages: Vec<DiscordMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DiscordGuild {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct DiscordChannel {
+    id: String,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DiscordMessage {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    timestamp: String,
+    #[serde(default)]
+    timestamp_edited: Option<String>,
+    #[serde(default)]
+    content: String,
+    author: DiscordAuthor,
+    #[serde(default)]
+    attachments: Vec<DiscordAttachment>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DiscordAuthor {
+    name: String,
+    #[serde(default)]
+    nickname: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DiscordAttachment {
+    file_name: String,
+}
+
+/// DiscordChatExporter uses this guild ID for direct messages.
+const DIRECT_MESSAGES_GUILD: &str = "0";
+
+/// Read the channels from a DiscordChatExporter JSON file, or from all the JSON files in a
+/// directory.
+pub fn parse_discord_export(location: &str) -> Result<Vec<ChatChannel>, eyre::Report> {
+    let root = Path::new(location);
+    let files = if root.is_file() {
+        vec![root.to_path_buf()]
+    } else {
+        ignore::WalkBuilder::new(root)
+            .build()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.into_path())
+            .filter(|path| {
+                path.extension()
+                    .map_or(false, |ext| ext.eq_ignore_ascii_case("json"))
+            })
+            .collect()
+    };
+
+    files
+        .into_iter()
+        .map(|path| {
+            let content =
+                std::fs::read(&path).wrap_err_with(|| eyre!("Reading {}", path.display()))?;
+            parse_discord_channel(&content).wrap_err_with(|| eyre!("Parsing {}", path.display()))
+        })
+        .collect()
+}
+
+fn parse_discord_channel(content: &[u8]) -> Result<ChatChannel, eyre::Report> {
+    let export: DiscordExport = serde_json::from_slice(content)?;
+
+    let is_dm = export.guild.id == DIRECT_MESSAGES_GUILD;
+    let guild = if is_dm { "@me" } else { &export.guild.id };
+    let channel_id = &export.channel.id;
+
+    let messages = export
+        .messages
+        .into_iter()
+        // Skip pins, joins, and other events.
+        .filter(|m| m.kind == "Default" || m.kind == "Reply")
+        .filter_map(|m| {
+            let mut text = m.content;
+            for attachment in &m.attachments {
+                if !text.is_empty() {
+                    text.push(' ');
+                }
+                text.push_str(&format!("[attachment: {}]", attachment.file_name));
+            }
+
+            if text.trim().is_empty() {
+                return None;
+            }
+
+            Some(ChatMessage {
+                permalink: format!("https://discord.com/channels/{guild}/{channel_id}/{}", m.id),
+                time: OffsetDateTime::parse(&m.timestamp, &Rfc3339).ok()?,
+                edited: m
+                    .timestamp_edited
+                    .and_then(|t| OffsetDateTime::parse(&t, &Rfc3339).ok()),
+                author: m
+                    .author
+                    .nickname
+                    .filter(|n| !n.is_empty())
+                    .unwrap_or(m.author.name),
+                text,
+                // Discord threads are exported as channels of their own.
+                thread: None,
+            })
+        })
+        .collect();
+
+    let name = if is_dm {
+        export.channel.name
+    } else {
+        format!("#{}", export.channel.name)
+    };
+
+    Ok(ChatChannel { name, messages })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discord_export() {
+        let export = r#"{
+            "guild": {"id": "100", "name": "Server", "iconUrl": ""},
+            "channel": {"id": "200", "type": "GuildTextChat", "categoryId": "1", "category": "Text", "name": "general", "topic": null},
+            "dateRange": {"after": null, "before": null},
+            "exportedAt": "2023-01-11T00:00:00+00:00",
+            "messages": [
+                {"id": "1", "type": "GuildMemberJoin", "timestamp": "2023-01-10T11:00:00+00:00", "timestampEdited": null, "isPinned": false, "content": "", "author": {"id": "10", "name": "alice", "discriminator": "0000", "nickname": "Alice", "isBot": false}, "attachments": []},
+                {"id": "2", "type": "Default", "timestamp": "2023-01-10T12:00:00.123+00:00", "timestampEdited": "2023-01-10T12:05:00+00:00", "isPinned": false, "content": "Hello @bob", "author": {"id": "10", "name": "alice", "discriminator": "0000", "nickname": "Alice", "isBot": false}, "attachments": []},
+                {"id": "3", "type": "Reply", "timestamp": "2023-01-10T12:01:00+00:00", "timestampEdited": null, "isPinned": false, "content": "", "author": {"id": "11", "name": "bob", "discriminator": "0000", "nickname": null, "isBot": false}, "attachments": [{"id": "5", "url": "https://cdn.discordapp.com/x.png", "fileName": "x.png", "fileSizeBytes": 10}], "reference": {"messageId": "2", "channelId": "200", "guildId": "100"}}
+            ],
+            "messageCount": 3
+        }"#;
+
+        let channel = parse_discord_channel(export.as_bytes()).unwrap();
+        assert_eq!(channel.name, "#general");
+        assert_eq!(
+            channel.messages,
+            vec![
+                ChatMessage {
+                    permalink: "https://discord.com/channels/100/200/2".to_string(),
+                    time: OffsetDateTime::from_unix_timestamp_nanos(1673352000123000000).unwrap(),
+                    edited: Some(OffsetDateTime::from_unix_timestamp(1673352300).unwrap()),
+                    author: "Alice".to_string(),
+                    text: "Hello @bob".to_string(),
+                    thread: None,
+                },
+                ChatMessage {
+                    permalink: "https://discord.com/channels/100/200/3".to_string(),
+                    time: OffsetDateTime::from_unix_timestamp(1673352060).unwrap(),
+                    edited: None,
+                    author: "bob".to_string(),
+                    text: "[attachment: x.png]".to_string(),
+                    thread: None,
+                },
+            ]
+        );
+    }
+}
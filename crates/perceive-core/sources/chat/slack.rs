@@ -0,0 +1,360 @@
+use std::io::{Read, Seek};
+
+use ahash::HashMap;
+use eyre::{eyre, Context};
+use serde::{de::DeserializeOwned, Deserialize};
+use time::OffsetDateTime;
+use zip::{result::ZipError, ZipArchive};
+
+use super::{ChatChannel, ChatMessage};
+
+#[derive(Debug, Default, Deserialize)]
+struct SlackProfile {
+    #[serde(default)]
+    display_name: Option<String>,
+    #[serde(default)]
+    real_name: Option<String>,
+}
+
+impl SlackProfile {
+    fn name(&self) -> Option<&str> {
+        [&self.display_name, &self.real_name]
+            .into_iter()
+            .flatten()
+            .map(|n| n.as_str())
+            .find(|n| !n.is_empty())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SlackUser {
+    id: String,
+    name: String,
+    #[serde(default)]
+    real_name: Option<String>,
+    #[serde(default)]
+    profile: SlackProfile,
+}
+
+impl SlackUser {
+    fn display_name(&self) -> &str {
+        self.profile
+            .name()
+            .or_else(|| self.real_name.as_deref().filter(|n| !n.is_empty()))
+            .unwrap_or(&self.name)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SlackChannel {
+    id: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    members: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SlackEdit {
+    ts: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SlackMessage {
+    #[serde(default)]
+    subtype: Option<String>,
+    #[serde(default)]
+    user: Option<String>,
+    /// Set on messages from bots and integrations.
+    #[serde(default)]
+    username: Option<String>,
+    #[serde(default)]
+    user_profile: Option<SlackProfile>,
+    #[serde(default)]
+    text: String,
+    ts: String,
+    #[serde(default)]
+    thread_ts: Option<String>,
+    #[serde(default)]
+    edited: Option<SlackEdit>,
+}
+
+/// The kinds of channel listed in an export, and whether the folder holding each channel's
+/// messages is named after the channel or its ID.
+const CHANNEL_LISTS: [(&str, bool); 4] = [
+    ("channels.json", true),
+    ("groups.json", true),
+    ("mpims.json", true),
+    ("dms.json", false),
+];
+
+/// Read the channels from a Slack workspace export zip.
+pub fn parse_slack_export(
+    location: &str,
+    workspace_url: Option<&str>,
+) -> Result<Vec<ChatChannel>, eyre::Report> {
+    let file = std::fs::File::open(location).wrap_err_with(|| eyre!("Opening {location}"))?;
+    let mut zip = ZipArchive::new(file).wrap_err_with(|| eyre!("Reading {location}"))?;
+    parse_slack_zip(&mut zip, workspace_url)
+}
+
+fn parse_slack_zip<R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    workspace_url: Option<&str>,
+) -> Result<Vec<ChatChannel>, eyre::Report> {
+    let users = read_json::<Vec<SlackUser>, _>(zip, "users.json")?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|u| (u.id.clone(), u.display_name().to_string()))
+        .collect::<HashMap<_, _>>();
+
+    let mut files_by_folder: HashMap<String, Vec<String>> = HashMap::default();
+    for name in zip.file_names() {
+        if let Some((folder, file)) = name.split_once('/') {
+            if file.ends_with(".json") && !file.contains('/') {
+                files_by_folder
+                    .entry(folder.to_string())
+                    .or_default()
+                    .push(name.to_string());
+            }
+        }
+    }
+
+    let permalink_base = workspace_url
+        .unwrap_or("https://slack.com")
+        .trim_end_matches('/')
+        .to_string();
+
+    let mut channels = Vec::new();
+    for (list, named_folders) in CHANNEL_LISTS {
+        let listed = read_json::<Vec<SlackChannel>, _>(zip, list)?.unwrap_or_default();
+        for channel in listed {
+            let folder = if named_folders {
+                channel.name.as_deref().unwrap_or(&channel.id)
+            } else {
+                &channel.id
+            };
+
+            let Some(mut files) = files_by_folder.remove(folder) else {
+                continue;
+            };
+            // The files are named by date.
+            files.sort();
+
+            let mut messages = Vec::new();
+            for file in files {
+                let day = read_json::<Vec<SlackMessage>, _>(zip, &file)?.unwrap_or_default();
+                messages.extend(day.into_iter().filter_map(|message| {
+                    chat_message(&permalink_base, &channel.id, &users, message)
+                }));
+            }
+
+            let name = match channel.name.as_deref() {
+                Some(name) if list == "channels.json" || list == "groups.json" => {
+                    format!("#{name}")
+                }
+                // Group DMs have generated names, so use the members instead.
+                _ => channel
+                    .members
+                    .iter()
+                    .map(|id| users.get(id).map(|n| n.as_str()).unwrap_or(id))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            };
+
+            channels.push(ChatChannel { name, messages });
+        }
+    }
+
+    Ok(channels)
+}
+
+fn read_json<T: DeserializeOwned, R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<Option<T>, eyre::Report> {
+    match zip.by_name(name) {
+        Ok(file) => serde_json::from_reader(file)
+            .map(Some)
+            .wrap_err_with(|| eyre!("Reading {name}")),
+        Err(ZipError::FileNotFound) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn chat_message(
+    permalink_base: &str,
+    channel_id: &str,
+    users: &HashMap<String, String>,
+    message: SlackMessage,
+) -> Option<ChatMessage> {
+    // Skip joins, topic changes, and other channel events.
+    let is_message = match message.subtype.as_deref() {
+        None => true,
+        Some(subtype) => matches!(
+            subtype,
+            "bot_message" | "me_message" | "thread_broadcast" | "file_share"
+        ),
+    };
+    if !is_message || message.text.trim().is_empty() {
+        return None;
+    }
+
+    let author = message
+        .user
+        .as_ref()
+        .and_then(|id| users.get(id))
+        .map(|n| n.as_str())
+        .or_else(|| message.user_profile.as_ref().and_then(|p| p.name()))
+        .or(message.username.as_deref())
+        .or(message.user.as_deref())
+        .unwrap_or("unknown")
+        .to_string();
+
+    Some(ChatMessage {
+        permalink: format!(
+            "{permalink_base}/archives/{channel_id}/p{}",
+            message.ts.replace('.', "")
+        ),
+        time: parse_ts(&message.ts)?,
+        edited: message.edited.and_then(|e| parse_ts(&e.ts)),
+        author,
+        text: format_text(&message.text, users),
+        thread: message.thread_ts,
+    })
+}
+
+/// Parse a Slack timestamp, like `1673352000.000100`.
+fn parse_ts(ts: &str) -> Option<OffsetDateTime> {
+    let (seconds, micros) = ts.split_once('.').unwrap_or((ts, "0"));
+    let nanos = seconds.parse::<i128>().ok()? * 1_000_000_000 + micros.parse::<i128>().ok()? * 1000;
+    OffsetDateTime::from_unix_timestamp_nanos(nanos).ok()
+}
+
+/// Convert Slack's markup for mentions and links, like `<@U123>` and `<https://example.com|a
+/// link>`, to plain text.
+fn format_text(text: &str, users: &HashMap<String, String>) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find('>') else {
+            break;
+        };
+
+        let inner = &rest[1..end];
+        let (target, label) = match inner.split_once('|') {
+            Some((target, label)) => (target, Some(label)),
+            None => (inner, None),
+        };
+
+        let replacement = if let Some(user) = target.strip_prefix('@') {
+            let name = label
+                .or_else(|| users.get(user).map(|n| n.as_str()))
+                .unwrap_or(user);
+            format!("@{}", name.trim_start_matches('@'))
+        } else if let Some(channel) = target.strip_prefix('#') {
+            format!("#{}", label.unwrap_or(channel))
+        } else if let Some(special) = target.strip_prefix('!') {
+            // Broadcasts like `<!here>` and user groups like `<!subteam^S123|@team>`
+            label
+                .map(String::from)
+                .unwrap_or_else(|| format!("@{special}"))
+        } else {
+            match label {
+                Some(label) if label != target => format!("{label} ({target})"),
+                _ => target.to_string(),
+            }
+        };
+
+        output.push_str(&replacement);
+        rest = &rest[end + 1..];
+    }
+    output.push_str(rest);
+
+    output
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::{write::FileOptions, ZipWriter};
+
+    use super::*;
+
+    #[test]
+    fn slack_export() {
+        let files = [
+            (
+                "users.json",
+                r#"[
+                    {"id": "U1", "name": "alice", "real_name": "Alice A", "profile": {"display_name": "Alice"}},
+                    {"id": "U2", "name": "bob", "profile": {"display_name": "", "real_name": "Bob B"}}
+                ]"#,
+            ),
+            (
+                "channels.json",
+                r#"[{"id": "C1", "name": "general", "members": ["U1", "U2"]}]"#,
+            ),
+            ("dms.json", r#"[{"id": "D1", "members": ["U1", "U2"]}]"#),
+            (
+                "general/2023-01-10.json",
+                r#"[
+                    {"type": "message", "subtype": "channel_join", "user": "U2", "text": "<@U2> has joined the channel", "ts": "1673352000.000100"},
+                    {"type": "message", "user": "U1", "text": "Hi <@U2>, see <https://example.com|the docs> &amp; <#C1|general>", "ts": "1673352060.000200", "thread_ts": "1673352060.000200", "edited": {"user": "U1", "ts": "1673352100.000000"}},
+                    {"type": "message", "user": "U2", "text": "Thanks", "ts": "1673352120.000300", "thread_ts": "1673352060.000200"}
+                ]"#,
+            ),
+            (
+                "D1/2023-01-11.json",
+                r#"[{"type": "message", "user": "U2", "text": "<!here> lunch?", "ts": "1673438400.000100"}]"#,
+            ),
+        ];
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        let mut zip = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        let channels = parse_slack_zip(&mut zip, Some("https://example.slack.com/")).unwrap();
+        assert_eq!(channels.len(), 2);
+
+        let general = &channels[0];
+        assert_eq!(general.name, "#general");
+        assert_eq!(
+            general.messages,
+            vec![
+                ChatMessage {
+                    permalink: "https://example.slack.com/archives/C1/p1673352060000200"
+                        .to_string(),
+                    time: OffsetDateTime::from_unix_timestamp_nanos(1673352060000200000).unwrap(),
+                    edited: Some(OffsetDateTime::from_unix_timestamp(1673352100).unwrap()),
+                    author: "Alice".to_string(),
+                    text: "Hi @Bob B, see the docs (https://example.com) & #general".to_string(),
+                    thread: Some("1673352060.000200".to_string()),
+                },
+                ChatMessage {
+                    permalink: "https://example.slack.com/archives/C1/p1673352120000300"
+                        .to_string(),
+                    time: OffsetDateTime::from_unix_timestamp_nanos(1673352120000300000).unwrap(),
+                    edited: None,
+                    author: "Bob B".to_string(),
+                    text: "Thanks".to_string(),
+                    thread: Some("1673352060.000200".to_string()),
+                },
+            ]
+        );
+
+        let dm = &channels[1];
+        assert_eq!(dm.name, "Alice, Bob B");
+        assert_eq!(dm.messages.len(), 1);
+        assert_eq!(dm.messages[0].text, "@here lunch?");
+    }
+}
@@ -2,20 +2,23 @@ use std::path::Path;
 
 use ahash::HashMap;
 use eyre::Result;
-use reqwest::{blocking::Client, Url};
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
 
 use super::{
-    parse_html::{fetch_html, reprocess_html_article, should_skip, HTML_PROCESS_VERSION},
+    parse_html::{read_web_page, reprocess_html_article, should_skip, HTML_PROCESS_VERSION},
     pipeline::{self, FoundItem, SourceScanner, SourceScannerReadResult},
+    web_fetcher::{FetchConfig, WebFetcher},
     ItemCompareStrategy,
 };
-use crate::{batch_sender::BatchSender, Item, ItemMetadata};
+use crate::{batch_sender::BatchSender, cancel::CancellationToken, Item, ItemMetadata};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ChromiumBookmarksConfig {
     /// Domains that we should never check
     pub skip: Vec<String>,
+    #[serde(default)]
+    pub fetch: FetchConfig,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -45,24 +48,26 @@ pub struct ChromiumBookmarksScanner {
     pub source_id: i64,
     pub location: String,
     pub config: ChromiumBookmarksConfig,
-    pub client: Client,
+    pub fetcher: WebFetcher,
 }
 
 impl ChromiumBookmarksScanner {
     pub fn new(source_id: i64, location: String, config: ChromiumBookmarksConfig) -> Result<Self> {
+        let fetcher = WebFetcher::new(config.fetch.clone(), true)?;
         Ok(Self {
             source_id,
             location,
             config,
-            client: Client::builder()
-                .user_agent("perceive-search")
-                .gzip(true)
-                .timeout(std::time::Duration::from_secs(30))
-                .build()?,
+            fetcher,
         })
     }
 
-    fn walk_bookmarks(&self, sender: &BatchSender<Item>, entry: &BookmarkEntry) -> Result<()> {
+    fn walk_bookmarks(
+        &self,
+        sender: &BatchSender<Item>,
+        cancel: &CancellationToken,
+        entry: &BookmarkEntry,
+    ) -> Result<()> {
         match entry {
             BookmarkEntry::Url {
                 url,
@@ -111,7 +116,11 @@ impl ChromiumBookmarksScanner {
             }
             BookmarkEntry::Folder { children, .. } => {
                 for child in children {
-                    self.walk_bookmarks(sender, child)?;
+                    if cancel.is_cancelled() {
+                        break;
+                    }
+
+                    self.walk_bookmarks(sender, cancel, child)?;
                 }
             }
         }
@@ -121,13 +130,17 @@ impl ChromiumBookmarksScanner {
 }
 
 impl SourceScanner for ChromiumBookmarksScanner {
-    fn scan(&self, output: pipeline::CountingVecSender<crate::Item>) -> Result<(), eyre::Report> {
+    fn scan(
+        &self,
+        output: pipeline::CountingVecSender<crate::Item>,
+        cancel: &CancellationToken,
+    ) -> Result<(), eyre::Report> {
         let sender = BatchSender::new(64, output);
         let file_path = Path::new(&self.location).join("Bookmarks");
         let bookmarks: BookmarksFile = serde_json::from_reader(std::fs::File::open(file_path)?)?;
 
         for root in bookmarks.roots.values() {
-            self.walk_bookmarks(&sender, root)?;
+            self.walk_bookmarks(&sender, cancel, root)?;
         }
 
         Ok(())
@@ -136,31 +149,10 @@ impl SourceScanner for ChromiumBookmarksScanner {
     fn read(
         &self,
         existing: Option<&FoundItem>,
-        compare_strategy: super::ItemCompareStrategy,
+        compare_strategy: ItemCompareStrategy,
         item: &mut crate::Item,
     ) -> Result<SourceScannerReadResult, eyre::Report> {
-        if compare_strategy != ItemCompareStrategy::Force {
-            if let Some(skipped) = existing.and_then(|e| e.skipped) {
-                if skipped.permanent() {
-                    // We skipped it last time, so continue skipping it.
-                    item.skipped = Some(skipped);
-                    return Ok(SourceScannerReadResult::Unchanged);
-                }
-            }
-
-            let existing_atime = existing.and_then(|e| e.last_accessed);
-            let new_atime = item.metadata.atime.map(|a| a.unix_timestamp());
-
-            let newer_access = new_atime
-                .zip(existing_atime)
-                .map(|(n, e)| n > e)
-                .unwrap_or(true);
-            if !newer_access {
-                return Ok(SourceScannerReadResult::Unchanged);
-            }
-        }
-
-        fetch_html(&self.client, existing, item)
+        read_web_page(&self.fetcher, existing, compare_strategy, item)
     }
 
     fn latest_process_version(&self) -> i32 {
@@ -1,30 +1,34 @@
-use std::{borrow::Cow, path::Path};
+use std::path::Path;
 
 use ahash::HashMap;
 use eyre::{eyre, Context};
 use itertools::Itertools;
-use reqwest::{blocking::Client, Url};
 use serde::{Deserialize, Serialize};
 use time::macros::datetime;
 
 use super::{
-    parse_html::{fetch_html, reprocess_html_article, should_skip, HTML_PROCESS_VERSION},
+    parse_html::{
+        normalize_history_url, read_web_page, reprocess_html_article, HTML_PROCESS_VERSION,
+    },
     pipeline::{CountingVecSender, FoundItem, SourceScanner, SourceScannerReadResult},
+    web_fetcher::{interleave_by_host, FetchConfig, WebFetcher},
     ItemCompareStrategy,
 };
-use crate::Item;
+use crate::{cancel::CancellationToken, Item};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ChromiumHistoryConfig {
     /// Domains that we should never check
     pub skip: Vec<String>,
+    #[serde(default)]
+    pub fetch: FetchConfig,
 }
 
 pub struct ChromiumHistoryScanner {
     pub source_id: i64,
     pub location: String,
     pub config: ChromiumHistoryConfig,
-    pub client: Client,
+    pub fetcher: WebFetcher,
 }
 
 impl ChromiumHistoryScanner {
@@ -33,22 +37,22 @@ impl ChromiumHistoryScanner {
         location: String,
         config: ChromiumHistoryConfig,
     ) -> Result<ChromiumHistoryScanner, eyre::Report> {
+        let fetcher = WebFetcher::new(config.fetch.clone(), false)?;
         Ok(ChromiumHistoryScanner {
             source_id,
             location,
             config,
-            client: Client::builder()
-                .user_agent("perceive-search")
-                .gzip(true)
-                .timeout(std::time::Duration::from_secs(30))
-                .redirect(reqwest::redirect::Policy::none())
-                .build()?,
+            fetcher,
         })
     }
 }
 
 impl SourceScanner for ChromiumHistoryScanner {
-    fn scan(&self, tx: CountingVecSender<Item>) -> Result<(), eyre::Report> {
+    fn scan(
+        &self,
+        tx: CountingVecSender<Item>,
+        cancel: &CancellationToken,
+    ) -> Result<(), eyre::Report> {
         // Some browsers lock the SQLite history database, so we copy it to be safe.
         let dir = tempfile::tempdir()?;
         let db_path = dir.path().join("History");
@@ -79,52 +83,31 @@ impl SourceScanner for ChromiumHistoryScanner {
             Ok((url, title, last_visit_time))
         })?;
 
+        let skip = &self.config.skip;
         let mut output = HashMap::default();
 
         for row in rows {
-            let (mut url_str, title, last_visit_time) = row?;
+            let (url_str, title, last_visit_time) = row?;
 
             // TODO Read cookies as well? This feels intrusive but could help a lot for sources
             // that need login. Probably make it an option
 
-            let Ok(mut url) = Url::parse(&url_str) else {
-                    // If it doesn't parse here then there's no point in continuing.
+            let Some((url_str, dedupe_key)) = normalize_history_url(skip, url_str) else {
                 continue;
-            } ;
-
-            // The history contains lots of entries with the same URL but different fragment.
-            // Clear it out so that we can dedupe.
-            if url.scheme() != "https" || url.fragment().is_some() {
-                url.set_scheme("https").ok();
-                url.set_fragment(None);
-                url_str = url.to_string();
-            }
-
-            if should_skip(&self.config.skip, &url) {
-                continue;
-            }
-
-            let dedupe_path = url.path().trim_end_matches('/');
-            let dedupe_key = if dedupe_path != url.path() {
-                // Remove trailing slash, but only from the dedupe key, since removing it from
-                // the URL that we actually request may cause problems with some sites.
-                let mut dedupe_url = url.clone();
-                dedupe_url.set_path(dedupe_path);
-                Cow::Owned(dedupe_url.to_string())
-            } else {
-                Cow::Borrowed(&url_str)
             };
 
-            if output.contains_key(dedupe_key.as_ref()) {
-                continue;
-            }
-
-            output.insert(dedupe_key.into_owned(), (url_str, title, last_visit_time));
+            output
+                .entry(dedupe_key)
+                .or_insert((url_str, title, last_visit_time));
         }
 
-        // Rely on the HashMap iteration order being random-ish, since we want to shuffle
-        // the URLs as a simple way to avoid hitting the same domain in quick succession.
-        for batch in &output.into_values().chunks(64) {
+        // Spread out the URLs so that the readers aren't all waiting on the same host.
+        let output = interleave_by_host(output.into_values().collect(), |(url, _, _)| url.as_str());
+        for batch in &output.into_iter().chunks(64) {
+            if cancel.is_cancelled() {
+                break;
+            }
+
             let batch = batch
                 .into_iter()
                 .map(|(url_str, title, last_visit_time)| Item {
@@ -156,28 +139,7 @@ impl SourceScanner for ChromiumHistoryScanner {
         compare_strategy: ItemCompareStrategy,
         item: &mut Item,
     ) -> Result<SourceScannerReadResult, eyre::Report> {
-        if compare_strategy != ItemCompareStrategy::Force {
-            if let Some(skipped) = existing.and_then(|e| e.skipped) {
-                if skipped.permanent() {
-                    // We skipped it last time, so continue skipping it.
-                    item.skipped = Some(skipped);
-                    return Ok(SourceScannerReadResult::Unchanged);
-                }
-            }
-
-            let existing_atime = existing.and_then(|e| e.last_accessed);
-            let new_atime = item.metadata.atime.map(|a| a.unix_timestamp());
-
-            let newer_access = new_atime
-                .zip(existing_atime)
-                .map(|(n, e)| n > e)
-                .unwrap_or(true);
-            if !newer_access {
-                return Ok(SourceScannerReadResult::Unchanged);
-            }
-        }
-
-        fetch_html(&self.client, existing, item)
+        read_web_page(&self.fetcher, existing, compare_strategy, item)
     }
 
     fn latest_process_version(&self) -> i32 {
@@ -9,7 +9,8 @@ use crate::db::{Database, DbError};
 pub fn list_sources(database: &Database) -> Result<Vec<Source>, DbError> {
     let conn = database.read_pool.get()?;
     let mut stmt = conn.prepare_cached(
-        "SELECT id, name, config, location, compare_strategy, status, last_indexed, index_version
+        "SELECT id, name, config, location, compare_strategy, status, last_indexed, index_version,
+            index_interval
         FROM sources",
     )?;
 
@@ -30,6 +31,7 @@ pub fn list_sources(database: &Database) -> Result<Vec<Source>, DbError> {
                 last_indexed: OffsetDateTime::from_unix_timestamp(row.get(6)?)
                     .unwrap_or_else(|_| OffsetDateTime::now_utc()),
                 index_version: row.get(7)?,
+                index_interval: row.get(8)?,
             })
         })?
         .collect::<Result<Vec<_>, _>>()?;
@@ -40,8 +42,8 @@ pub fn list_sources(database: &Database) -> Result<Vec<Source>, DbError> {
 pub fn add_source(database: &Database, mut source: Source) -> Result<Source, DbError> {
     let conn = database.write_conn.lock();
     let mut stmt = conn.prepare_cached(
-        r##"INSERT INTO sources (name, config, location, compare_strategy, status)
-            VALUES (:name, :config, :location, :compare_strategy, :status)"##,
+        r##"INSERT INTO sources (name, config, location, compare_strategy, status, index_interval)
+            VALUES (:name, :config, :location, :compare_strategy, :status, :index_interval)"##,
     )?;
 
     stmt.execute(named_params! {
@@ -50,6 +52,7 @@ pub fn add_source(database: &Database, mut source: Source) -> Result<Source, DbE
         ":location": source.location,
         ":compare_strategy": source.compare_strategy.to_string(),
         ":status": serde_json::to_string(&source.status).map_err(DbError::query)?,
+        ":index_interval": source.index_interval,
     })?;
 
     source.id = conn.last_insert_rowid();
@@ -65,7 +68,10 @@ pub fn update_source(database: &Database, source: &Source) -> Result<(), DbError
             config = :config,
             location = :location,
             compare_strategy = :compare_strategy,
-            status = :status
+            status = :status,
+            last_indexed = :last_indexed,
+            index_version = :index_version,
+            index_interval = :index_interval
         WHERE id = :id"##,
     )?;
 
@@ -76,6 +82,9 @@ pub fn update_source(database: &Database, source: &Source) -> Result<(), DbError
         ":location": source.location,
         ":compare_strategy": source.compare_strategy.to_string(),
         ":status": serde_json::to_string(&source.status).map_err(DbError::query)?,
+        ":last_indexed": source.last_indexed.unix_timestamp(),
+        ":index_version": source.index_version,
+        ":index_interval": source.index_interval,
     })?;
 
     Ok(())
@@ -0,0 +1,376 @@
+use std::path::Path;
+
+use ahash::HashSet;
+use eyre::{eyre, Context};
+use mail_parser::{mailbox, Message, MessageParser, PartType};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use super::{
+    parse_html::extract_html_article,
+    pipeline::{
+        content_hash, prefilled_read, CountingVecSender, FoundItem, SourceScanner,
+        SourceScannerReadResult, SCAN_BATCH_SIZE,
+    },
+    ItemCompareStrategy,
+};
+use crate::{batch_sender::BatchSender, cancel::CancellationToken, Item, ItemMetadata};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EmailConfig {}
+
+/// Reads the messages from an mbox file, or from a Maildir directory and its subfolders.
+pub struct EmailScanner {
+    pub source_id: i64,
+    pub location: String,
+    pub config: EmailConfig,
+}
+
+impl EmailScanner {
+    fn scan_mbox(
+        &self,
+        sender: &BatchSender<Item>,
+        seen: &mut HashSet<String>,
+        cancel: &CancellationToken,
+    ) -> Result<(), eyre::Report> {
+        let file = std::fs::File::open(&self.location)
+            .wrap_err_with(|| eyre!("Opening {}", self.location))?;
+        let messages = mailbox::mbox::MessageIterator::new(std::io::BufReader::new(file));
+
+        for message in messages {
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            let Ok(message) = message else {
+                continue;
+            };
+
+            if let Some(item) = message_item(self.source_id, message.contents(), || {
+                message.internal_date()
+            }) {
+                if seen.insert(item.external_id.clone()) {
+                    sender.add(item)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn scan_maildir(
+        &self,
+        sender: &BatchSender<Item>,
+        seen: &mut HashSet<String>,
+        cancel: &CancellationToken,
+    ) -> Result<(), eyre::Report> {
+        // Maildir++ subfolders are named like ".Archive" and ".Archive.2023".
+        let folders = mailbox::maildir::FolderIterator::new(&self.location, Some("."))
+            .wrap_err_with(|| eyre!("Opening {}", self.location))?;
+
+        for folder in folders {
+            let folder = folder?;
+            for message in folder {
+                if cancel.is_cancelled() {
+                    return Ok(());
+                }
+
+                let Ok(message) = message else {
+                    continue;
+                };
+
+                if let Some(item) = message_item(self.source_id, message.contents(), || {
+                    message.internal_date()
+                }) {
+                    if seen.insert(item.external_id.clone()) {
+                        sender.add(item)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Create an item for a raw message.
+fn message_item(
+    source_id: i64,
+    contents: &[u8],
+    internal_date: impl FnOnce() -> u64,
+) -> Option<Item> {
+    let message = MessageParser::default().parse(contents)?;
+
+    let mtime = match message.date() {
+        Some(date) => date.to_timestamp(),
+        None => internal_date() as i64,
+    };
+
+    let author = message
+        .from()
+        .and_then(|from| from.first())
+        .and_then(
+            |addr| match (addr.name.as_deref(), addr.address.as_deref()) {
+                (Some(name), Some(address)) => Some(format!("{name} <{address}>")),
+                (Some(name), None) => Some(name.to_string()),
+                (None, Some(address)) => Some(address.to_string()),
+                (None, None) => None,
+            },
+        );
+
+    let subject = message.subject().map(|s| s.to_string());
+    let content = message_body(message.message_id().unwrap_or_default(), &message);
+
+    let external_id = match message.message_id() {
+        Some(id) => id.to_string(),
+        // Without a Message-ID, the headers and body identify the message, so that it gets the
+        // same ID on every scan.
+        None => {
+            let hash = content_hash(&[
+                &mtime.to_string(),
+                author.as_deref().unwrap_or_default(),
+                subject.as_deref().unwrap_or_default(),
+                content.as_deref().unwrap_or_default(),
+            ]);
+            format!("no-message-id:{hash}")
+        }
+    };
+
+    Some(Item {
+        id: -1,
+        source_id,
+        hash: None,
+        skipped: None,
+        process_version: 0,
+        raw_content: None,
+        metadata: ItemMetadata {
+            name: subject,
+            author,
+            mtime: OffsetDateTime::from_unix_timestamp(mtime).ok(),
+            ..Default::default()
+        },
+        content,
+        external_id,
+    })
+}
+
+/// Get the text of a message, preferring a text/plain part and otherwise extracting the text
+/// from the HTML part.
+fn message_body(message_id: &str, message: &Message) -> Option<String> {
+    let part = message.text_part(0).or_else(|| message.html_part(0))?;
+    let text = match &part.body {
+        PartType::Text(text) => text.to_string(),
+        PartType::Html(html) => {
+            let url = format!("mid:{message_id}");
+            match extract_html_article(&url, html.as_bytes()) {
+                Ok(doc) => doc.text,
+                // Fall back to the plain conversion if readability can't handle it.
+                Err(_) => message.body_text(0)?.into_owned(),
+            }
+        }
+        _ => return None,
+    };
+
+    Some(text).filter(|t| !t.trim().is_empty())
+}
+
+impl SourceScanner for EmailScanner {
+    fn scan(
+        &self,
+        output: CountingVecSender<Item>,
+        cancel: &CancellationToken,
+    ) -> Result<(), eyre::Report> {
+        let sender = BatchSender::new(SCAN_BATCH_SIZE, output);
+        // The same message can show up more than once, such as when it was copied to several
+        // folders, so only keep the first copy.
+        let mut seen = HashSet::default();
+        if Path::new(&self.location).is_dir() {
+            self.scan_maildir(&sender, &mut seen, cancel)?;
+        } else {
+            self.scan_mbox(&sender, &mut seen, cancel)?;
+        }
+
+        Ok(())
+    }
+
+    fn read(
+        &self,
+        existing: Option<&FoundItem>,
+        compare_strategy: ItemCompareStrategy,
+        item: &mut Item,
+    ) -> Result<SourceScannerReadResult, eyre::Report> {
+        // The body was already decoded while scanning, since messages in an mbox file can't be
+        // looked up individually.
+        Ok(prefilled_read(existing, compare_strategy, item))
+    }
+
+    fn latest_process_version(&self) -> i32 {
+        0
+    }
+
+    fn reprocess(&self, _item: &mut Item) -> Result<SourceScannerReadResult, eyre::Report> {
+        Ok(SourceScannerReadResult::Unchanged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::timeline::test_scan;
+
+    const PLAIN: &str = "Message-ID: <1@example.com>
+Date: Tue, 10 Jan 2023 12:00:00 +0000
+From: Alice <alice@example.com>
+Subject: Plans
+
+Let's meet on Friday.
+";
+
+    const ALTERNATIVE: &str = r#"Message-ID: <2@example.com>
+Date: Tue, 10 Jan 2023 13:00:00 +0000
+From: bob@example.com
+Subject: Both kinds
+MIME-Version: 1.0
+Content-Type: multipart/alternative; boundary="boundary"
+
+--boundary
+Content-Type: text/plain; charset=utf-8
+
+The plain version
+--boundary
+Content-Type: text/html; charset=utf-8
+
+<html><body><p>The HTML version</p></body></html>
+--boundary--
+"#;
+
+    const NO_MESSAGE_ID: &str = "Date: Tue, 10 Jan 2023 14:00:00 +0000
+From: Carol <carol@example.com>
+Subject: Untracked
+
+No ID here.
+";
+
+    fn scan(location: &Path) -> Vec<Item> {
+        let scanner = EmailScanner {
+            source_id: 1,
+            location: location.display().to_string(),
+            config: EmailConfig {},
+        };
+
+        let mut items = test_scan(&scanner);
+        items.sort_by(|a, b| a.external_id.cmp(&b.external_id));
+        items
+    }
+
+    #[test]
+    fn headers() {
+        let item = message_item(1, PLAIN.as_bytes(), || 0).unwrap();
+        assert_eq!(item.external_id, "1@example.com");
+        assert_eq!(item.metadata.name.as_deref(), Some("Plans"));
+        assert_eq!(
+            item.metadata.author.as_deref(),
+            Some("Alice <alice@example.com>")
+        );
+        assert_eq!(
+            item.metadata.mtime,
+            Some(OffsetDateTime::from_unix_timestamp(1673352000).unwrap())
+        );
+        assert_eq!(
+            item.content.as_deref().map(str::trim),
+            Some("Let's meet on Friday.")
+        );
+    }
+
+    #[test]
+    fn prefers_plain_text() {
+        let item = message_item(1, ALTERNATIVE.as_bytes(), || 0).unwrap();
+        assert_eq!(item.metadata.author.as_deref(), Some("bob@example.com"));
+        assert_eq!(
+            item.content.as_deref().map(str::trim),
+            Some("The plain version")
+        );
+    }
+
+    #[test]
+    fn missing_message_id() {
+        let item = message_item(1, NO_MESSAGE_ID.as_bytes(), || 0).unwrap();
+        assert!(item.external_id.starts_with("no-message-id:"));
+
+        let again = message_item(1, NO_MESSAGE_ID.as_bytes(), || 0).unwrap();
+        assert_eq!(item.external_id, again.external_id);
+
+        let edited = NO_MESSAGE_ID.replace("No ID here.", "Something else.");
+        let other = message_item(1, edited.as_bytes(), || 0).unwrap();
+        assert_ne!(item.external_id, other.external_id);
+    }
+
+    #[test]
+    fn mbox() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("archive.mbox");
+        let mbox = format!(
+            "From alice@example.com Tue Jan 10 12:00:00 2023\n{PLAIN}\n\
+             From bob@example.com Tue Jan 10 13:00:00 2023\n{ALTERNATIVE}\n\
+             From carol@example.com Tue Jan 10 14:00:00 2023\n{NO_MESSAGE_ID}\n"
+        );
+        std::fs::write(&path, mbox).unwrap();
+
+        let items = scan(&path);
+        let ids = items
+            .iter()
+            .map(|item| item.external_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids.len(), 3);
+        assert_eq!(&ids[..2], &["1@example.com", "2@example.com"]);
+        assert!(ids[2].starts_with("no-message-id:"));
+        assert_eq!(items[1].metadata.name.as_deref(), Some("Both kinds"));
+    }
+
+    #[test]
+    fn maildir() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for folder in ["", ".Archive"] {
+            for sub in ["cur", "new", "tmp"] {
+                std::fs::create_dir_all(root.join(folder).join(sub)).unwrap();
+            }
+        }
+
+        std::fs::write(root.join("new/1673352000.1.host"), PLAIN).unwrap();
+        std::fs::write(root.join("cur/1673355600.2.host:2,S"), ALTERNATIVE).unwrap();
+        std::fs::write(
+            root.join(".Archive/cur/1673359200.3.host:2,S"),
+            NO_MESSAGE_ID,
+        )
+        .unwrap();
+
+        let items = scan(root);
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].external_id, "1@example.com");
+        assert_eq!(items[1].external_id, "2@example.com");
+        assert_eq!(items[2].metadata.name.as_deref(), Some("Untracked"));
+    }
+
+    #[test]
+    fn maildir_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for folder in ["", ".Archive"] {
+            for sub in ["cur", "new", "tmp"] {
+                std::fs::create_dir_all(root.join(folder).join(sub)).unwrap();
+            }
+        }
+
+        // The same message, filed in both the inbox and the archive.
+        std::fs::write(root.join("cur/1673352000.1.host:2,S"), PLAIN).unwrap();
+        std::fs::write(root.join(".Archive/cur/1673352000.2.host:2,S"), PLAIN).unwrap();
+
+        let items = scan(root);
+        let ids = items
+            .iter()
+            .map(|item| item.external_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["1@example.com"]);
+    }
+}
@@ -0,0 +1,297 @@
+use std::{
+    io::BufRead,
+    path::{Path, PathBuf},
+};
+
+use ahash::HashSet;
+use eyre::{eyre, Context, Result};
+use itertools::Itertools;
+use quick_xml::events::Event;
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+use super::{
+    parse_html::html_to_text,
+    pipeline::{
+        prefilled_read, CountingVecSender, FoundItem, SourceScanner, SourceScannerReadResult,
+        SCAN_BATCH_SIZE,
+    },
+    ItemCompareStrategy,
+};
+use crate::{batch_sender::BatchSender, cancel::CancellationToken, Item, ItemMetadata};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EvernoteConfig {}
+
+/// A note read from an ENEX file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct EvernoteNote {
+    pub title: String,
+    pub author: Option<String>,
+    pub created: Option<OffsetDateTime>,
+    pub updated: Option<OffsetDateTime>,
+    pub tags: Vec<String>,
+    /// The text of the note, converted from ENML
+    pub text: String,
+}
+
+/// The elements inside a note that we read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NoteField {
+    Title,
+    Content,
+    Created,
+    Updated,
+    Tag,
+    Author,
+}
+
+/// Read the notes from an ENEX file, as exported by Evernote. Attachments are skipped.
+pub fn parse_enex(input: impl BufRead) -> Result<Vec<EvernoteNote>> {
+    let mut reader = quick_xml::Reader::from_reader(input);
+    let mut buf = Vec::new();
+
+    let mut notes = Vec::new();
+    let mut note: Option<EvernoteNote> = None;
+    let mut field = None;
+    let mut value = String::new();
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"note" => note = Some(EvernoteNote::default()),
+                name if note.is_some() => {
+                    field = match name {
+                        b"title" => Some(NoteField::Title),
+                        b"content" => Some(NoteField::Content),
+                        b"created" => Some(NoteField::Created),
+                        b"updated" => Some(NoteField::Updated),
+                        b"tag" => Some(NoteField::Tag),
+                        b"author" => Some(NoteField::Author),
+                        _ => None,
+                    };
+                    value.clear();
+                }
+                _ => {}
+            },
+            Event::Text(e) if field.is_some() => value.push_str(&e.unescape()?),
+            Event::CData(e) if field.is_some() => value.push_str(&String::from_utf8_lossy(&e)),
+            Event::End(e) => {
+                if e.local_name().as_ref() == b"note" {
+                    notes.extend(note.take());
+                } else if let (Some(note), Some(f)) = (note.as_mut(), field.take()) {
+                    let v = value.trim();
+                    match f {
+                        NoteField::Title => note.title = v.to_string(),
+                        NoteField::Content => note.text = enml_to_text(v),
+                        NoteField::Created => note.created = parse_enex_date(v),
+                        NoteField::Updated => note.updated = parse_enex_date(v),
+                        NoteField::Tag if !v.is_empty() => note.tags.push(v.to_string()),
+                        NoteField::Tag => {}
+                        NoteField::Author => {
+                            note.author = Some(v.to_string()).filter(|a| !a.is_empty())
+                        }
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+
+        buf.clear();
+    }
+
+    Ok(notes)
+}
+
+/// Convert the ENML content of a note to text. ENML is XHTML with a few extra elements, and
+/// encrypted sections can't be read, so they're left out.
+fn enml_to_text(enml: &str) -> String {
+    // Skip the XML declaration and doctype.
+    let mut html = enml.find("<en-note").map(|i| &enml[i..]).unwrap_or(enml);
+
+    let mut without_crypt = String::new();
+    while let Some(start) = html.find("<en-crypt") {
+        without_crypt.push_str(&html[..start]);
+        html = match html[start..].find("</en-crypt>") {
+            Some(end) => &html[start + end + "</en-crypt>".len()..],
+            None => "",
+        };
+    }
+    without_crypt.push_str(html);
+
+    html_to_text(&without_crypt)
+}
+
+/// Parse a date like `20230110T120000Z`.
+fn parse_enex_date(date: &str) -> Option<OffsetDateTime> {
+    let format =
+        time::format_description::parse("[year][month][day]T[hour][minute][second]Z").ok()?;
+    PrimitiveDateTime::parse(date, &format)
+        .ok()
+        .map(|t| t.assume_utc())
+}
+
+/// Reads the notes from Evernote's ENEX exports. The location can be a single file, or a
+/// directory of them, since Evernote exports each notebook separately.
+pub struct EvernoteScanner {
+    pub source_id: i64,
+    pub location: String,
+    pub config: EvernoteConfig,
+}
+
+impl EvernoteScanner {
+    fn enex_files(&self) -> Vec<PathBuf> {
+        let root = Path::new(&self.location);
+        if root.is_file() {
+            return vec![root.to_path_buf()];
+        }
+
+        ignore::WalkBuilder::new(root)
+            .build()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.into_path())
+            .filter(|path| {
+                path.extension()
+                    .map_or(false, |ext| ext.eq_ignore_ascii_case("enex"))
+            })
+            .sorted()
+            .collect()
+    }
+}
+
+impl SourceScanner for EvernoteScanner {
+    fn scan(&self, output: CountingVecSender<Item>, cancel: &CancellationToken) -> Result<()> {
+        let sender = BatchSender::new(SCAN_BATCH_SIZE, output);
+        let mut seen = HashSet::default();
+
+        for path in self.enex_files() {
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            let file =
+                std::fs::File::open(&path).wrap_err_with(|| eyre!("Opening {}", path.display()))?;
+            let notes = parse_enex(std::io::BufReader::new(file))
+                .wrap_err_with(|| eyre!("Reading {}", path.display()))?;
+            let notebook = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string());
+
+            for note in notes {
+                if note.text.is_empty() {
+                    continue;
+                }
+
+                // ENEX files don't include note IDs, so notes are identified by their file and
+                // when they were created.
+                let key = match note.created {
+                    Some(created) => created.unix_timestamp().to_string(),
+                    None => note.title.clone(),
+                };
+                let mut external_id = format!("{}#{key}", path.display());
+                let mut n = 2;
+                while !seen.insert(external_id.clone()) {
+                    external_id = format!("{}#{key}-{n}", path.display());
+                    n += 1;
+                }
+
+                sender.add(Item {
+                    id: -1,
+                    source_id: self.source_id,
+                    external_id,
+                    hash: None,
+                    content: Some(note.text),
+                    raw_content: None,
+                    skipped: None,
+                    process_version: 0,
+                    metadata: ItemMetadata {
+                        name: Some(note.title).filter(|t| !t.is_empty()),
+                        author: note.author,
+                        description: notebook.clone(),
+                        mtime: note.updated.or(note.created),
+                        created: note.created,
+                        tags: Some(note.tags),
+                        ..Default::default()
+                    },
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read(
+        &self,
+        existing: Option<&FoundItem>,
+        compare_strategy: ItemCompareStrategy,
+        item: &mut Item,
+    ) -> Result<SourceScannerReadResult> {
+        // The notes were read from the ENEX file while scanning.
+        Ok(prefilled_read(existing, compare_strategy, item))
+    }
+
+    fn latest_process_version(&self) -> i32 {
+        0
+    }
+
+    fn reprocess(&self, _item: &mut Item) -> Result<SourceScannerReadResult> {
+        Ok(SourceScannerReadResult::Unchanged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enex() {
+        let enex = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE en-export SYSTEM "http://xml.evernote.com/pub/evernote-export4.dtd">
+<en-export export-date="20230111T000000Z" application="Evernote" version="10.0">
+  <note>
+    <title>Trip &amp; Plans</title>
+    <created>20230110T120000Z</created>
+    <updated>20230110T130000Z</updated>
+    <tag>travel</tag>
+    <tag>2023</tag>
+    <note-attributes>
+      <author>Alice</author>
+    </note-attributes>
+    <content>
+      <![CDATA[<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<!DOCTYPE en-note SYSTEM "http://xml.evernote.com/pub/enml2.dtd">
+<en-note><div>Pack the bags</div><en-crypt cipher="AES">c2VjcmV0</en-crypt><div>Book a hotel</div><en-media hash="abc" type="image/png"/></en-note>]]>
+    </content>
+    <resource>
+      <data encoding="base64">iVBORw0KGgo=</data>
+      <mime>image/png</mime>
+    </resource>
+  </note>
+  <note>
+    <title>Empty</title>
+    <content><![CDATA[<en-note></en-note>]]></content>
+  </note>
+</en-export>"#;
+
+        let notes = parse_enex(enex.as_bytes()).unwrap();
+        assert_eq!(notes.len(), 2);
+
+        let note = &notes[0];
+        assert_eq!(note.title, "Trip & Plans");
+        assert_eq!(note.author.as_deref(), Some("Alice"));
+        assert_eq!(
+            note.created,
+            Some(OffsetDateTime::from_unix_timestamp(1673352000).unwrap())
+        );
+        assert_eq!(
+            note.updated,
+            Some(OffsetDateTime::from_unix_timestamp(1673355600).unwrap())
+        );
+        assert_eq!(note.tags, vec!["travel", "2023"]);
+        assert!(note.text.contains("Pack the bags"));
+        assert!(note.text.contains("Book a hotel"));
+        assert!(!note.text.contains("c2VjcmV0"));
+
+        assert_eq!(notes[1].text, "");
+    }
+}
@@ -0,0 +1,135 @@
+//! Extract the text and metadata from document formats that aren't plain text.
+
+mod docx;
+mod epub;
+mod odf;
+mod pdf;
+mod xml;
+
+use std::path::Path;
+
+use ahash::HashMap;
+use once_cell::sync::Lazy;
+use thiserror::Error;
+
+use crate::Item;
+
+/// The text and metadata read from a document.
+#[derive(Debug, Default)]
+pub struct ExtractedContent {
+    pub text: String,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub description: Option<String>,
+}
+
+impl ExtractedContent {
+    /// Set the item's content and metadata. Metadata that the document doesn't have is left as
+    /// it was, so that a web page keeps the title that the browser saw.
+    pub fn apply(self, item: &mut Item) {
+        if self.title.is_some() {
+            item.metadata.name = self.title;
+        }
+        if self.author.is_some() {
+            item.metadata.author = self.author;
+        }
+        if self.description.is_some() {
+            item.metadata.description = self.description;
+        }
+        item.content = Some(self.text);
+    }
+}
+
+/// A document that couldn't be read.
+#[derive(Debug, Error)]
+#[error("Failed to read {format} document: {message}")]
+pub struct ExtractError {
+    pub format: &'static str,
+    pub message: String,
+}
+
+impl ExtractError {
+    pub fn new(format: &'static str, message: impl ToString) -> Self {
+        Self {
+            format,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Reads a document format.
+pub trait ContentExtractor: Send + Sync {
+    /// The name of the format, for error messages.
+    fn name(&self) -> &'static str;
+
+    /// The lowercase file extensions for the format, without the leading dot.
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// The MIME types for the format.
+    fn mime_types(&self) -> &'static [&'static str];
+
+    /// Check if the content is in this format. This is used when there's no file extension or
+    /// MIME type to go on, such as when reprocessing stored content.
+    fn detect(&self, content: &[u8]) -> bool;
+
+    fn extract(&self, content: &[u8]) -> Result<ExtractedContent, ExtractError>;
+}
+
+/// The content extractors, looked up by file extension, MIME type, or the content itself.
+#[derive(Default)]
+pub struct ExtractorRegistry {
+    extractors: Vec<Box<dyn ContentExtractor>>,
+    by_extension: HashMap<&'static str, usize>,
+    by_mime_type: HashMap<&'static str, usize>,
+}
+
+impl ExtractorRegistry {
+    /// Add an extractor. This replaces any earlier extractor for the same extensions or MIME
+    /// types.
+    pub fn register(&mut self, extractor: impl ContentExtractor + 'static) {
+        let index = self.extractors.len();
+        for ext in extractor.extensions() {
+            self.by_extension.insert(ext, index);
+        }
+        for mime_type in extractor.mime_types() {
+            self.by_mime_type.insert(mime_type, index);
+        }
+        self.extractors.push(Box::new(extractor));
+    }
+
+    pub fn for_extension(&self, extension: &str) -> Option<&dyn ContentExtractor> {
+        let index = self.by_extension.get(extension.to_lowercase().as_str())?;
+        Some(self.extractors[*index].as_ref())
+    }
+
+    pub fn for_path(&self, path: &Path) -> Option<&dyn ContentExtractor> {
+        self.for_extension(path.extension()?.to_str()?)
+    }
+
+    pub fn for_mime_type(&self, mime_type: &str) -> Option<&dyn ContentExtractor> {
+        let index = self.by_mime_type.get(mime_type.to_lowercase().as_str())?;
+        Some(self.extractors[*index].as_ref())
+    }
+
+    pub fn detect(&self, content: &[u8]) -> Option<&dyn ContentExtractor> {
+        self.extractors
+            .iter()
+            .rev()
+            .find(|e| e.detect(content))
+            .map(|e| e.as_ref())
+    }
+}
+
+static EXTRACTORS: Lazy<ExtractorRegistry> = Lazy::new(|| {
+    let mut registry = ExtractorRegistry::default();
+    registry.register(pdf::PdfExtractor);
+    registry.register(docx::DocxExtractor);
+    registry.register(odf::OdfExtractor);
+    registry.register(epub::EpubExtractor);
+    registry
+});
+
+/// The built-in content extractors.
+pub fn extractors() -> &'static ExtractorRegistry {
+    Lazy::force(&EXTRACTORS)
+}
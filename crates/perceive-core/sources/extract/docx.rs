@@ -0,0 +1,121 @@
+use super::{
+    xml::{dublin_core, is_zip, open_archive, read_entry, tidy_text, walk_xml, XmlEvent},
+    ContentExtractor, ExtractError, ExtractedContent,
+};
+
+const DOCUMENT_PATH: &str = "word/document.xml";
+
+/// Word documents in the Office Open XML format.
+pub struct DocxExtractor;
+
+impl ContentExtractor for DocxExtractor {
+    fn name(&self) -> &'static str {
+        "DOCX"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["docx"]
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/vnd.openxmlformats-officedocument.wordprocessingml.document"]
+    }
+
+    fn detect(&self, content: &[u8]) -> bool {
+        is_zip(content)
+            && open_archive(self.name(), content)
+                .map(|archive| archive.file_names().any(|name| name == DOCUMENT_PATH))
+                .unwrap_or(false)
+    }
+
+    fn extract(&self, content: &[u8]) -> Result<ExtractedContent, ExtractError> {
+        let mut archive = open_archive(self.name(), content)?;
+        let document = read_entry(self.name(), &mut archive, DOCUMENT_PATH)?
+            .ok_or_else(|| ExtractError::new(self.name(), "missing document.xml"))?;
+
+        // Text is only ever inside `<w:t>` elements. Other elements hold things like field codes
+        // that shouldn't be indexed.
+        let mut text = String::new();
+        let mut in_text = false;
+        walk_xml(self.name(), &document, |event| match event {
+            XmlEvent::Start("t") => in_text = true,
+            XmlEvent::End("t") => in_text = false,
+            XmlEvent::Start("tab") => text.push('\t'),
+            XmlEvent::Start("br" | "cr") => text.push('\n'),
+            XmlEvent::End("p") => text.push('\n'),
+            XmlEvent::Text(t) if in_text => text.push_str(t),
+            _ => {}
+        })?;
+
+        let metadata = read_entry(self.name(), &mut archive, "docProps/core.xml")?;
+        let (title, author, description) = match metadata {
+            Some(core) => dublin_core(self.name(), &core)?,
+            None => (None, None, None),
+        };
+
+        Ok(ExtractedContent {
+            text: tidy_text(&text),
+            title,
+            author,
+            description,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::xml::zip_archive, *};
+    use crate::sources::extract::{epub::EpubExtractor, extractors, odf::OdfExtractor};
+
+    const DOCUMENT: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<w:document xmlns:w="w"><w:body>
+<w:p><w:r><w:t>First</w:t><w:tab/><w:t>paragraph</w:t></w:r></w:p>
+<w:p><w:r><w:instrText>PAGE</w:instrText><w:t xml:space="preserve">Second </w:t><w:br/><w:t>line</w:t></w:r></w:p>
+</w:body></w:document>"##;
+
+    const CORE: &str = r##"<cp:coreProperties xmlns:cp="cp" xmlns:dc="dc">
+<dc:title>A Word Document</dc:title>
+<dc:creator>Jane Author</dc:creator>
+<dc:description>What it's about</dc:description>
+</cp:coreProperties>"##;
+
+    #[test]
+    fn text_and_metadata() {
+        let docx = zip_archive(&[(DOCUMENT_PATH, DOCUMENT), ("docProps/core.xml", CORE)]);
+        let extracted = DocxExtractor.extract(&docx).unwrap();
+        // Field codes like the page number aren't part of the text.
+        assert_eq!(extracted.text, "First\tparagraph\nSecond\nline");
+        assert_eq!(extracted.title.as_deref(), Some("A Word Document"));
+        assert_eq!(extracted.author.as_deref(), Some("Jane Author"));
+        assert_eq!(extracted.description.as_deref(), Some("What it's about"));
+
+        // The metadata is optional.
+        let docx = zip_archive(&[(DOCUMENT_PATH, DOCUMENT)]);
+        let extracted = DocxExtractor.extract(&docx).unwrap();
+        assert_eq!(extracted.title, None);
+    }
+
+    #[test]
+    fn detect() {
+        let docx = zip_archive(&[(DOCUMENT_PATH, DOCUMENT)]);
+        assert!(DocxExtractor.detect(&docx));
+        assert!(!OdfExtractor.detect(&docx));
+        assert!(!EpubExtractor.detect(&docx));
+        assert!(!DocxExtractor.detect(&zip_archive(&[("other.xml", DOCUMENT)])));
+        assert!(!DocxExtractor.detect(DOCUMENT.as_bytes()));
+
+        assert_eq!(extractors().detect(&docx).map(|e| e.name()), Some("DOCX"));
+        assert_eq!(
+            extractors().for_extension("DOCX").map(|e| e.name()),
+            Some("DOCX")
+        );
+        assert_eq!(
+            extractors()
+                .for_mime_type(
+                    "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+                )
+                .map(|e| e.name()),
+            Some("DOCX")
+        );
+    }
+}
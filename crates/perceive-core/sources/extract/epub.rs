@@ -0,0 +1,255 @@
+use ahash::HashMap;
+use quick_xml::events::Event;
+
+use super::{
+    xml::{dublin_core, is_zip, open_archive, read_entry, tidy_text, walk_xml, Archive, XmlEvent},
+    ContentExtractor, ExtractError, ExtractedContent,
+};
+
+const MIME_TYPE: &str = "application/epub+zip";
+
+/// Elements that end a line of text in the XHTML chapters.
+const BLOCK_ELEMENTS: [&str; 14] = [
+    "p",
+    "div",
+    "br",
+    "li",
+    "tr",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "blockquote",
+    "pre",
+    "dd",
+];
+
+pub struct EpubExtractor;
+
+impl EpubExtractor {
+    /// Find the package document, which lists the metadata and the chapters.
+    fn package_path(&self, archive: &mut Archive) -> Result<String, ExtractError> {
+        let container = read_entry(self.name(), archive, "META-INF/container.xml")?
+            .ok_or_else(|| ExtractError::new(self.name(), "missing container.xml"))?;
+
+        let mut reader = quick_xml::Reader::from_str(&container);
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(e) | Event::Empty(e)) if e.local_name().as_ref() == b"rootfile" => {
+                    let path = e
+                        .try_get_attribute("full-path")
+                        .ok()
+                        .flatten()
+                        .and_then(|attr| attr.unescape_value().ok())
+                        .map(|path| path.into_owned());
+                    if let Some(path) = path {
+                        return Ok(path);
+                    }
+                }
+                Ok(Event::Eof) => break,
+                Err(e) => return Err(ExtractError::new(self.name(), e)),
+                _ => {}
+            }
+        }
+
+        Err(ExtractError::new(
+            self.name(),
+            "no rootfile in container.xml",
+        ))
+    }
+
+    /// Read the chapter paths from the package document, in reading order.
+    fn spine(&self, package: &str) -> Result<Vec<String>, ExtractError> {
+        let mut manifest = HashMap::default();
+        let mut spine = Vec::new();
+
+        let mut reader = quick_xml::Reader::from_str(package);
+        loop {
+            let event = reader
+                .read_event()
+                .map_err(|e| ExtractError::new(self.name(), e))?;
+            let element = match event {
+                Event::Start(e) | Event::Empty(e) => e,
+                Event::Eof => break,
+                _ => continue,
+            };
+
+            let attr = |name: &str| {
+                element
+                    .try_get_attribute(name)
+                    .ok()
+                    .flatten()
+                    .and_then(|attr| attr.unescape_value().ok())
+                    .map(|value| value.into_owned())
+            };
+
+            match element.local_name().as_ref() {
+                b"item" => {
+                    if let Some((id, href)) = attr("id").zip(attr("href")) {
+                        manifest.insert(id, href);
+                    }
+                }
+                b"itemref" => {
+                    if let Some(idref) = attr("idref") {
+                        spine.push(idref);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(spine
+            .into_iter()
+            .filter_map(|id| manifest.remove(&id))
+            .collect())
+    }
+}
+
+impl ContentExtractor for EpubExtractor {
+    fn name(&self) -> &'static str {
+        "EPUB"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["epub"]
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &[MIME_TYPE]
+    }
+
+    fn detect(&self, content: &[u8]) -> bool {
+        is_zip(content)
+            && open_archive(self.name(), content)
+                .ok()
+                .and_then(|mut archive| read_entry(self.name(), &mut archive, "mimetype").ok())
+                .flatten()
+                .map(|mime_type| mime_type.trim() == MIME_TYPE)
+                .unwrap_or(false)
+    }
+
+    fn extract(&self, content: &[u8]) -> Result<ExtractedContent, ExtractError> {
+        let mut archive = open_archive(self.name(), content)?;
+        let package_path = self.package_path(&mut archive)?;
+        let package = read_entry(self.name(), &mut archive, &package_path)?
+            .ok_or_else(|| ExtractError::new(self.name(), format!("missing {package_path}")))?;
+
+        let (title, author, description) = dublin_core(self.name(), &package)?;
+
+        // Chapter paths are relative to the package document.
+        let base_dir = match package_path.rfind('/') {
+            Some(pos) => &package_path[..=pos],
+            None => "",
+        };
+
+        let mut text = String::new();
+        for chapter_path in self.spine(&package)? {
+            let chapter_path = format!(
+                "{base_dir}{}",
+                chapter_path.split('#').next().unwrap_or_default()
+            );
+            let Some(chapter) = read_entry(self.name(), &mut archive, &chapter_path)? else {
+                continue;
+            };
+
+            let mut in_body = false;
+            let mut skip_depth = 0;
+            walk_xml(self.name(), &chapter, |event| match event {
+                XmlEvent::Start("body") => in_body = true,
+                XmlEvent::End("body") => in_body = false,
+                XmlEvent::Start("script" | "style") => skip_depth += 1,
+                XmlEvent::End("script" | "style") => skip_depth -= 1,
+                XmlEvent::End(name) if BLOCK_ELEMENTS.contains(&name) => text.push('\n'),
+                XmlEvent::Text(t) if in_body && skip_depth == 0 => text.push_str(t),
+                _ => {}
+            })?;
+
+            text.push_str("\n\n");
+        }
+
+        Ok(ExtractedContent {
+            text: tidy_text(&text),
+            title,
+            author,
+            description,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::xml::zip_archive, *};
+    use crate::sources::extract::{docx::DocxExtractor, extractors, odf::OdfExtractor};
+
+    const CONTAINER: &str = r##"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+<rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"##;
+
+    const PACKAGE: &str = r##"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+<metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+<dc:title>An E-book</dc:title>
+<dc:creator>Ann Author</dc:creator>
+<dc:description>A short book</dc:description>
+</metadata>
+<manifest>
+<item id="one" href="one.xhtml" media-type="application/xhtml+xml"/>
+<item id="preface" href="text/preface.xhtml#start" media-type="application/xhtml+xml"/>
+</manifest>
+<spine><itemref idref="preface"/><itemref idref="one"/></spine>
+</package>"##;
+
+    const CHAPTER_ONE: &str = r##"<html xmlns="http://www.w3.org/1999/xhtml">
+<head><title>Not text</title><style>p { margin: 0; }</style></head>
+<body><h1>Chapter One</h1><p>It&nbsp;begins.</p><script>ignored();</script></body>
+</html>"##;
+
+    const PREFACE: &str =
+        r##"<html xmlns="http://www.w3.org/1999/xhtml"><body><p>Preface</p></body></html>"##;
+
+    fn epub() -> Vec<u8> {
+        zip_archive(&[
+            ("mimetype", MIME_TYPE),
+            ("META-INF/container.xml", CONTAINER),
+            ("OEBPS/content.opf", PACKAGE),
+            ("OEBPS/one.xhtml", CHAPTER_ONE),
+            ("OEBPS/text/preface.xhtml", PREFACE),
+        ])
+    }
+
+    #[test]
+    fn text_and_metadata() {
+        let extracted = EpubExtractor.extract(&epub()).unwrap();
+        // The chapters are read in spine order.
+        assert_eq!(extracted.text, "Preface\n\nChapter One\nIt\u{a0}begins.");
+        assert_eq!(extracted.title.as_deref(), Some("An E-book"));
+        assert_eq!(extracted.author.as_deref(), Some("Ann Author"));
+        assert_eq!(extracted.description.as_deref(), Some("A short book"));
+    }
+
+    #[test]
+    fn missing_container() {
+        let epub = zip_archive(&[("mimetype", MIME_TYPE), ("OEBPS/content.opf", PACKAGE)]);
+        assert!(EpubExtractor.extract(&epub).is_err());
+    }
+
+    #[test]
+    fn detect() {
+        let epub = epub();
+        assert!(EpubExtractor.detect(&epub));
+        assert!(!DocxExtractor.detect(&epub));
+        assert!(!OdfExtractor.detect(&epub));
+
+        assert_eq!(extractors().detect(&epub).map(|e| e.name()), Some("EPUB"));
+        assert_eq!(
+            extractors().for_extension("epub").map(|e| e.name()),
+            Some("EPUB")
+        );
+        assert_eq!(
+            extractors().for_mime_type(MIME_TYPE).map(|e| e.name()),
+            Some("EPUB")
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use super::{
+    xml::{dublin_core, is_zip, open_archive, read_entry, tidy_text, walk_xml, XmlEvent},
+    ContentExtractor, ExtractError, ExtractedContent,
+};
+
+/// OpenDocument text documents and spreadsheets.
+pub struct OdfExtractor;
+
+impl ContentExtractor for OdfExtractor {
+    fn name(&self) -> &'static str {
+        "OpenDocument"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["odt", "ods"]
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &[
+            "application/vnd.oasis.opendocument.text",
+            "application/vnd.oasis.opendocument.spreadsheet",
+        ]
+    }
+
+    fn detect(&self, content: &[u8]) -> bool {
+        is_zip(content)
+            && open_archive(self.name(), content)
+                .ok()
+                .and_then(|mut archive| read_entry(self.name(), &mut archive, "mimetype").ok())
+                .flatten()
+                .map(|mime_type| self.mime_types().contains(&mime_type.trim()))
+                .unwrap_or(false)
+    }
+
+    fn extract(&self, content: &[u8]) -> Result<ExtractedContent, ExtractError> {
+        let mut archive = open_archive(self.name(), content)?;
+        let document = read_entry(self.name(), &mut archive, "content.xml")?
+            .ok_or_else(|| ExtractError::new(self.name(), "missing content.xml"))?;
+
+        // Paragraphs and headings hold all the text. In spreadsheets, each cell has its own
+        // paragraph.
+        let mut text = String::new();
+        let mut in_body = false;
+        walk_xml(self.name(), &document, |event| match event {
+            XmlEvent::Start("body") => in_body = true,
+            XmlEvent::End("body") => in_body = false,
+            XmlEvent::Start("s") => text.push(' '),
+            XmlEvent::Start("tab") => text.push('\t'),
+            XmlEvent::Start("line-break") => text.push('\n'),
+            XmlEvent::End("p" | "h") => text.push('\n'),
+            XmlEvent::Text(t) if in_body => text.push_str(t),
+            _ => {}
+        })?;
+
+        let metadata = read_entry(self.name(), &mut archive, "meta.xml")?;
+        let (title, author, description) = match metadata {
+            Some(meta) => dublin_core(self.name(), &meta)?,
+            None => (None, None, None),
+        };
+
+        Ok(ExtractedContent {
+            text: tidy_text(&text),
+            title,
+            author,
+            description,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::xml::zip_archive, *};
+    use crate::sources::extract::{docx::DocxExtractor, epub::EpubExtractor, extractors};
+
+    const CONTENT: &str = r##"<office:document-content xmlns:office="office" xmlns:text="text">
+<office:automatic-styles><style:style>Not text</style:style></office:automatic-styles>
+<office:body><office:text><text:h>Heading</text:h><text:p>One<text:s/>two<text:tab/>three</text:p><text:p>Next<text:line-break/>line</text:p></office:text></office:body>
+</office:document-content>"##;
+
+    const META: &str = r##"<office:document-meta xmlns:office="office" xmlns:dc="dc" xmlns:meta="meta">
+<office:meta>
+<dc:title>An OpenDocument</dc:title>
+<meta:initial-creator>Someone Else</meta:initial-creator>
+<dc:creator>Sam Author</dc:creator>
+</office:meta>
+</office:document-meta>"##;
+
+    fn odt() -> Vec<u8> {
+        zip_archive(&[
+            ("mimetype", "application/vnd.oasis.opendocument.text"),
+            ("content.xml", CONTENT),
+            ("meta.xml", META),
+        ])
+    }
+
+    #[test]
+    fn text_and_metadata() {
+        let extracted = OdfExtractor.extract(&odt()).unwrap();
+        assert_eq!(extracted.text, "Heading\nOne two\tthree\nNext\nline");
+        assert_eq!(extracted.title.as_deref(), Some("An OpenDocument"));
+        assert_eq!(extracted.author.as_deref(), Some("Sam Author"));
+        assert_eq!(extracted.description, None);
+    }
+
+    #[test]
+    fn detect() {
+        let odt = odt();
+        assert!(OdfExtractor.detect(&odt));
+        assert!(!DocxExtractor.detect(&odt));
+        assert!(!EpubExtractor.detect(&odt));
+
+        // Other OpenDocument formats, like presentations, aren't handled.
+        let odp = zip_archive(&[
+            (
+                "mimetype",
+                "application/vnd.oasis.opendocument.presentation",
+            ),
+            ("content.xml", CONTENT),
+        ]);
+        assert!(!OdfExtractor.detect(&odp));
+
+        assert_eq!(
+            extractors().detect(&odt).map(|e| e.name()),
+            Some("OpenDocument")
+        );
+        for ext in ["odt", "ods"] {
+            assert_eq!(
+                extractors().for_extension(ext).map(|e| e.name()),
+                Some("OpenDocument")
+            );
+        }
+        assert!(extractors().for_extension("odp").is_none());
+    }
+}
@@ -0,0 +1,154 @@
+use std::panic::AssertUnwindSafe;
+
+use pdf_extract::{decode_text_string, Document, PlainTextOutput};
+
+use super::{ContentExtractor, ExtractError, ExtractedContent};
+
+pub struct PdfExtractor;
+
+impl ContentExtractor for PdfExtractor {
+    fn name(&self) -> &'static str {
+        "PDF"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["pdf"]
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/pdf"]
+    }
+
+    fn detect(&self, content: &[u8]) -> bool {
+        content.starts_with(b"%PDF-")
+    }
+
+    fn extract(&self, content: &[u8]) -> Result<ExtractedContent, ExtractError> {
+        let error = |e: &dyn ToString| ExtractError::new(self.name(), e.to_string());
+
+        let mut doc = Document::load_mem(content).map_err(|e| error(&e))?;
+        if doc.is_encrypted() {
+            // Many PDFs are encrypted only to set permissions, and open with an empty password.
+            doc.decrypt("").map_err(|e| error(&e))?;
+        }
+
+        // The extractor panics on some malformed documents, so don't let one bad file take down
+        // the whole scan.
+        let text = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut text = String::new();
+            {
+                let mut output = PlainTextOutput::new(&mut text);
+                pdf_extract::output_doc(&doc, &mut output)?;
+            }
+            Ok::<_, pdf_extract::OutputError>(text)
+        }))
+        .map_err(|_| error(&"the extractor crashed"))?
+        .map_err(|e| error(&e))?;
+
+        let info = doc
+            .trailer
+            .get(b"Info")
+            .and_then(|info| doc.dereference(info))
+            .and_then(|(_, info)| info.as_dict())
+            .ok();
+        let info_string = |key: &[u8]| {
+            info.and_then(|info| info.get(key).ok())
+                .and_then(|value| doc.dereference(value).ok())
+                .and_then(|(_, value)| decode_text_string(value).ok())
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+
+        Ok(ExtractedContent {
+            title: info_string(b"Title"),
+            author: info_string(b"Author"),
+            description: info_string(b"Subject"),
+            text,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pdf_extract::{
+        content::{Content, Operation},
+        dictionary, Object, Stream,
+    };
+
+    use super::*;
+
+    /// Build a one-page PDF with a title and author.
+    fn sample_pdf() -> Vec<u8> {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+        });
+        let resources_id = doc.add_object(dictionary! {
+            "Font" => dictionary! { "F1" => font_id },
+        });
+
+        let content = Content {
+            operations: vec![
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec!["F1".into(), 24.into()]),
+                Operation::new("Td", vec![100.into(), 600.into()]),
+                Operation::new("Tj", vec![Object::string_literal("Hello from a PDF")]),
+                Operation::new("ET", vec![]),
+            ],
+        };
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+        });
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+                "Resources" => resources_id,
+                "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+            }),
+        );
+
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        let info_id = doc.add_object(dictionary! {
+            "Title" => Object::string_literal("A Test Document"),
+            "Author" => Object::string_literal("Jane Author"),
+            "Subject" => Object::string_literal("  "),
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc.trailer.set("Info", info_id);
+
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn text_and_info() {
+        let pdf = sample_pdf();
+        assert!(PdfExtractor.detect(&pdf));
+
+        let extracted = PdfExtractor.extract(&pdf).unwrap();
+        assert!(extracted.text.contains("Hello from a PDF"));
+        assert_eq!(extracted.title.as_deref(), Some("A Test Document"));
+        assert_eq!(extracted.author.as_deref(), Some("Jane Author"));
+        // Blank info fields are left out.
+        assert_eq!(extracted.description, None);
+    }
+
+    #[test]
+    fn invalid_pdf() {
+        let err = PdfExtractor.extract(b"%PDF-1.5 not really").unwrap_err();
+        assert_eq!(err.format, "PDF");
+    }
+}
@@ -0,0 +1,255 @@
+//! Helpers for the formats that are XML files inside a ZIP archive.
+
+use std::io::{Cursor, Read};
+
+use quick_xml::events::Event;
+use zip::ZipArchive;
+
+use super::ExtractError;
+
+pub type Archive<'a> = ZipArchive<Cursor<&'a [u8]>>;
+
+/// The largest file that will be read out of an archive. The sizes in the ZIP headers can't be
+/// trusted, and a small archive can decompress to something huge.
+const MAX_ENTRY_SIZE: u64 = 64 * 1024 * 1024;
+
+pub enum XmlEvent<'a> {
+    /// An element opened. Empty elements are reported as a start followed by an end.
+    Start(&'a str),
+    End(&'a str),
+    Text(&'a str),
+}
+
+/// Check for the ZIP file header.
+pub fn is_zip(content: &[u8]) -> bool {
+    content.starts_with(b"PK\x03\x04")
+}
+
+pub fn open_archive<'a>(
+    format: &'static str,
+    content: &'a [u8],
+) -> Result<Archive<'a>, ExtractError> {
+    ZipArchive::new(Cursor::new(content)).map_err(|e| ExtractError::new(format, e))
+}
+
+/// Read a file from the archive, or None if it doesn't exist.
+pub fn read_entry(
+    format: &'static str,
+    archive: &mut Archive,
+    name: &str,
+) -> Result<Option<String>, ExtractError> {
+    let mut file = match archive.by_name(name) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(ExtractError::new(format, e)),
+    };
+
+    read_limited(format, name, &mut file, MAX_ENTRY_SIZE).map(Some)
+}
+
+/// Read a file as UTF-8 text, failing if it's longer than `limit` bytes.
+fn read_limited(
+    format: &'static str,
+    name: &str,
+    file: impl Read,
+    limit: u64,
+) -> Result<String, ExtractError> {
+    let mut output = Vec::new();
+    file.take(limit + 1)
+        .read_to_end(&mut output)
+        .map_err(|e| ExtractError::new(format, format!("{name}: {e}")))?;
+
+    if output.len() as u64 > limit {
+        return Err(ExtractError::new(
+            format,
+            format!("{name} is larger than {limit} bytes"),
+        ));
+    }
+
+    String::from_utf8(output).map_err(|e| ExtractError::new(format, format!("{name}: {e}")))
+}
+
+/// Walk through an XML document, calling `handle` for each element and run of text. Elements
+/// are identified by their name without the namespace prefix.
+pub fn walk_xml(
+    format: &'static str,
+    xml: &str,
+    mut handle: impl FnMut(XmlEvent),
+) -> Result<(), ExtractError> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    reader.expand_empty_elements(true);
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| ExtractError::new(format, e))?;
+        match event {
+            Event::Start(e) => {
+                let name = e.local_name();
+                handle(XmlEvent::Start(
+                    std::str::from_utf8(name.as_ref()).unwrap_or_default(),
+                ));
+            }
+            Event::End(e) => {
+                let name = e.local_name();
+                handle(XmlEvent::End(
+                    std::str::from_utf8(name.as_ref()).unwrap_or_default(),
+                ));
+            }
+            Event::Text(e) => {
+                // XHTML may use HTML entities that XML doesn't define. If there are others
+                // that we don't know either, fall back to the raw text.
+                let text = match e.unescape_with(html_entity) {
+                    Ok(text) => text,
+                    Err(_) => String::from_utf8_lossy(&e),
+                };
+                handle(XmlEvent::Text(&text));
+            }
+            Event::CData(e) => {
+                handle(XmlEvent::Text(&String::from_utf8_lossy(&e)));
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolve the HTML entities that commonly show up in XHTML.
+fn html_entity(name: &str) -> Option<&'static str> {
+    let value = match name {
+        "nbsp" => "\u{a0}",
+        "shy" => "\u{ad}",
+        "ndash" => "\u{2013}",
+        "mdash" => "\u{2014}",
+        "lsquo" => "\u{2018}",
+        "rsquo" => "\u{2019}",
+        "ldquo" => "\u{201c}",
+        "rdquo" => "\u{201d}",
+        "hellip" => "\u{2026}",
+        "copy" => "\u{a9}",
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+/// Read the Dublin Core title, creator, and description from a metadata document. The first
+/// value found for each is used.
+pub fn dublin_core(
+    format: &'static str,
+    xml: &str,
+) -> Result<(Option<String>, Option<String>, Option<String>), ExtractError> {
+    let mut values: [Option<String>; 3] = Default::default();
+    let mut current = None;
+
+    walk_xml(format, xml, |event| match event {
+        XmlEvent::Start(name) => {
+            current = match name {
+                "title" => Some(0),
+                "creator" => Some(1),
+                "description" => Some(2),
+                _ => None,
+            }
+            .filter(|&i| values[i].is_none());
+        }
+        XmlEvent::End(_) => current = None,
+        XmlEvent::Text(text) => {
+            let text = text.trim();
+            if let Some(i) = current.filter(|_| !text.is_empty()) {
+                values[i].get_or_insert_with(String::new).push_str(text);
+            }
+        }
+    })?;
+
+    let [title, creator, description] = values;
+    Ok((title, creator, description))
+}
+
+/// Collapse the whitespace at the end of each line and the runs of blank lines that come from
+/// empty paragraphs.
+pub fn tidy_text(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut blank_lines = 0;
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank_lines += 1;
+            if blank_lines > 1 {
+                continue;
+            }
+        } else {
+            blank_lines = 0;
+        }
+
+        output.push_str(line);
+        output.push('\n');
+    }
+
+    output.trim().to_string()
+}
+
+/// Build a ZIP archive holding the given files, for testing the extractors.
+#[cfg(test)]
+pub fn zip_archive(files: &[(&str, &str)]) -> Vec<u8> {
+    use std::io::Write;
+
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, content) in files {
+        writer
+            .start_file(*name, zip::write::FileOptions::default())
+            .unwrap();
+        writer.write_all(content.as_bytes()).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dublin_core_metadata() {
+        let xml = r##"<cp:coreProperties xmlns:cp="cp" xmlns:dc="dc">
+            <dc:title>First</dc:title>
+            <dc:title>Second</dc:title>
+            <dc:creator>Someone &amp; Co</dc:creator>
+        </cp:coreProperties>"##;
+
+        let (title, creator, description) = dublin_core("test", xml).unwrap();
+        assert_eq!(title.as_deref(), Some("First"));
+        assert_eq!(creator.as_deref(), Some("Someone & Co"));
+        assert_eq!(description, None);
+    }
+
+    #[test]
+    fn entry_size_limit() {
+        let text = read_limited("test", "a.xml", "<a>1234</a>".as_bytes(), 11).unwrap();
+        assert_eq!(text, "<a>1234</a>");
+
+        let err = read_limited("test", "a.xml", "<a>12345</a>".as_bytes(), 11).unwrap_err();
+        assert_eq!(err.message, "a.xml is larger than 11 bytes");
+    }
+
+    #[test]
+    fn html_entities() {
+        let mut text = String::new();
+        walk_xml("test", "<p>a&nbsp;b&amp;c&mdash;d</p>", |event| {
+            if let XmlEvent::Text(t) = event {
+                text.push_str(t);
+            }
+        })
+        .unwrap();
+
+        assert_eq!(text, "a\u{a0}b&c\u{2014}d");
+    }
+
+    #[test]
+    fn tidy_blank_lines() {
+        assert_eq!(
+            tidy_text("\n\none  \n\n\n\ntwo\nthree\n\n"),
+            "one\n\ntwo\nthree"
+        );
+    }
+}
@@ -0,0 +1,583 @@
+use std::path::Path;
+
+use ahash::HashMap;
+use eyre::{eyre, Context, Result};
+use itertools::Itertools;
+use parking_lot::Mutex;
+use quick_xml::events::Event;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use time::{
+    format_description::well_known::{Rfc2822, Rfc3339},
+    OffsetDateTime,
+};
+
+use super::{
+    parse_html::{
+        html_to_text, read_web_page_from, reprocess_html_article, should_skip, HTML_PROCESS_VERSION,
+    },
+    pipeline::{
+        content_hash, prefilled_read, CountingVecSender, FoundItem, SourceScanner,
+        SourceScannerReadResult, SCAN_BATCH_SIZE,
+    },
+    web_fetcher::{FetchConfig, WebFetcher},
+    ItemCompareStrategy,
+};
+use crate::{batch_sender::BatchSender, cancel::CancellationToken, Item, ItemMetadata};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FeedsConfig {
+    /// The URLs of the feeds to read. When the source's location is an OPML file, the feeds
+    /// listed in it are read as well.
+    pub feeds: Vec<String>,
+    /// Domains that we should never check
+    pub skip: Vec<String>,
+    #[serde(default)]
+    pub fetch: FetchConfig,
+}
+
+/// Entries with less text than this are assumed to be summaries, and the linked page is fetched
+/// instead.
+const FULL_TEXT_MIN_LENGTH: usize = 1000;
+
+/// An entry read from an RSS or Atom feed. Summaries and content have been converted to text.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FeedEntry {
+    /// The RSS `guid` or Atom `id` of the entry
+    pub guid: Option<String>,
+    pub link: Option<String>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub published: Option<OffsetDateTime>,
+    pub updated: Option<OffsetDateTime>,
+    pub summary: Option<String>,
+    pub content: Option<String>,
+}
+
+/// Parse an RSS or Atom feed.
+pub fn parse_feed(content: &[u8]) -> Result<Vec<FeedEntry>> {
+    match rss::Channel::read_from(content) {
+        Ok(channel) => Ok(channel.items().iter().map(rss_entry).collect()),
+        Err(rss_error) => match atom_syndication::Feed::read_from(content) {
+            Ok(feed) => Ok(feed
+                .entries()
+                .iter()
+                .map(|entry| atom_entry(&feed, entry))
+                .collect()),
+            Err(_) => Err(eyre!("Not an RSS or Atom feed: {rss_error}")),
+        },
+    }
+}
+
+fn rss_entry(item: &rss::Item) -> FeedEntry {
+    let dublin_core = item.dublin_core_ext();
+    let author = dublin_core
+        .and_then(|dc| dc.creators().first())
+        .map(String::as_str)
+        .or_else(|| item.author());
+    let published = item
+        .pub_date()
+        .or_else(|| {
+            dublin_core
+                .and_then(|dc| dc.dates().first())
+                .map(String::as_str)
+        })
+        .and_then(parse_date);
+
+    FeedEntry {
+        guid: item.guid().map(|guid| guid.value().to_string()),
+        link: item.link().map(String::from),
+        title: item.title().map(html_to_text),
+        author: author.map(|a| a.trim().to_string()),
+        published,
+        updated: None,
+        summary: item.description().map(html_to_text),
+        content: item.content().map(html_to_text),
+    }
+}
+
+fn atom_entry(feed: &atom_syndication::Feed, entry: &atom_syndication::Entry) -> FeedEntry {
+    let timestamp = |date: &atom_syndication::FixedDateTime| {
+        OffsetDateTime::from_unix_timestamp(date.timestamp()).ok()
+    };
+
+    // Prefer the link to the entry itself over links to comments, enclosures, etc.
+    let link = entry
+        .links()
+        .iter()
+        .find(|link| link.rel() == "alternate")
+        .or_else(|| entry.links().first())
+        .map(|link| link.href().to_string());
+
+    let author = entry
+        .authors()
+        .first()
+        .or_else(|| feed.authors().first())
+        .map(|person| person.name().to_string());
+
+    let content = entry.content().and_then(|content| {
+        let value = content.value()?;
+        Some(match content.content_type() {
+            Some("text") | Some("text/plain") => value.to_string(),
+            _ => html_to_text(value),
+        })
+    });
+
+    FeedEntry {
+        guid: Some(entry.id().to_string()).filter(|id| !id.is_empty()),
+        link,
+        title: Some(atom_text(entry.title())).filter(|t| !t.is_empty()),
+        author,
+        published: entry.published().and_then(timestamp),
+        updated: timestamp(entry.updated()),
+        summary: entry.summary().map(atom_text),
+        content,
+    }
+}
+
+fn atom_text(text: &atom_syndication::Text) -> String {
+    match text.r#type {
+        atom_syndication::TextType::Text => text.value.trim().to_string(),
+        atom_syndication::TextType::Html | atom_syndication::TextType::Xhtml => {
+            html_to_text(&text.value)
+        }
+    }
+}
+
+/// Parse a date from a feed. RSS uses RFC 2822 dates, but some feeds use RFC 3339 instead.
+fn parse_date(date: &str) -> Option<OffsetDateTime> {
+    let date = date.trim();
+    OffsetDateTime::parse(date, &Rfc2822)
+        .or_else(|_| OffsetDateTime::parse(date, &Rfc3339))
+        .ok()
+}
+
+/// Read the feed URLs from an OPML file, as exported by feed readers.
+pub fn parse_opml(content: &str) -> Result<Vec<String>> {
+    let mut reader = quick_xml::Reader::from_str(content);
+    let mut urls = Vec::new();
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"outline" => {
+                let url = e
+                    .attributes()
+                    .flatten()
+                    .find(|attr| attr.key.as_ref().eq_ignore_ascii_case(b"xmlUrl"))
+                    .and_then(|attr| attr.unescape_value().ok())
+                    .map(|url| url.trim().to_string())
+                    .filter(|url| !url.is_empty());
+                if let Some(url) = url {
+                    urls.push(url);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(urls)
+}
+
+/// Get the external ID for an entry. GUIDs are used when possible, since links sometimes change
+/// between fetches. GUIDs that aren't URLs are attached to the feed URL so that they stay unique
+/// across feeds.
+fn entry_external_id(feed_url: &Url, entry: &FeedEntry, link: Option<&Url>) -> Option<String> {
+    match entry
+        .guid
+        .as_deref()
+        .map(str::trim)
+        .filter(|g| !g.is_empty())
+    {
+        Some(guid) if Url::parse(guid).is_ok() => Some(guid.to_string()),
+        Some(guid) => {
+            let mut url = feed_url.clone();
+            url.set_fragment(Some(guid));
+            Some(url.to_string())
+        }
+        None => link.map(|link| link.to_string()),
+    }
+}
+
+/// Reads the entries from a list of RSS and Atom feeds.
+pub struct FeedsScanner {
+    pub source_id: i64,
+    /// The path to an OPML file, or an empty string
+    pub location: String,
+    pub config: FeedsConfig,
+    pub fetcher: WebFetcher,
+    /// The link for each entry that needs its page fetched, by external ID
+    entry_links: Mutex<HashMap<String, String>>,
+}
+
+impl FeedsScanner {
+    pub fn new(source_id: i64, location: String, config: FeedsConfig) -> Result<Self> {
+        let fetcher = WebFetcher::new(config.fetch.clone(), true)?;
+        Ok(Self {
+            source_id,
+            location,
+            config,
+            fetcher,
+            entry_links: Mutex::new(HashMap::default()),
+        })
+    }
+
+    /// Get the URLs of the feeds from the config and the OPML file.
+    fn feed_urls(&self) -> Result<Vec<String>> {
+        let mut urls = self.config.feeds.clone();
+        if !self.location.is_empty() {
+            let path = Path::new(&self.location);
+            let opml = std::fs::read_to_string(path)
+                .wrap_err_with(|| eyre!("Reading {}", path.display()))?;
+            let feeds = parse_opml(&opml).wrap_err_with(|| eyre!("Parsing {}", path.display()))?;
+            urls.extend(feeds);
+        }
+
+        Ok(urls.into_iter().unique().collect())
+    }
+
+    fn fetch_feed(&self, url: &Url) -> Result<Vec<FeedEntry>> {
+        let permit = self.fetcher.acquire(url);
+        let response = self
+            .fetcher
+            .client()
+            .get(url.clone())
+            .send()
+            .and_then(|r| r.error_for_status())
+            .and_then(|r| r.bytes())?;
+        drop(permit);
+
+        parse_feed(&response)
+    }
+
+    fn entry_item(&self, feed_url: &Url, entry: FeedEntry) -> Option<Item> {
+        let link = entry
+            .link
+            .as_deref()
+            .and_then(|link| feed_url.join(link.trim()).ok())
+            .filter(|link| link.scheme().starts_with("http"));
+        let external_id = entry_external_id(feed_url, &entry, link.as_ref())?;
+
+        let link = link.filter(|link| !should_skip(&self.config.skip, link));
+        let text = entry
+            .content
+            .filter(|c| !c.is_empty())
+            .or_else(|| entry.summary.clone())
+            .filter(|t| !t.is_empty());
+        let full_text = text
+            .as_ref()
+            .map_or(false, |t| t.len() >= FULL_TEXT_MIN_LENGTH);
+
+        let content = match link {
+            Some(link) if !full_text => {
+                self.entry_links
+                    .lock()
+                    .insert(external_id.clone(), link.to_string());
+                None
+            }
+            // Without a page to fetch, the feed's text is all there is.
+            _ => Some(text?),
+        };
+
+        Some(Item {
+            id: -1,
+            source_id: self.source_id,
+            external_id,
+            hash: content.as_deref().map(|c| content_hash(&[c])),
+            content,
+            raw_content: None,
+            skipped: None,
+            process_version: HTML_PROCESS_VERSION,
+            metadata: ItemMetadata {
+                name: entry.title,
+                author: entry.author,
+                description: entry.summary,
+                mtime: entry.published.or(entry.updated),
+                atime: entry.updated.or(entry.published),
+                ..Default::default()
+            },
+        })
+    }
+}
+
+impl SourceScanner for FeedsScanner {
+    fn scan(
+        &self,
+        output: CountingVecSender<Item>,
+        cancel: &CancellationToken,
+    ) -> Result<(), eyre::Report> {
+        let sender = BatchSender::new(SCAN_BATCH_SIZE, output);
+        let mut failed = Vec::new();
+        for feed_url in self.feed_urls()? {
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            let Ok(parsed) = Url::parse(&feed_url) else {
+                failed.push(format!("{feed_url}: invalid URL"));
+                continue;
+            };
+
+            if should_skip(&self.config.skip, &parsed) {
+                continue;
+            }
+
+            // Keep going if one feed fails, so that the rest still get updated.
+            let entries = match self.fetch_feed(&parsed) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    failed.push(format!("{feed_url}: {e}"));
+                    continue;
+                }
+            };
+
+            let items = entries
+                .into_iter()
+                .filter_map(|entry| self.entry_item(&parsed, entry))
+                .unique_by(|item| item.external_id.clone());
+            for item in items {
+                sender.add(item)?;
+            }
+        }
+
+        if !failed.is_empty() {
+            return Err(eyre!("Failed to read feeds:\n{}", failed.join("\n")));
+        }
+
+        Ok(())
+    }
+
+    fn read(
+        &self,
+        existing: Option<&FoundItem>,
+        compare_strategy: ItemCompareStrategy,
+        item: &mut Item,
+    ) -> Result<SourceScannerReadResult, eyre::Report> {
+        // Entries with their full text in the feed were already read during the scan.
+        let Some(link) = self.entry_links.lock().get(&item.external_id).cloned() else {
+            return Ok(prefilled_read(existing, compare_strategy, item));
+        };
+
+        // The page's dates don't matter here, so keep the dates from the feed.
+        let (mtime, atime) = (item.metadata.mtime, item.metadata.atime);
+        let result = read_web_page_from(&self.fetcher, &link, existing, compare_strategy, item)?;
+        item.metadata.mtime = mtime.or(item.metadata.mtime);
+        item.metadata.atime = atime;
+        Ok(result)
+    }
+
+    fn latest_process_version(&self) -> i32 {
+        HTML_PROCESS_VERSION
+    }
+
+    fn reprocess(&self, item: &mut Item) -> Result<SourceScannerReadResult, eyre::Report> {
+        reprocess_html_article(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+    };
+
+    use super::*;
+    use crate::sources::timeline::test_scan;
+
+    const RSS: &str = r##"<?xml version="1.0"?>
+<rss version="2.0" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:content="http://purl.org/rss/1.0/modules/content/">
+<channel>
+  <title>Test</title>
+  <item>
+    <title>Summary only</title>
+    <link>/post</link>
+    <guid isPermaLink="false">post-1</guid>
+    <dc:creator>Jane</dc:creator>
+    <pubDate>Tue, 10 Jan 2023 12:00:00 +0000</pubDate>
+    <description>&lt;p&gt;A &lt;b&gt;short&lt;/b&gt; summary&lt;/p&gt;</description>
+  </item>
+  <item>
+    <title>Full text</title>
+    <link>https://example.com/full</link>
+    <guid>https://example.com/full</guid>
+    <description>The summary</description>
+    <content:encoded><![CDATA[<p>{FULL_TEXT}</p>]]></content:encoded>
+  </item>
+</channel>
+</rss>"##;
+
+    const ATOM: &str = r##"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Atom Test</title>
+  <id>urn:uuid:60a76c80-d399-11d9-b93C-0003939e0af6</id>
+  <updated>2023-01-12T18:30:02Z</updated>
+  <author><name>Feed Author</name></author>
+  <entry>
+    <title type="html">An &amp;lt;Atom&amp;gt; entry</title>
+    <link rel="replies" href="https://example.com/comments"/>
+    <link rel="alternate" href="https://example.com/atom-entry"/>
+    <id>tag:example.com,2023:entry-1</id>
+    <published>2023-01-11T10:00:00+02:00</published>
+    <updated>2023-01-12T18:30:02Z</updated>
+    <summary>Plain summary</summary>
+  </entry>
+</feed>"##;
+
+    const OPML: &str = r##"<?xml version="1.0"?>
+<opml version="2.0">
+  <body>
+    <outline text="Blogs">
+      <outline type="rss" text="One" xmlUrl="https://example.com/one.xml"/>
+      <outline type="rss" text="Two" xmlUrl="https://example.com/two.xml?a=1&amp;b=2"/>
+    </outline>
+  </body>
+</opml>"##;
+
+    const PAGE: &str = r##"<html><head><title>The Post</title></head><body><article>
+<h1>The Post</h1>
+<p>This is the full text of the post, which the feed only had a summary of. It has enough
+text in it that the article extraction keeps it around when it cleans up the page.</p>
+<p>Here is a second paragraph, to make sure that there is enough content to count as an
+article instead of just navigation or boilerplate.</p>
+</article></body></html>"##;
+
+    fn rss() -> String {
+        RSS.replace("{FULL_TEXT}", &"All of the words. ".repeat(100))
+    }
+
+    #[test]
+    fn rss_entries() {
+        let entries = parse_feed(rss().as_bytes()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].guid.as_deref(), Some("post-1"));
+        assert_eq!(entries[0].author.as_deref(), Some("Jane"));
+        assert_eq!(entries[0].summary.as_deref(), Some("A short summary"));
+        assert_eq!(
+            entries[0].published.map(|d| d.unix_timestamp()),
+            Some(1673352000)
+        );
+        assert!(entries[1]
+            .content
+            .as_deref()
+            .unwrap_or_default()
+            .starts_with("All of the words."));
+    }
+
+    #[test]
+    fn atom_entries() {
+        let entries = parse_feed(ATOM.as_bytes()).unwrap();
+        assert_eq!(
+            entries,
+            vec![FeedEntry {
+                guid: Some("tag:example.com,2023:entry-1".to_string()),
+                link: Some("https://example.com/atom-entry".to_string()),
+                title: Some("An <Atom> entry".to_string()),
+                author: Some("Feed Author".to_string()),
+                published: OffsetDateTime::from_unix_timestamp(1673424000).ok(),
+                updated: OffsetDateTime::from_unix_timestamp(1673548202).ok(),
+                summary: Some("Plain summary".to_string()),
+                content: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn opml_feeds() {
+        assert_eq!(
+            parse_opml(OPML).unwrap(),
+            vec![
+                "https://example.com/one.xml",
+                "https://example.com/two.xml?a=1&b=2"
+            ]
+        );
+    }
+
+    /// Serve the test feed and page from a local port, and return the base URL.
+    fn serve_fixtures() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let feed = rss();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+
+                let mut request_line = String::new();
+                let mut reader = BufReader::new(&stream);
+                reader.read_line(&mut request_line).ok();
+                // Read the rest of the headers.
+                let mut line = String::new();
+                while reader.read_line(&mut line).map_or(false, |n| n > 2) {
+                    line.clear();
+                }
+
+                let path = request_line.split(' ').nth(1).unwrap_or_default();
+                let (status, content_type, body) = match path {
+                    "/feed.xml" => ("200 OK", "application/rss+xml", feed.as_str()),
+                    "/post" => ("200 OK", "text/html", PAGE),
+                    _ => ("404 Not Found", "text/plain", ""),
+                };
+
+                write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .ok();
+            }
+        });
+
+        base
+    }
+
+    #[test]
+    fn scan_local_feed() {
+        let base = serve_fixtures();
+        let config = FeedsConfig {
+            feeds: vec![format!("{base}/feed.xml")],
+            skip: Vec::new(),
+            fetch: FetchConfig {
+                host_delay_ms: 0,
+                respect_robots_txt: false,
+                ..Default::default()
+            },
+        };
+        let scanner = FeedsScanner::new(1, String::new(), config).unwrap();
+
+        let mut items = test_scan(&scanner);
+        items.sort_by(|a, b| a.external_id.cmp(&b.external_id));
+
+        assert_eq!(
+            items
+                .iter()
+                .map(|i| i.external_id.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                format!("{base}/feed.xml#post-1"),
+                "https://example.com/full".to_string()
+            ]
+        );
+
+        // The full text entry doesn't need to be fetched.
+        assert!(items[1].content.is_some());
+
+        let summary_only = &mut items[0];
+        assert_eq!(summary_only.content, None);
+        assert_eq!(summary_only.metadata.author.as_deref(), Some("Jane"));
+        let result = scanner
+            .read(None, ItemCompareStrategy::MTimeAndContent, summary_only)
+            .unwrap();
+        assert!(matches!(result, SourceScannerReadResult::Found));
+        assert!(summary_only
+            .content
+            .as_deref()
+            .unwrap_or_default()
+            .contains("the full text of the post"));
+        assert_eq!(
+            summary_only.metadata.mtime.map(|d| d.unix_timestamp()),
+            Some(1673352000)
+        );
+    }
+}
@@ -0,0 +1,378 @@
+use ahash::HashMap;
+use eyre::Result;
+use itertools::Itertools;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    firefox_history::{firefox_time, open_places_db},
+    parse_html::{read_web_page, reprocess_html_article, should_skip, HTML_PROCESS_VERSION},
+    pipeline::{CountingVecSender, FoundItem, SourceScanner, SourceScannerReadResult},
+    web_fetcher::{FetchConfig, WebFetcher},
+    ItemCompareStrategy,
+};
+use crate::{cancel::CancellationToken, Item, ItemMetadata};
+
+/// The GUID of the folder at the top of the bookmarks tree.
+const ROOT_GUID: &str = "root________";
+/// The GUID of the folder that holds a folder for each tag.
+const TAGS_GUID: &str = "tags________";
+
+/// `moz_bookmarks.type` for a bookmark.
+const TYPE_BOOKMARK: i64 = 1;
+/// `moz_bookmarks.type` for a folder.
+const TYPE_FOLDER: i64 = 2;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FirefoxBookmarksConfig {
+    /// Domains that we should never check
+    pub skip: Vec<String>,
+    /// Add the names of the folders containing each bookmark to its tags.
+    #[serde(default)]
+    pub folder_tags: bool,
+    #[serde(default)]
+    pub fetch: FetchConfig,
+}
+
+/// A row from `moz_bookmarks`, joined with its place.
+struct BookmarkRow {
+    id: i64,
+    kind: i64,
+    title: Option<String>,
+    guid: String,
+    date_added: Option<i64>,
+    place_id: Option<i64>,
+    url: Option<String>,
+    last_visit: Option<i64>,
+}
+
+/// The bookmarks table arranged as a tree.
+struct BookmarkTree {
+    rows: HashMap<i64, BookmarkRow>,
+    /// The children of each folder, in order.
+    children: HashMap<i64, Vec<i64>>,
+    /// The tags for each place.
+    tags: HashMap<i64, Vec<String>>,
+}
+
+pub struct FirefoxBookmarksScanner {
+    pub source_id: i64,
+    /// The Firefox profile directory
+    pub location: String,
+    pub config: FirefoxBookmarksConfig,
+    pub fetcher: WebFetcher,
+}
+
+impl FirefoxBookmarksScanner {
+    pub fn new(source_id: i64, location: String, config: FirefoxBookmarksConfig) -> Result<Self> {
+        let fetcher = WebFetcher::new(config.fetch.clone(), true)?;
+        Ok(Self {
+            source_id,
+            location,
+            config,
+            fetcher,
+        })
+    }
+
+    fn read_tree(&self, conn: &rusqlite::Connection) -> Result<BookmarkTree> {
+        let mut stmt = conn.prepare(
+            r##"SELECT b.id, b.type, b.parent, b.title, b.guid, b.dateAdded, b.fk,
+                p.url, p.last_visit_date
+            FROM moz_bookmarks b
+            LEFT JOIN moz_places p ON p.id = b.fk
+            ORDER BY b.parent, b.position"##,
+        )?;
+
+        let mut rows = HashMap::default();
+        let mut children: HashMap<i64, Vec<i64>> = HashMap::default();
+        let mut query = stmt.query([])?;
+        while let Some(row) = query.next()? {
+            let id: i64 = row.get(0)?;
+            let parent: Option<i64> = row.get(2)?;
+            if let Some(parent) = parent {
+                children.entry(parent).or_default().push(id);
+            }
+
+            rows.insert(
+                id,
+                BookmarkRow {
+                    id,
+                    kind: row.get(1)?,
+                    title: row.get(3)?,
+                    guid: row.get(4)?,
+                    date_added: row.get(5)?,
+                    place_id: row.get(6)?,
+                    url: row.get(7)?,
+                    last_visit: row.get(8)?,
+                },
+            );
+        }
+
+        // Each tag is a folder under the tags root, which contains an entry for each place
+        // with that tag.
+        let mut stmt = conn.prepare(
+            r##"SELECT b.fk, t.title
+            FROM moz_bookmarks b
+            JOIN moz_bookmarks t ON t.id = b.parent
+            JOIN moz_bookmarks r ON r.id = t.parent
+            WHERE r.guid = ? AND b.fk IS NOT NULL AND t.title IS NOT NULL"##,
+        )?;
+
+        let mut tags: HashMap<i64, Vec<String>> = HashMap::default();
+        let mut query = stmt.query([TAGS_GUID])?;
+        while let Some(row) = query.next()? {
+            tags.entry(row.get(0)?).or_default().push(row.get(1)?);
+        }
+
+        Ok(BookmarkTree {
+            rows,
+            children,
+            tags,
+        })
+    }
+
+    /// Read the bookmarks, keyed by URL, with the tags for each one.
+    fn read_bookmarks(
+        &self,
+        conn: &rusqlite::Connection,
+        cancel: &CancellationToken,
+    ) -> Result<HashMap<String, Item>> {
+        let tree = self.read_tree(conn)?;
+
+        let mut output = HashMap::default();
+        if let Some(root) = tree.rows.values().find(|row| row.guid == ROOT_GUID) {
+            self.walk_bookmarks(&tree, &mut output, cancel, &mut Vec::new(), 0, root);
+        }
+
+        Ok(output)
+    }
+
+    fn walk_bookmarks(
+        &self,
+        tree: &BookmarkTree,
+        output: &mut HashMap<String, Item>,
+        cancel: &CancellationToken,
+        folders: &mut Vec<String>,
+        depth: usize,
+        entry: &BookmarkRow,
+    ) {
+        match entry.kind {
+            TYPE_BOOKMARK => {
+                let Some(url) = entry.url.as_ref() else {
+                    return;
+                };
+
+                let Ok(parsed) = Url::parse(url) else {
+                    // Don't exit on a failure to parse the URL, but also don't pass it on.
+                    return;
+                };
+
+                if !parsed.scheme().starts_with("http") || should_skip(&self.config.skip, &parsed) {
+                    return;
+                }
+
+                let mut tags = entry
+                    .place_id
+                    .and_then(|id| tree.tags.get(&id))
+                    .cloned()
+                    .unwrap_or_default();
+                if self.config.folder_tags {
+                    tags.extend(folders.iter().cloned());
+                }
+
+                // The same page may be bookmarked in multiple folders.
+                let item = output.entry(url.clone()).or_insert_with(|| Item {
+                    id: -1,
+                    external_id: url.clone(),
+                    source_id: self.source_id,
+                    content: None,
+                    raw_content: None,
+                    hash: None,
+                    process_version: HTML_PROCESS_VERSION,
+                    skipped: None,
+                    metadata: ItemMetadata {
+                        name: entry.title.clone(),
+                        atime: entry.last_visit.or(entry.date_added).map(firefox_time),
+                        tags: Some(Vec::new()),
+                        ..Default::default()
+                    },
+                });
+
+                let item_tags = item.metadata.tags.get_or_insert_with(Vec::new);
+                for tag in tags {
+                    if !item_tags.contains(&tag) {
+                        item_tags.push(tag);
+                    }
+                }
+            }
+            TYPE_FOLDER => {
+                if entry.guid == TAGS_GUID {
+                    // These are handled separately.
+                    return;
+                }
+
+                // The root and the folders directly under it, like "menu" and "toolbar", aren't
+                // meaningful as tags.
+                let folder_name = entry.title.as_ref().filter(|t| depth >= 2 && !t.is_empty());
+                if let Some(name) = folder_name {
+                    folders.push(name.clone());
+                }
+
+                for child in tree.children.get(&entry.id).into_iter().flatten() {
+                    if cancel.is_cancelled() {
+                        break;
+                    }
+
+                    if let Some(child) = tree.rows.get(child) {
+                        self.walk_bookmarks(tree, output, cancel, folders, depth + 1, child);
+                    }
+                }
+
+                if folder_name.is_some() {
+                    folders.pop();
+                }
+            }
+            // Separators
+            _ => {}
+        }
+    }
+}
+
+impl SourceScanner for FirefoxBookmarksScanner {
+    fn scan(
+        &self,
+        tx: CountingVecSender<Item>,
+        cancel: &CancellationToken,
+    ) -> Result<(), eyre::Report> {
+        let (_dir, conn) = open_places_db(&self.location)?;
+        let output = self.read_bookmarks(&conn, cancel)?;
+
+        for batch in &output.into_values().chunks(64) {
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            tx.send(batch.collect())?;
+        }
+
+        Ok(())
+    }
+
+    fn read(
+        &self,
+        existing: Option<&FoundItem>,
+        compare_strategy: ItemCompareStrategy,
+        item: &mut Item,
+    ) -> Result<SourceScannerReadResult, eyre::Report> {
+        read_web_page(&self.fetcher, existing, compare_strategy, item)
+    }
+
+    fn latest_process_version(&self) -> i32 {
+        HTML_PROCESS_VERSION
+    }
+
+    fn reprocess(&self, item: &mut Item) -> Result<SourceScannerReadResult, eyre::Report> {
+        reprocess_html_article(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::OffsetDateTime;
+
+    use super::*;
+
+    fn places_db() -> rusqlite::Connection {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r##"CREATE TABLE moz_places (id INTEGER PRIMARY KEY, url TEXT, last_visit_date INTEGER);
+            CREATE TABLE moz_bookmarks (
+                id INTEGER PRIMARY KEY,
+                type INTEGER,
+                fk INTEGER,
+                parent INTEGER,
+                position INTEGER,
+                title TEXT,
+                dateAdded INTEGER,
+                guid TEXT
+            );
+
+            INSERT INTO moz_places (id, url, last_visit_date) VALUES
+                (1, 'https://doc.rust-lang.org/book/', 1673352000123456),
+                (2, 'https://example.com/', NULL),
+                (3, 'place:sort=8', NULL);
+
+            INSERT INTO moz_bookmarks (id, type, fk, parent, position, title, dateAdded, guid) VALUES
+                (1, 2, NULL, 0, 0, '', NULL, 'root________'),
+                (2, 2, NULL, 1, 0, 'menu', NULL, 'menu________'),
+                (3, 2, NULL, 1, 1, 'tags', NULL, 'tags________'),
+                (4, 2, NULL, 2, 0, 'Rust', NULL, 'folder______'),
+                (5, 1, 1, 4, 0, 'The Book', 1673352000000000, 'bookmark1___'),
+                (6, 3, NULL, 2, 1, NULL, NULL, 'separator___'),
+                (7, 1, 2, 2, 2, 'Example', 1673352060000000, 'bookmark2___'),
+                (8, 1, 3, 2, 3, 'Recent', 1673352060000000, 'bookmark3___'),
+                (9, 2, NULL, 3, 0, 'docs', NULL, 'tag_________'),
+                (10, 1, 1, 9, 0, NULL, NULL, 'tagentry____');"##,
+        )
+        .unwrap();
+        conn
+    }
+
+    fn scanner(folder_tags: bool) -> FirefoxBookmarksScanner {
+        let config = FirefoxBookmarksConfig {
+            skip: Vec::new(),
+            folder_tags,
+            fetch: FetchConfig::default(),
+        };
+        FirefoxBookmarksScanner::new(1, String::new(), config).unwrap()
+    }
+
+    #[test]
+    fn bookmarks_and_tags() {
+        let conn = places_db();
+        let bookmarks = scanner(false)
+            .read_bookmarks(&conn, &CancellationToken::new())
+            .unwrap();
+        assert_eq!(
+            bookmarks.keys().sorted().collect::<Vec<_>>(),
+            vec!["https://doc.rust-lang.org/book/", "https://example.com/"]
+        );
+
+        let book = &bookmarks["https://doc.rust-lang.org/book/"];
+        assert_eq!(book.metadata.name.as_deref(), Some("The Book"));
+        assert_eq!(book.metadata.tags, Some(vec!["docs".to_string()]));
+        assert_eq!(
+            book.metadata.atime,
+            Some(
+                OffsetDateTime::from_unix_timestamp(1673352000).unwrap()
+                    + time::Duration::microseconds(123456)
+            )
+        );
+
+        // Without a visit, the time that it was bookmarked is used.
+        let example = &bookmarks["https://example.com/"];
+        assert_eq!(example.metadata.tags, Some(Vec::new()));
+        assert_eq!(
+            example.metadata.atime,
+            Some(OffsetDateTime::from_unix_timestamp(1673352060).unwrap())
+        );
+    }
+
+    #[test]
+    fn folder_tags() {
+        let conn = places_db();
+        let bookmarks = scanner(true)
+            .read_bookmarks(&conn, &CancellationToken::new())
+            .unwrap();
+
+        assert_eq!(
+            bookmarks["https://doc.rust-lang.org/book/"].metadata.tags,
+            Some(vec!["docs".to_string(), "Rust".to_string()])
+        );
+        // "menu" is one of the built-in folders, so it isn't a tag.
+        assert_eq!(
+            bookmarks["https://example.com/"].metadata.tags,
+            Some(Vec::new())
+        );
+    }
+}
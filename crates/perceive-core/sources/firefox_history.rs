@@ -0,0 +1,226 @@
+use std::path::Path;
+
+use ahash::HashMap;
+use eyre::{eyre, Context};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use super::{
+    parse_html::{
+        normalize_history_url, read_web_page, reprocess_html_article, HTML_PROCESS_VERSION,
+    },
+    pipeline::{CountingVecSender, FoundItem, SourceScanner, SourceScannerReadResult},
+    web_fetcher::{interleave_by_host, FetchConfig, WebFetcher},
+    ItemCompareStrategy,
+};
+use crate::{cancel::CancellationToken, Item};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FirefoxHistoryConfig {
+    /// Domains that we should never check
+    pub skip: Vec<String>,
+    #[serde(default)]
+    pub fetch: FetchConfig,
+}
+
+pub struct FirefoxHistoryScanner {
+    pub source_id: i64,
+    /// The Firefox profile directory
+    pub location: String,
+    pub config: FirefoxHistoryConfig,
+    pub fetcher: WebFetcher,
+}
+
+impl FirefoxHistoryScanner {
+    pub fn new(
+        source_id: i64,
+        location: String,
+        config: FirefoxHistoryConfig,
+    ) -> Result<FirefoxHistoryScanner, eyre::Report> {
+        let fetcher = WebFetcher::new(config.fetch.clone(), false)?;
+        Ok(FirefoxHistoryScanner {
+            source_id,
+            location,
+            config,
+            fetcher,
+        })
+    }
+}
+
+/// Copy a Firefox profile's `places.sqlite` to a temporary directory and open it. Firefox keeps
+/// the database locked while it's running, so it can't be read in place.
+pub(super) fn open_places_db(
+    location: &str,
+) -> Result<(tempfile::TempDir, rusqlite::Connection), eyre::Report> {
+    let dir = tempfile::tempdir()?;
+    let db_path = dir.path().join("places.sqlite");
+    let from_db_path = Path::new(location).join("places.sqlite");
+    std::fs::copy(&from_db_path, &db_path)
+        .wrap_err_with(|| eyre!("Copying {} to temporary location", from_db_path.display()))?;
+
+    // Recent changes may still be in the write-ahead log.
+    let from_wal_path = Path::new(location).join("places.sqlite-wal");
+    if from_wal_path.is_file() {
+        std::fs::copy(&from_wal_path, dir.path().join("places.sqlite-wal"))
+            .wrap_err_with(|| eyre!("Copying {} to temporary location", from_wal_path.display()))?;
+    }
+
+    // Not opened read-only, so that SQLite can apply the write-ahead log.
+    let conn = rusqlite::Connection::open(db_path)?;
+    Ok((dir, conn))
+}
+
+/// Convert a Firefox timestamp, in microseconds since the Unix epoch.
+pub(super) fn firefox_time(micros: i64) -> OffsetDateTime {
+    OffsetDateTime::UNIX_EPOCH + time::Duration::microseconds(micros)
+}
+
+/// Read the URL, title, and last visit time of each page in the history, keeping the most
+/// recently visited entry for each page after normalizing the URLs.
+fn read_history(
+    conn: &rusqlite::Connection,
+    skip: &[String],
+) -> Result<Vec<(String, Option<String>, OffsetDateTime)>, eyre::Report> {
+    let mut stmt = conn.prepare(
+        r##"SELECT p.url, MAX(p.title), MAX(v.visit_date)
+         FROM moz_places p
+         JOIN moz_historyvisits v ON v.place_id = p.id
+         -- Skip things like "about:" and "moz-extension://"
+         WHERE p.url LIKE 'http%'
+         GROUP BY p.url
+         -- Most recent first, so that the latest visit is kept when URLs are merged.
+         ORDER BY 3 DESC"##,
+    )?;
+
+    let rows = stmt.query_map([], |row| {
+        let url: String = row.get(0)?;
+        let title: Option<String> = row.get(1)?;
+        let last_visit_time: i64 = row.get(2)?;
+
+        Ok((url, title, firefox_time(last_visit_time)))
+    })?;
+
+    let mut output = HashMap::default();
+    for row in rows {
+        let (url_str, title, last_visit_time) = row?;
+
+        let Some((url_str, dedupe_key)) = normalize_history_url(skip, url_str) else {
+            continue;
+        };
+
+        output
+            .entry(dedupe_key)
+            .or_insert((url_str, title, last_visit_time));
+    }
+
+    Ok(output.into_values().collect())
+}
+
+impl SourceScanner for FirefoxHistoryScanner {
+    fn scan(
+        &self,
+        tx: CountingVecSender<Item>,
+        cancel: &CancellationToken,
+    ) -> Result<(), eyre::Report> {
+        let (_dir, conn) = open_places_db(&self.location)?;
+        let output = read_history(&conn, &self.config.skip)?;
+
+        // Spread out the URLs so that the readers aren't all waiting on the same host.
+        let output = interleave_by_host(output, |(url, _, _)| url.as_str());
+        for batch in &output.into_iter().chunks(64) {
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            let batch = batch
+                .into_iter()
+                .map(|(url_str, title, last_visit_time)| Item {
+                    id: -1,
+                    source_id: self.source_id,
+                    external_id: url_str,
+                    hash: None,
+                    skipped: None,
+                    metadata: crate::ItemMetadata {
+                        name: title,
+                        atime: Some(last_visit_time),
+                        ..Default::default()
+                    },
+                    content: None,
+                    raw_content: None,
+                    process_version: HTML_PROCESS_VERSION,
+                })
+                .collect::<Vec<_>>();
+
+            tx.send(batch)?;
+        }
+
+        Ok(())
+    }
+
+    fn read(
+        &self,
+        existing: Option<&FoundItem>,
+        compare_strategy: ItemCompareStrategy,
+        item: &mut Item,
+    ) -> Result<SourceScannerReadResult, eyre::Report> {
+        read_web_page(&self.fetcher, existing, compare_strategy, item)
+    }
+
+    fn latest_process_version(&self) -> i32 {
+        HTML_PROCESS_VERSION
+    }
+
+    fn reprocess(&self, item: &mut Item) -> Result<SourceScannerReadResult, eyre::Report> {
+        reprocess_html_article(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn microsecond_timestamps() {
+        assert_eq!(
+            firefox_time(1673352000123456),
+            OffsetDateTime::from_unix_timestamp(1673352000).unwrap()
+                + time::Duration::microseconds(123456)
+        );
+    }
+
+    #[test]
+    fn history() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r##"CREATE TABLE moz_places (id INTEGER PRIMARY KEY, url TEXT, title TEXT);
+            CREATE TABLE moz_historyvisits (id INTEGER PRIMARY KEY, place_id INTEGER, visit_date INTEGER);
+
+            INSERT INTO moz_places (id, url, title) VALUES
+                (1, 'https://example.com/page', 'A Page'),
+                (2, 'https://example.com/page/#section', 'A Page'),
+                (3, 'about:config', NULL),
+                (4, 'https://skipped.com/', 'Skipped'),
+                (5, 'https://example.com/never-visited', 'Bookmarked');
+
+            INSERT INTO moz_historyvisits (place_id, visit_date) VALUES
+                (1, 1673352000000000),
+                (1, 1673352060000000),
+                (2, 1673352120000000),
+                (3, 1673352000000000),
+                (4, 1673352000000000);"##,
+        )
+        .unwrap();
+
+        let history = read_history(&conn, &["skipped.com".to_string()]).unwrap();
+        // The two URLs for the page are merged, keeping the one visited most recently.
+        assert_eq!(
+            history,
+            vec![(
+                "https://example.com/page/".to_string(),
+                Some("A Page".to_string()),
+                OffsetDateTime::from_unix_timestamp(1673352120).unwrap()
+            )]
+        );
+    }
+}
@@ -4,10 +4,11 @@ use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
 use super::{
+    extract::{extractors, ContentExtractor},
     pipeline::{CountingVecSender, FoundItem, SourceScanner, SourceScannerReadResult},
     ItemCompareStrategy,
 };
-use crate::{batch_sender::BatchSender, Item, ItemMetadata};
+use crate::{batch_sender::BatchSender, cancel::CancellationToken, Item, ItemMetadata};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FsSourceConfig {
@@ -20,23 +21,64 @@ pub struct FileScanner {
     pub config: FsSourceConfig,
 }
 
-impl SourceScanner for FileScanner {
-    fn scan(&self, output: CountingVecSender<Item>) -> Result<(), eyre::Report> {
-        let mut glob_builder = globset::GlobSetBuilder::new();
-        if self.config.globs.is_empty() {
-            glob_builder.add(globset::Glob::new("*")?);
-        } else {
-            for glob in &self.config.globs {
-                glob_builder.add(globset::Glob::new(glob)?);
-            }
+impl FileScanner {
+    pub(super) fn build_globset(&self) -> Result<globset::GlobSet, eyre::Report> {
+        build_globset(&self.config.globs)
+    }
+}
+
+/// Build a matcher for a list of globs, which matches everything when the list is empty.
+pub(super) fn build_globset(globs: &[String]) -> Result<globset::GlobSet, eyre::Report> {
+    let mut glob_builder = globset::GlobSetBuilder::new();
+    if globs.is_empty() {
+        glob_builder.add(globset::Glob::new("*")?);
+    } else {
+        for glob in globs {
+            glob_builder.add(globset::Glob::new(glob)?);
         }
+    }
+
+    Ok(glob_builder.build()?)
+}
+
+/// Create an unread item for a file.
+pub(super) fn file_item(source_id: i64, path: &std::path::Path, meta: &std::fs::Metadata) -> Item {
+    Item {
+        id: -1,
+        source_id,
+        external_id: path.to_string_lossy().to_string(),
+        hash: None,
+        content: None,
+        raw_content: None,
+        skipped: None,
+        process_version: 0,
+        metadata: crate::ItemMetadata {
+            name: None,
+            author: None,
+            description: None,
+            mtime: meta.modified().ok().map(OffsetDateTime::from),
+            atime: meta.accessed().ok().map(OffsetDateTime::from),
+            created: None,
+            http_cache: None,
+            tags: None,
+            links: None,
+        },
+    }
+}
 
-        let glob = glob_builder.build()?;
+impl SourceScanner for FileScanner {
+    fn scan(
+        &self,
+        output: CountingVecSender<Item>,
+        cancel: &CancellationToken,
+    ) -> Result<(), eyre::Report> {
+        let glob = self.build_globset()?;
 
         let mut visitor_builder = FileVisitorBuilder {
             source_id: self.source_id,
             glob,
             output,
+            cancel,
         };
 
         ignore::WalkBuilder::new(std::path::Path::new(&self.location))
@@ -51,53 +93,111 @@ impl SourceScanner for FileScanner {
         _compare_strategy: ItemCompareStrategy,
         item: &mut Item,
     ) -> Result<SourceScannerReadResult, eyre::Report> {
-        let Ok(content) = std::fs::read_to_string(std::path::Path::new(&item.external_id)) else {
-            // Currently we just return None if reading fails. This includes where the file
-            // is a binary file that can't be converted to UTF-8. In the future we should
-            // distinguish between these cases.
-            //
-            // Future iterations of this will also try to process other file formats that aren't plain
-            // text but do contain text (i.e. Word, Pages, etc.).
+        let path = std::path::PathBuf::from(&item.external_id);
+        let Ok(content) = std::fs::read(&path) else {
             return Ok(SourceScannerReadResult::Omit);
         };
 
-        if content.trim().is_empty() {
+        read_file_content(&path, content, item)
+    }
+
+    fn latest_process_version(&self) -> i32 {
+        0
+    }
+
+    fn reprocess(&self, item: &mut Item) -> Result<SourceScannerReadResult, eyre::Report> {
+        let path = std::path::PathBuf::from(&item.external_id);
+        reprocess_file_content(&path, item)
+    }
+}
+
+/// Set an item's content from the contents of a file, using the path to pick an extractor.
+pub(super) fn read_file_content(
+    path: &std::path::Path,
+    content: Vec<u8>,
+    item: &mut Item,
+) -> Result<SourceScannerReadResult, eyre::Report> {
+    let extractor = extractors()
+        .for_path(path)
+        .or_else(|| extractors().detect(&content));
+    if let Some(extractor) = extractor {
+        if !extract_content(extractor, &content, item)? {
             return Ok(SourceScannerReadResult::Omit);
         }
 
-        if let Some(doc_content) = process_content(&content, &mut item.metadata) {
-            item.content = Some(doc_content);
+        // Keep the original so that reprocessing doesn't have to read the file again.
+        item.raw_content = Some(zstd::encode_all(content.as_slice(), 3)?);
+        return Ok(SourceScannerReadResult::Found);
+    }
 
-            let compressed = zstd::encode_all(content.as_bytes(), 3)?;
-            item.raw_content = Some(compressed);
-        } else {
-            item.content = Some(content);
-        }
+    let Ok(content) = String::from_utf8(content) else {
+        // Binary files that none of the extractors can handle are skipped.
+        return Ok(SourceScannerReadResult::Omit);
+    };
 
-        Ok(SourceScannerReadResult::Found)
+    if content.trim().is_empty() {
+        return Ok(SourceScannerReadResult::Omit);
     }
 
-    fn latest_process_version(&self) -> i32 {
-        0
+    if let Some(doc_content) = process_content(&content, &mut item.metadata) {
+        item.content = Some(doc_content);
+
+        let compressed = zstd::encode_all(content.as_bytes(), 3)?;
+        item.raw_content = Some(compressed);
+    } else {
+        item.content = Some(content);
     }
 
-    fn reprocess(&self, item: &mut Item) -> Result<SourceScannerReadResult, eyre::Report> {
-        let content = match (item.raw_content.as_ref(), item.content.as_ref()) {
-            (Some(buffer), _) => {
-                let decompressed = zstd::decode_all(buffer.as_slice())?;
-                Cow::Owned(String::from_utf8(decompressed)?)
+    Ok(SourceScannerReadResult::Found)
+}
+
+/// Reprocess an item read with [read_file_content].
+pub(super) fn reprocess_file_content(
+    path: &std::path::Path,
+    item: &mut Item,
+) -> Result<SourceScannerReadResult, eyre::Report> {
+    let content = match (item.raw_content.as_ref(), item.content.as_ref()) {
+        (Some(buffer), _) => {
+            let decompressed = zstd::decode_all(buffer.as_slice())?;
+            let extractor = extractors()
+                .for_path(path)
+                .or_else(|| extractors().detect(&decompressed));
+            if let Some(extractor) = extractor {
+                return Ok(if extract_content(extractor, &decompressed, item)? {
+                    SourceScannerReadResult::Found
+                } else {
+                    SourceScannerReadResult::Unchanged
+                });
             }
-            (None, Some(content)) => Cow::Borrowed(content),
-            (None, None) => return Ok(SourceScannerReadResult::Unchanged),
-        };
 
-        if let Some(content) = process_content(&content, &mut item.metadata) {
-            item.content = Some(content);
-            Ok(SourceScannerReadResult::Found)
-        } else {
-            Ok(SourceScannerReadResult::Unchanged)
+            Cow::Owned(String::from_utf8(decompressed)?)
         }
+        (None, Some(content)) => Cow::Borrowed(content),
+        (None, None) => return Ok(SourceScannerReadResult::Unchanged),
+    };
+
+    if let Some(content) = process_content(&content, &mut item.metadata) {
+        item.content = Some(content);
+        Ok(SourceScannerReadResult::Found)
+    } else {
+        Ok(SourceScannerReadResult::Unchanged)
+    }
+}
+
+/// Set the item's content and metadata from a document. Returns false if the document had no
+/// text, as with scanned PDFs that were never run through OCR.
+fn extract_content(
+    extractor: &dyn ContentExtractor,
+    content: &[u8],
+    item: &mut Item,
+) -> Result<bool, eyre::Report> {
+    let extracted = extractor.extract(content)?;
+    if extracted.text.trim().is_empty() {
+        return Ok(false);
     }
+
+    extracted.apply(item);
+    Ok(true)
 }
 
 fn process_content(content: &str, metadata: &mut ItemMetadata) -> Option<String> {
@@ -119,6 +219,7 @@ struct FileVisitorBuilder<'a> {
     source_id: i64,
     glob: globset::GlobSet,
     output: CountingVecSender<'a, Item>,
+    cancel: &'a CancellationToken,
 }
 
 impl<'a, 's> ignore::ParallelVisitorBuilder<'s> for FileVisitorBuilder<'a>
@@ -130,6 +231,7 @@ where
             source_id: self.source_id,
             glob: self.glob.clone(),
             sender: BatchSender::new(BATCH_SIZE, self.output.clone()),
+            cancel: self.cancel,
         })
     }
 }
@@ -138,10 +240,15 @@ struct FileVisitor<'a> {
     source_id: i64,
     glob: globset::GlobSet,
     sender: BatchSender<'a, Item>,
+    cancel: &'a CancellationToken,
 }
 
 impl<'a> ignore::ParallelVisitor for FileVisitor<'a> {
     fn visit(&mut self, entry: Result<ignore::DirEntry, ignore::Error>) -> ignore::WalkState {
+        if self.cancel.is_cancelled() {
+            return ignore::WalkState::Quit;
+        }
+
         if let Ok(entry) = entry {
             let meta = match entry.metadata() {
                 Ok(meta) if meta.file_type().is_file() => meta,
@@ -152,23 +259,7 @@ impl<'a> ignore::ParallelVisitor for FileVisitor<'a> {
             let is_match = self.glob.is_match(path);
 
             if is_match {
-                let item = Item {
-                    id: -1,
-                    source_id: self.source_id,
-                    external_id: entry.path().to_string_lossy().to_string(),
-                    hash: None,
-                    content: None,
-                    raw_content: None,
-                    skipped: None,
-                    process_version: 0,
-                    metadata: crate::ItemMetadata {
-                        name: None,
-                        author: None,
-                        description: None,
-                        mtime: meta.modified().ok().map(OffsetDateTime::from),
-                        atime: meta.accessed().ok().map(OffsetDateTime::from),
-                    },
-                };
+                let item = file_item(self.source_id, path, &meta);
 
                 let send_result = self.sender.add(item);
                 if send_result.is_err() {
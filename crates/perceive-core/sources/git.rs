@@ -0,0 +1,369 @@
+use std::path::Path;
+
+use eyre::{eyre, Context};
+use git2::{ObjectType, Repository, Sort, TreeWalkMode, TreeWalkResult};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use super::{
+    fs::{build_globset, read_file_content, reprocess_file_content},
+    pipeline::{
+        prefilled_read, CountingVecSender, FoundItem, SourceScanner, SourceScannerReadResult,
+        SCAN_BATCH_SIZE,
+    },
+    ItemCompareStrategy,
+};
+use crate::{batch_sender::BatchSender, cancel::CancellationToken, Item, ItemMetadata};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct GitConfig {
+    /// The ref whose history is indexed. Defaults to HEAD.
+    #[serde(default)]
+    pub commits_ref: Option<String>,
+    /// Index the files at this ref. Files are read from the repository rather than the working
+    /// tree, so uncommitted changes are ignored.
+    #[serde(default)]
+    pub files_ref: Option<String>,
+    /// The globs for files to include. All files are included when this is empty.
+    #[serde(default)]
+    pub globs: Vec<String>,
+}
+
+/// Reads the commit messages from a git repository, and optionally the files at a ref.
+/// Commits use the commit hash as their external ID, and files use `ref:path`.
+pub struct GitScanner {
+    pub source_id: i64,
+    pub location: String,
+    pub config: GitConfig,
+}
+
+/// Symbolic links are stored as blobs containing the target path, so they have to be told apart
+/// by their mode.
+const SYMLINK_MODE: i32 = 0o120000;
+
+impl GitScanner {
+    fn open(&self) -> Result<Repository, eyre::Report> {
+        Repository::open(&self.location).wrap_err_with(|| eyre!("Opening {}", self.location))
+    }
+
+    fn scan_commits(
+        &self,
+        repo: &Repository,
+        sender: &BatchSender<Item>,
+        cancel: &CancellationToken,
+    ) -> Result<(), eyre::Report> {
+        let commits_ref = self.config.commits_ref.as_deref().unwrap_or("HEAD");
+        let start = repo
+            .revparse_single(commits_ref)
+            .and_then(|obj| obj.peel_to_commit())
+            .wrap_err_with(|| eyre!("Finding {commits_ref}"))?;
+
+        let mut revwalk = repo.revwalk()?;
+        revwalk.set_sorting(Sort::TIME)?;
+        revwalk.push(start.id())?;
+
+        for oid in revwalk {
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            let commit = repo.find_commit(oid?)?;
+            let message = String::from_utf8_lossy(commit.message_bytes())
+                .trim()
+                .to_string();
+            if message.is_empty() {
+                continue;
+            }
+
+            let author = commit.author();
+            let author = match (author.name(), author.email()) {
+                (Some(name), Some(email)) => Some(format!("{name} <{email}>")),
+                (Some(name), None) => Some(name.to_string()),
+                (None, Some(email)) => Some(email.to_string()),
+                (None, None) => None,
+            };
+
+            sender.add(Item {
+                id: -1,
+                source_id: self.source_id,
+                external_id: commit.id().to_string(),
+                hash: None,
+                content: Some(message),
+                raw_content: None,
+                skipped: None,
+                process_version: 0,
+                metadata: ItemMetadata {
+                    name: commit.summary().map(String::from),
+                    author,
+                    mtime: OffsetDateTime::from_unix_timestamp(commit.time().seconds()).ok(),
+                    ..Default::default()
+                },
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn scan_files(
+        &self,
+        repo: &Repository,
+        files_ref: &str,
+        sender: &BatchSender<Item>,
+        cancel: &CancellationToken,
+    ) -> Result<(), eyre::Report> {
+        let glob = build_globset(&self.config.globs)?;
+        let tree = repo
+            .revparse_single(files_ref)
+            .and_then(|obj| obj.peel_to_tree())
+            .wrap_err_with(|| eyre!("Finding {files_ref}"))?;
+
+        let mut items = Vec::new();
+        let walked = tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+            if cancel.is_cancelled() {
+                return TreeWalkResult::Abort;
+            }
+
+            // Submodules show up as commits, and are skipped along with symlinks.
+            if entry.kind() != Some(ObjectType::Blob) || entry.filemode() == SYMLINK_MODE {
+                return TreeWalkResult::Ok;
+            }
+
+            let Some(name) = entry.name() else {
+                return TreeWalkResult::Ok;
+            };
+
+            let path = format!("{dir}{name}");
+            if glob.is_match(&path) {
+                items.push(Item {
+                    id: -1,
+                    source_id: self.source_id,
+                    external_id: format!("{files_ref}:{path}"),
+                    // The blob ID changes exactly when the content does.
+                    hash: Some(entry.id().to_string()),
+                    content: None,
+                    raw_content: None,
+                    skipped: None,
+                    process_version: 0,
+                    metadata: ItemMetadata::default(),
+                });
+            }
+
+            TreeWalkResult::Ok
+        });
+
+        if cancel.is_cancelled() {
+            return Ok(());
+        }
+        walked?;
+
+        for item in items {
+            sender.add(item)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SourceScanner for GitScanner {
+    fn scan(
+        &self,
+        output: CountingVecSender<Item>,
+        cancel: &CancellationToken,
+    ) -> Result<(), eyre::Report> {
+        let repo = self.open()?;
+        let sender = BatchSender::new(SCAN_BATCH_SIZE, output);
+
+        self.scan_commits(&repo, &sender, cancel)?;
+        if let Some(files_ref) = self.config.files_ref.as_deref() {
+            self.scan_files(&repo, files_ref, &sender, cancel)?;
+        }
+
+        Ok(())
+    }
+
+    fn read(
+        &self,
+        existing: Option<&FoundItem>,
+        compare_strategy: ItemCompareStrategy,
+        item: &mut Item,
+    ) -> Result<SourceScannerReadResult, eyre::Report> {
+        // Commit messages were read during the scan.
+        let Some((_, path)) = item.external_id.split_once(':') else {
+            return Ok(prefilled_read(existing, compare_strategy, item));
+        };
+
+        let unchanged = compare_strategy != ItemCompareStrategy::Force
+            && existing.map_or(false, |e| Some(e.hash.as_str()) == item.hash.as_deref());
+        if unchanged {
+            return Ok(SourceScannerReadResult::Unchanged);
+        }
+
+        let path = Path::new(path).to_path_buf();
+        let repo = self.open()?;
+        let blob = repo
+            .revparse_single(&item.external_id)
+            .and_then(|obj| obj.peel_to_blob())
+            .wrap_err_with(|| eyre!("Reading {}", item.external_id))?;
+
+        read_file_content(&path, blob.content().to_vec(), item)
+    }
+
+    fn latest_process_version(&self) -> i32 {
+        0
+    }
+
+    fn reprocess(&self, item: &mut Item) -> Result<SourceScannerReadResult, eyre::Report> {
+        let Some((_, path)) = item.external_id.split_once(':') else {
+            return Ok(SourceScannerReadResult::Unchanged);
+        };
+
+        let path = Path::new(path).to_path_buf();
+        reprocess_file_content(&path, item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use git2::{Oid, Signature, Time};
+
+    use super::*;
+    use crate::sources::timeline::{test_found_item, test_scan, test_time};
+
+    /// Write the files and commit them on top of HEAD.
+    fn commit(repo: &Repository, files: &[(&str, &[u8])], message: &str, minute: i64) -> Oid {
+        let root = repo.workdir().unwrap();
+        let mut index = repo.index().unwrap();
+        for (path, content) in files {
+            let full_path = root.join(path);
+            std::fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+            std::fs::write(&full_path, content).unwrap();
+            index.add_path(Path::new(path)).unwrap();
+        }
+        index.write().unwrap();
+
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let time = Time::new(test_time(minute).unix_timestamp(), 0);
+        let signature = Signature::new("Alice", "alice@example.com", &time).unwrap();
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents = parent.iter().collect::<Vec<_>>();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .unwrap()
+    }
+
+    /// Create a repository with two commits, and one with an empty message between them.
+    fn test_repo() -> (tempfile::TempDir, Oid, Oid) {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let first = commit(
+            &repo,
+            &[
+                ("README.md", b"# Project"),
+                ("docs/guide.md", b"Old guide"),
+                ("logo.bin", &[0xff, 0xfe, 0]),
+            ],
+            "Add the project",
+            0,
+        );
+        commit(&repo, &[], "", 5);
+        let second = commit(
+            &repo,
+            &[("docs/guide.md", b"How to use it")],
+            "Update the guide\n\nWith more detail.",
+            10,
+        );
+
+        (dir, first, second)
+    }
+
+    fn scanner(location: &Path, files_ref: Option<&str>) -> GitScanner {
+        GitScanner {
+            source_id: 1,
+            location: location.display().to_string(),
+            config: GitConfig {
+                commits_ref: None,
+                files_ref: files_ref.map(String::from),
+                globs: vec!["*.md".to_string()],
+            },
+        }
+    }
+
+    #[test]
+    fn commits() {
+        let (dir, first, second) = test_repo();
+        let scanner = scanner(dir.path(), None);
+        let mut items = test_scan(&scanner);
+
+        let ids = items
+            .iter()
+            .map(|item| item.external_id.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![second.to_string(), first.to_string()]);
+
+        let latest = &mut items[0];
+        assert_eq!(
+            latest.content.as_deref(),
+            Some("Update the guide\n\nWith more detail.")
+        );
+        assert_eq!(latest.metadata.name.as_deref(), Some("Update the guide"));
+        assert_eq!(
+            latest.metadata.author.as_deref(),
+            Some("Alice <alice@example.com>")
+        );
+        assert_eq!(latest.metadata.mtime, Some(test_time(10)));
+
+        let result = scanner
+            .read(None, ItemCompareStrategy::MTimeAndContent, latest)
+            .unwrap();
+        assert!(matches!(result, SourceScannerReadResult::Found));
+    }
+
+    #[test]
+    fn files() {
+        let (dir, _, _) = test_repo();
+        let scanner = scanner(dir.path(), Some("HEAD"));
+        let mut files = test_scan(&scanner)
+            .into_iter()
+            .filter(|item| item.external_id.contains(':'))
+            .collect::<Vec<_>>();
+
+        let ids = files
+            .iter()
+            .map(|item| item.external_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["HEAD:README.md", "HEAD:docs/guide.md"]);
+
+        let repo = Repository::open(dir.path()).unwrap();
+        let blob_id = repo
+            .revparse_single("HEAD:docs/guide.md")
+            .unwrap()
+            .id()
+            .to_string();
+        let guide = &mut files[1];
+        assert_eq!(guide.hash.as_deref(), Some(blob_id.as_str()));
+        assert!(guide.content.is_none());
+
+        let result = scanner
+            .read(None, ItemCompareStrategy::MTimeAndContent, guide)
+            .unwrap();
+        assert!(matches!(result, SourceScannerReadResult::Found));
+        assert_eq!(guide.content.as_deref(), Some("How to use it"));
+
+        // The blob ID is the hash, so the file is only read again when it changes.
+        let existing = test_found_item(&blob_id, "How to use it");
+        let result = scanner
+            .read(Some(&existing), ItemCompareStrategy::MTimeAndContent, guide)
+            .unwrap();
+        assert!(matches!(result, SourceScannerReadResult::Unchanged));
+        let result = scanner
+            .read(Some(&existing), ItemCompareStrategy::Force, guide)
+            .unwrap();
+        assert!(matches!(result, SourceScannerReadResult::Found));
+    }
+}
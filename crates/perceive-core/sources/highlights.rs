@@ -0,0 +1,327 @@
+mod hypothesis;
+mod kindle;
+mod readwise;
+
+use std::path::Path;
+
+use ahash::{HashMap, HashSet};
+use eyre::{eyre, Context};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use time::OffsetDateTime;
+
+pub use self::{
+    hypothesis::parse_hypothesis_export,
+    kindle::parse_kindle_clippings,
+    readwise::{parse_readwise_csv, parse_readwise_markdown},
+};
+use super::{
+    pipeline::{
+        prefilled_read, CountingVecSender, FoundItem, SourceScanner, SourceScannerReadResult,
+        SCAN_BATCH_SIZE,
+    },
+    ItemCompareStrategy,
+};
+use crate::{batch_sender::BatchSender, cancel::CancellationToken, Item, ItemMetadata};
+
+#[derive(Debug, Display, EnumString, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum HighlightsFormat {
+    /// The `My Clippings.txt` file from a Kindle
+    Kindle,
+    /// A Readwise CSV export, a Markdown export file, or a directory of Markdown files
+    Readwise,
+    /// A Hypothesis JSON export
+    Hypothesis,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HighlightsConfig {
+    pub format: HighlightsFormat,
+}
+
+/// A highlight or note read from an export.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Highlight {
+    /// A stable ID for the highlight
+    pub id: String,
+    /// Identifies the book or page that the highlight came from, for grouping the highlights
+    /// together.
+    pub document: String,
+    /// The title of the book or page
+    pub title: String,
+    pub author: Option<String>,
+    /// Where the highlight is, like `Location 150-152`, `Page 12`, or the URL of the page.
+    pub location: Option<String>,
+    /// Used to put highlights in the order that they appear in the book.
+    pub position: Option<u64>,
+    pub time: Option<OffsetDateTime>,
+    /// The highlighted text. This is empty for notes that aren't attached to a highlight.
+    pub text: String,
+    pub note: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl Highlight {
+    fn content(&self) -> String {
+        let text = self.text.trim();
+        match self
+            .note
+            .as_deref()
+            .map(str::trim)
+            .filter(|n| !n.is_empty())
+        {
+            Some(note) if text.is_empty() => note.to_string(),
+            Some(note) => format!("{text}\n\nNote: {note}"),
+            None => text.to_string(),
+        }
+    }
+}
+
+/// Group highlights by the book that they came from, with each book's highlights in the order
+/// that they appear in the book.
+pub fn group_by_document(highlights: Vec<Highlight>) -> Vec<Vec<Highlight>> {
+    let mut documents: Vec<Vec<Highlight>> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::default();
+    for highlight in highlights {
+        let i = *index.entry(highlight.document.clone()).or_insert_with(|| {
+            documents.push(Vec::new());
+            documents.len() - 1
+        });
+        documents[i].push(highlight);
+    }
+
+    for document in documents.iter_mut() {
+        // The sort is stable, so highlights without a position stay in the export's order.
+        document.sort_by_key(|h| h.position.unwrap_or(u64::MAX));
+    }
+
+    documents
+}
+
+/// Make the IDs unique, for exports where two highlights can end up with the same ID, such as
+/// two highlights at the same location.
+fn dedupe_ids(highlights: &mut [Highlight]) {
+    let mut seen = HashSet::default();
+    for highlight in highlights.iter_mut() {
+        if seen.insert(highlight.id.clone()) {
+            continue;
+        }
+
+        let mut n = 2;
+        while !seen.insert(format!("{}-{n}", highlight.id)) {
+            n += 1;
+        }
+        highlight.id = format!("{}-{n}", highlight.id);
+    }
+}
+
+/// Create an item for each highlight in a book. Each one links to the highlights just before
+/// and after it, so that a search result can show the surrounding highlights. Highlights without
+/// any text are skipped, and their neighbors link to each other instead.
+fn document_items(source_id: i64, document: &[Highlight]) -> Vec<Item> {
+    let highlights = document
+        .iter()
+        .map(|highlight| (highlight, highlight.content()))
+        .filter(|(_, content)| !content.is_empty())
+        .collect::<Vec<_>>();
+
+    highlights
+        .iter()
+        .enumerate()
+        .map(|(i, (highlight, content))| {
+            let links = [i.checked_sub(1), Some(i + 1)]
+                .into_iter()
+                .flatten()
+                .filter_map(|j| highlights.get(j))
+                .map(|(h, _)| h.id.clone())
+                .collect();
+
+            Item {
+                id: -1,
+                source_id,
+                external_id: highlight.id.clone(),
+                hash: None,
+                content: Some(content.clone()),
+                raw_content: None,
+                skipped: None,
+                process_version: 0,
+                metadata: ItemMetadata {
+                    name: Some(highlight.title.clone()),
+                    author: highlight.author.clone(),
+                    description: highlight.location.clone(),
+                    mtime: highlight.time,
+                    tags: Some(highlight.tags.clone()),
+                    links: Some(links),
+                    ..Default::default()
+                },
+            }
+        })
+        .collect()
+}
+
+/// Reads highlights and notes from Kindle, Readwise, or Hypothesis exports. Each highlight is a
+/// separate item named after the book or page that it came from.
+pub struct HighlightsScanner {
+    pub source_id: i64,
+    pub location: String,
+    pub config: HighlightsConfig,
+}
+
+impl HighlightsScanner {
+    fn read_highlights(&self) -> Result<Vec<Highlight>, eyre::Report> {
+        let path = Path::new(&self.location);
+        let read = |path: &Path| {
+            std::fs::read_to_string(path).wrap_err_with(|| eyre!("Reading {}", path.display()))
+        };
+
+        match self.config.format {
+            HighlightsFormat::Kindle => Ok(parse_kindle_clippings(&read(path)?)),
+            HighlightsFormat::Hypothesis => parse_hypothesis_export(&read(path)?),
+            HighlightsFormat::Readwise if path.is_dir() => {
+                let mut highlights = Vec::new();
+                let files = ignore::WalkBuilder::new(path)
+                    .build()
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.into_path())
+                    .filter(|path| {
+                        path.extension()
+                            .map_or(false, |ext| ext.eq_ignore_ascii_case("md"))
+                    })
+                    .sorted();
+                for file in files {
+                    highlights.extend(parse_readwise_markdown(&read(&file)?));
+                }
+                Ok(highlights)
+            }
+            HighlightsFormat::Readwise => {
+                let is_csv = path
+                    .extension()
+                    .map_or(false, |ext| ext.eq_ignore_ascii_case("csv"));
+                if is_csv {
+                    parse_readwise_csv(&read(path)?)
+                } else {
+                    Ok(parse_readwise_markdown(&read(path)?))
+                }
+            }
+        }
+    }
+}
+
+impl SourceScanner for HighlightsScanner {
+    fn scan(
+        &self,
+        output: CountingVecSender<Item>,
+        cancel: &CancellationToken,
+    ) -> Result<(), eyre::Report> {
+        let mut highlights = self.read_highlights()?;
+        dedupe_ids(&mut highlights);
+
+        let sender = BatchSender::new(SCAN_BATCH_SIZE, output);
+        for document in group_by_document(highlights) {
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            for item in document_items(self.source_id, &document) {
+                sender.add(item)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read(
+        &self,
+        existing: Option<&FoundItem>,
+        compare_strategy: ItemCompareStrategy,
+        item: &mut Item,
+    ) -> Result<SourceScannerReadResult, eyre::Report> {
+        // The highlights were read while scanning, since the links between them can't be set
+        // until the whole book has been seen.
+        Ok(prefilled_read(existing, compare_strategy, item))
+    }
+
+    fn latest_process_version(&self) -> i32 {
+        0
+    }
+
+    fn reprocess(&self, _item: &mut Item) -> Result<SourceScannerReadResult, eyre::Report> {
+        Ok(SourceScannerReadResult::Unchanged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn highlight(id: &str, document: &str, position: Option<u64>) -> Highlight {
+        Highlight {
+            id: id.to_string(),
+            document: document.to_string(),
+            title: document.to_string(),
+            author: None,
+            location: None,
+            position,
+            time: None,
+            text: id.to_string(),
+            note: None,
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn documents_and_links() {
+        let mut highlights = vec![
+            highlight("b", "Book", Some(20)),
+            highlight("x", "Other", None),
+            highlight("a", "Book", Some(10)),
+            highlight("c", "Book", Some(30)),
+            highlight("c", "Book", Some(40)),
+            highlight("empty", "Book", Some(25)),
+        ];
+        // Highlights without text are skipped, so "b" and "c" link to each other.
+        highlights[5].text = String::new();
+        dedupe_ids(&mut highlights);
+
+        let documents = group_by_document(highlights);
+        assert_eq!(documents.len(), 2);
+
+        let items = document_items(1, &documents[0]);
+        let links = items
+            .iter()
+            .map(|item| {
+                (
+                    item.external_id.as_str(),
+                    item.metadata.links.clone().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            links,
+            vec![
+                ("a", vec!["b".to_string()]),
+                ("b", vec!["a".to_string(), "c".to_string()]),
+                ("c", vec!["b".to_string(), "c-2".to_string()]),
+                ("c-2", vec!["c".to_string()]),
+            ]
+        );
+
+        let other = document_items(1, &documents[1]);
+        assert_eq!(other[0].metadata.links, Some(Vec::new()));
+    }
+
+    #[test]
+    fn content_with_note() {
+        let mut h = highlight("a", "Book", None);
+        h.note = Some("my thoughts".to_string());
+        assert_eq!(h.content(), "a\n\nNote: my thoughts");
+
+        h.text = String::new();
+        assert_eq!(h.content(), "my thoughts");
+    }
+}
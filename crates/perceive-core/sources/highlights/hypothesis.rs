@@ -0,0 +1,170 @@
+use serde::Deserialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use super::Highlight;
+
+/// The export from the Hypothesis website wraps the annotations in an object, the search API
+/// returns them in `rows`, and other tools save them as a plain list.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Export {
+    Wrapped { annotations: Vec<Annotation> },
+    List(Vec<Annotation>),
+    Search { rows: Vec<Annotation> },
+}
+
+#[derive(Debug, Deserialize)]
+struct Annotation {
+    id: String,
+    uri: String,
+    #[serde(default)]
+    created: Option<String>,
+    #[serde(default)]
+    updated: Option<String>,
+    /// The annotation's note
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    target: Vec<Target>,
+    #[serde(default)]
+    document: Option<Document>,
+    /// Set on replies to other annotations.
+    #[serde(default)]
+    references: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Target {
+    #[serde(default)]
+    selector: Vec<Selector>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+enum Selector {
+    #[serde(rename = "TextQuoteSelector")]
+    TextQuote { exact: String },
+    #[serde(rename = "TextPositionSelector")]
+    TextPosition { start: u64 },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct Document {
+    #[serde(default)]
+    title: Vec<String>,
+}
+
+/// Read the annotations from a Hypothesis JSON export. Page notes without any highlighted text
+/// become notes on their own, and replies are skipped.
+pub fn parse_hypothesis_export(content: &str) -> Result<Vec<Highlight>, eyre::Report> {
+    let annotations = match serde_json::from_str(content)? {
+        Export::Wrapped { annotations } => annotations,
+        Export::List(annotations) => annotations,
+        Export::Search { rows } => rows,
+    };
+
+    let highlights = annotations
+        .into_iter()
+        .filter(|a| a.references.is_empty())
+        .map(|a| {
+            let selectors = a.target.iter().flat_map(|t| t.selector.iter());
+            let mut text = String::new();
+            let mut position = None;
+            for selector in selectors {
+                match selector {
+                    Selector::TextQuote { exact } => text = exact.clone(),
+                    Selector::TextPosition { start } => position = Some(*start),
+                    Selector::Other => {}
+                }
+            }
+
+            let title = a
+                .document
+                .and_then(|d| d.title.into_iter().find(|t| !t.trim().is_empty()))
+                .unwrap_or_else(|| a.uri.clone());
+
+            Highlight {
+                id: format!("https://hypothes.is/a/{}", a.id),
+                document: a.uri.clone(),
+                title,
+                author: None,
+                location: Some(a.uri),
+                position,
+                time: a
+                    .updated
+                    .or(a.created)
+                    .and_then(|t| OffsetDateTime::parse(&t, &Rfc3339).ok()),
+                text,
+                note: Some(a.text).filter(|t| !t.trim().is_empty()),
+                tags: a.tags,
+            }
+        })
+        .collect();
+
+    Ok(highlights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hypothesis_export() {
+        let export = r#"{
+            "export_date": "2023-01-11T00:00:00.000000+00:00",
+            "export_userid": "acct:alice@hypothes.is",
+            "annotations": [
+                {
+                    "id": "abc",
+                    "created": "2023-01-10T11:00:00.000000+00:00",
+                    "updated": "2023-01-10T12:00:00.000000+00:00",
+                    "user": "acct:alice@hypothes.is",
+                    "uri": "https://example.com/article",
+                    "text": "My note",
+                    "tags": ["rust"],
+                    "target": [{
+                        "source": "https://example.com/article",
+                        "selector": [
+                            {"type": "RangeSelector", "startContainer": "/p[1]", "endContainer": "/p[1]", "startOffset": 0, "endOffset": 5},
+                            {"type": "TextPositionSelector", "start": 120, "end": 140},
+                            {"type": "TextQuoteSelector", "exact": "The highlighted text", "prefix": "", "suffix": ""}
+                        ]
+                    }],
+                    "document": {"title": ["An Article"]},
+                    "links": {"html": "https://hypothes.is/a/abc", "incontext": "https://hyp.is/abc/example.com/article"}
+                },
+                {
+                    "id": "def",
+                    "created": "2023-01-10T13:00:00.000000+00:00",
+                    "updated": "2023-01-10T13:00:00.000000+00:00",
+                    "uri": "https://example.com/article",
+                    "text": "A reply",
+                    "tags": [],
+                    "target": [{"source": "https://example.com/article"}],
+                    "references": ["abc"]
+                }
+            ]
+        }"#;
+
+        let highlights = parse_hypothesis_export(export).unwrap();
+        assert_eq!(
+            highlights,
+            vec![Highlight {
+                id: "https://hypothes.is/a/abc".to_string(),
+                document: "https://example.com/article".to_string(),
+                title: "An Article".to_string(),
+                author: None,
+                location: Some("https://example.com/article".to_string()),
+                position: Some(120),
+                time: Some(OffsetDateTime::from_unix_timestamp(1673352000).unwrap()),
+                text: "The highlighted text".to_string(),
+                note: Some("My note".to_string()),
+                tags: vec!["rust".to_string()],
+            }]
+        );
+    }
+}
@@ -0,0 +1,281 @@
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+use super::Highlight;
+
+const SEPARATOR: &str = "==========";
+
+#[derive(Debug, PartialEq, Eq)]
+enum ClippingKind {
+    Highlight,
+    Note,
+    Bookmark,
+}
+
+#[derive(Debug)]
+struct Clipping<'a> {
+    book: &'a str,
+    kind: ClippingKind,
+    /// The start and end of the location range, or the page for books without locations.
+    range: Option<(u64, u64)>,
+    location: Option<String>,
+    time: Option<OffsetDateTime>,
+    text: String,
+}
+
+/// Read the highlights from a Kindle `My Clippings.txt` file. Notes are attached to the highlight
+/// that they were written on.
+pub fn parse_kindle_clippings(content: &str) -> Vec<Highlight> {
+    let clippings = content
+        .trim_start_matches('\u{feff}')
+        .split(SEPARATOR)
+        .filter_map(parse_clipping)
+        .collect::<Vec<_>>();
+
+    let mut highlights: Vec<(Option<(u64, u64)>, Highlight)> = Vec::new();
+    let mut notes = Vec::new();
+    for clipping in clippings {
+        match clipping.kind {
+            ClippingKind::Bookmark => {}
+            ClippingKind::Note => notes.push(clipping),
+            ClippingKind::Highlight => {
+                // Changing a highlight adds another clipping and leaves the old one in the file,
+                // so a later highlight at the same place replaces the earlier one.
+                let existing = highlights.iter().position(|(range, h)| {
+                    h.document == clipping.book
+                        && clipping.range.is_some()
+                        && range.map(|r| r.0) == clipping.range.map(|r| r.0)
+                });
+
+                let range = clipping.range;
+                let highlight = clipping_highlight(clipping, "highlight");
+                match existing {
+                    Some(i) => highlights[i] = (range, highlight),
+                    None => highlights.push((range, highlight)),
+                }
+            }
+        }
+    }
+
+    for note in notes {
+        // A note is placed at the end of the highlight that it's attached to.
+        let attached = note.range.and_then(|(start, _)| {
+            highlights.iter_mut().rev().find(|(range, h)| {
+                h.document == note.book && range.map_or(false, |r| r.0 <= start && start <= r.1)
+            })
+        });
+
+        match attached {
+            Some((_, highlight)) if highlight.note.is_none() => highlight.note = Some(note.text),
+            _ => {
+                let range = note.range;
+                let mut highlight = clipping_highlight(note, "note");
+                highlight.note = Some(std::mem::take(&mut highlight.text));
+                highlights.push((range, highlight));
+            }
+        }
+    }
+
+    highlights.into_iter().map(|(_, h)| h).collect()
+}
+
+fn clipping_highlight(clipping: Clipping, kind: &str) -> Highlight {
+    let (title, author) = split_book(clipping.book);
+    let id = match clipping.range {
+        Some((start, _)) => format!("kindle:{}#{kind}-{start}", clipping.book),
+        None => format!("kindle:{}#{kind}", clipping.book),
+    };
+
+    Highlight {
+        id,
+        document: clipping.book.to_string(),
+        title,
+        author,
+        location: clipping.location,
+        position: clipping.range.map(|r| r.0),
+        time: clipping.time,
+        text: clipping.text,
+        note: None,
+        tags: Vec::new(),
+    }
+}
+
+/// Parse a clipping, which looks like
+///
+/// ```text
+/// Book Title (Author Name)
+/// - Your Highlight on page 12 | Location 150-152 | Added on Tuesday, January 10, 2023 12:00:00 PM
+///
+/// The highlighted text
+/// ```
+fn parse_clipping(clipping: &str) -> Option<Clipping<'_>> {
+    let mut lines = clipping.trim().lines();
+    let book = lines.next()?.trim().trim_start_matches('\u{feff}');
+    let header = lines.next()?.trim().trim_start_matches('-').trim();
+    let text = lines.collect::<Vec<_>>().join("\n").trim().to_string();
+
+    let lower = header.to_ascii_lowercase();
+    let kind = if lower.contains("bookmark") {
+        ClippingKind::Bookmark
+    } else if lower.contains("note") {
+        ClippingKind::Note
+    } else {
+        ClippingKind::Highlight
+    };
+
+    if book.is_empty() || (text.is_empty() && kind != ClippingKind::Bookmark) {
+        return None;
+    }
+
+    let mut location = None;
+    let mut page = None;
+    let mut time = None;
+    for part in header.split('|').map(str::trim) {
+        let lower = part.to_ascii_lowercase();
+        if let Some(added) = part.strip_prefix("Added on ") {
+            time = parse_added_on(added);
+        } else if let Some(i) = lower.find("location ").or_else(|| lower.find("loc. ")) {
+            let value = part[i..].split_once(' ').map(|(_, v)| v.trim());
+            location = value.and_then(parse_range);
+        } else if let Some(i) = lower.find("page ") {
+            page = parse_range(part[i + 5..].trim());
+        }
+    }
+
+    let (range, location) = match (location, page) {
+        (Some(range), _) => (Some(range), Some(format_range("Location", range))),
+        (None, Some(range)) => (Some(range), Some(format_range("Page", range))),
+        (None, None) => (None, None),
+    };
+
+    Some(Clipping {
+        book,
+        kind,
+        range,
+        location,
+        time,
+        text,
+    })
+}
+
+/// Parse a range like `150-152`. Older Kindles shorten the end of the range, like `1234-37`.
+fn parse_range(value: &str) -> Option<(u64, u64)> {
+    let value = value.split_whitespace().next()?;
+    let (start, end) = value.split_once('-').unwrap_or((value, value));
+    let start_value = start.parse::<u64>().ok()?;
+
+    let end = if end.len() < start.len() {
+        format!("{}{end}", &start[..start.len() - end.len()])
+    } else {
+        end.to_string()
+    };
+    let end_value = end.parse::<u64>().unwrap_or(start_value).max(start_value);
+
+    Some((start_value, end_value))
+}
+
+fn format_range(label: &str, (start, end): (u64, u64)) -> String {
+    if start == end {
+        format!("{label} {start}")
+    } else {
+        format!("{label} {start}-{end}")
+    }
+}
+
+/// Split a line like `Book Title (Author Name)` into the title and author.
+fn split_book(book: &str) -> (String, Option<String>) {
+    let author = book
+        .strip_suffix(')')
+        .and_then(|rest| rest.rfind('(').map(|start| (start, &rest[start + 1..])));
+
+    match author {
+        Some((start, author)) if start > 0 && !author.trim().is_empty() => (
+            book[..start].trim().to_string(),
+            Some(author.trim().to_string()),
+        ),
+        _ => (book.to_string(), None),
+    }
+}
+
+/// Parse a date like `Tuesday, January 10, 2023 12:00:00 PM`. The file doesn't include the time
+/// zone, so this assumes UTC.
+fn parse_added_on(date: &str) -> Option<OffsetDateTime> {
+    let format = time::format_description::parse(
+        "[weekday], [month repr:long] [day padding:none], [year] \
+         [hour repr:12 padding:none]:[minute]:[second] [period]",
+    )
+    .ok()?;
+    PrimitiveDateTime::parse(date.trim(), &format)
+        .ok()
+        .map(|t| t.assume_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clippings() {
+        let content = "\u{feff}The Book (Series 1) (Jane Author)
+- Your Highlight on page 5 | Location 70-71 | Added on Tuesday, January 10, 2023 12:00:00 PM
+
+The first highlight
+==========
+The Book (Series 1) (Jane Author)
+- Your Bookmark on Location 80 | Added on Tuesday, January 10, 2023 12:01:00 PM
+
+
+==========
+The Book (Series 1) (Jane Author)
+- Your Highlight on Location 150-152 | Added on Tuesday, January 10, 2023 12:02:00 PM
+
+Second
+==========
+The Book (Series 1) (Jane Author)
+- Your Note on Location 152 | Added on Tuesday, January 10, 2023 12:03:00 PM
+
+A note
+==========
+The Book (Series 1) (Jane Author)
+- Your Highlight on Location 150-155 | Added on Tuesday, January 10, 2023 12:04:00 PM
+
+Second, but longer
+==========
+Old Book
+- Highlight Loc. 1234-37 | Added on Thursday, April 19, 2012, 08:29 PM
+
+An old one
+==========
+";
+
+        let highlights = parse_kindle_clippings(content);
+        assert_eq!(highlights.len(), 3);
+
+        assert_eq!(
+            highlights[0],
+            Highlight {
+                id: "kindle:The Book (Series 1) (Jane Author)#highlight-70".to_string(),
+                document: "The Book (Series 1) (Jane Author)".to_string(),
+                title: "The Book (Series 1)".to_string(),
+                author: Some("Jane Author".to_string()),
+                location: Some("Location 70-71".to_string()),
+                position: Some(70),
+                time: Some(OffsetDateTime::from_unix_timestamp(1673352000).unwrap()),
+                text: "The first highlight".to_string(),
+                note: None,
+                tags: Vec::new(),
+            }
+        );
+
+        assert_eq!(highlights[1].text, "Second, but longer");
+        assert_eq!(highlights[1].location.as_deref(), Some("Location 150-155"));
+        assert_eq!(highlights[1].note.as_deref(), Some("A note"));
+
+        assert_eq!(highlights[2].title, "Old Book");
+        assert_eq!(highlights[2].author, None);
+        assert_eq!(
+            highlights[2].location.as_deref(),
+            Some("Location 1234-1237")
+        );
+        assert_eq!(highlights[2].time, None);
+    }
+}
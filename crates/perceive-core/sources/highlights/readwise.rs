@@ -0,0 +1,332 @@
+use eyre::Context;
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+use super::Highlight;
+use crate::sources::pipeline::content_hash;
+
+#[derive(Debug, Deserialize)]
+struct CsvRow {
+    #[serde(rename = "Highlight")]
+    highlight: String,
+    #[serde(rename = "Book Title")]
+    title: String,
+    #[serde(rename = "Book Author", default)]
+    author: String,
+    #[serde(rename = "Note", default)]
+    note: String,
+    #[serde(rename = "Tags", default)]
+    tags: String,
+    #[serde(rename = "Location Type", default)]
+    location_type: String,
+    #[serde(rename = "Location", default)]
+    location: String,
+    #[serde(rename = "Highlighted at", default)]
+    highlighted_at: String,
+}
+
+/// Read the highlights from a Readwise CSV export.
+pub fn parse_readwise_csv(content: &str) -> Result<Vec<Highlight>, eyre::Report> {
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    reader
+        .deserialize::<CsvRow>()
+        .enumerate()
+        .map(|(index, row)| {
+            let row = row.wrap_err_with(|| format!("Reading row {}", index + 1))?;
+            Ok(csv_highlight(row))
+        })
+        .collect()
+}
+
+fn csv_highlight(row: CsvRow) -> Highlight {
+    let position = row.location.parse::<u64>().ok();
+    let location = match (row.location_type.as_str(), position) {
+        ("location", Some(position)) => Some(format!("Location {position}")),
+        ("page", Some(position)) => Some(format!("Page {position}")),
+        _ => None,
+    };
+
+    // Articles don't have locations, so their highlights are identified by their text, which
+    // stays the same when highlights are added or removed before them.
+    let id = match position {
+        Some(position) => format!("readwise:{}#{}-{position}", row.title, row.location_type),
+        None => format!(
+            "readwise:{}#{}",
+            row.title,
+            content_hash(&[&row.title, &row.highlight])
+        ),
+    };
+
+    Highlight {
+        id,
+        document: row.title.clone(),
+        title: row.title,
+        author: Some(row.author).filter(|a| !a.is_empty()),
+        location,
+        position,
+        time: parse_highlighted_at(&row.highlighted_at),
+        text: row.highlight,
+        note: Some(row.note).filter(|n| !n.trim().is_empty()),
+        tags: row
+            .tags
+            .split(',')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(String::from)
+            .collect(),
+    }
+}
+
+/// Parse a date like `2023-01-10 12:00:00+00:00`.
+fn parse_highlighted_at(date: &str) -> Option<OffsetDateTime> {
+    let format = time::format_description::parse(
+        "[year]-[month]-[day] [hour]:[minute]:[second][offset_hour sign:mandatory]:[offset_minute]",
+    )
+    .ok()?;
+    OffsetDateTime::parse(date.trim(), &format).ok()
+}
+
+/// Read the highlights from one book in a Readwise Markdown export, which looks like
+///
+/// ```text
+/// # Book Title
+///
+/// ## Metadata
+/// - Author: [[Author Name]]
+/// - URL: https://example.com/article
+///
+/// ## Highlights
+/// - The highlighted text ([Location 150](https://readwise.io/to_kindle?...))
+///     - Note: A note on the highlight
+///     - Tags: [[tag]]
+/// ```
+pub fn parse_readwise_markdown(content: &str) -> Vec<Highlight> {
+    let mut title = None;
+    let mut author = None;
+    let mut url = None;
+    let mut in_highlights = false;
+    let mut entries: Vec<MarkdownEntry> = Vec::new();
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if let Some(heading) = trimmed.strip_prefix('#') {
+            let level = heading.chars().take_while(|&c| c == '#').count() + 1;
+            let heading = heading.trim_start_matches('#').trim();
+            if level == 1 && title.is_none() {
+                title = Some(heading.to_string());
+            }
+            in_highlights = heading.eq_ignore_ascii_case("highlights");
+            continue;
+        }
+
+        if !in_highlights {
+            if let Some(value) = trimmed.strip_prefix("- Author:") {
+                author = Some(strip_wikilink(value.trim()).to_string());
+            } else if let Some(value) = trimmed.strip_prefix("- URL:") {
+                url = Some(value.trim().to_string());
+            } else if let Some(value) = trimmed.strip_prefix("- Full Title:") {
+                title = Some(value.trim().to_string());
+            }
+            continue;
+        }
+
+        let indented = line.starts_with([' ', '\t']);
+        match (indented, trimmed.strip_prefix("- ")) {
+            (false, Some(text)) => entries.push(MarkdownEntry {
+                text: text.to_string(),
+                note: None,
+                tags: Vec::new(),
+            }),
+            (true, Some(sub)) => {
+                let Some(entry) = entries.last_mut() else {
+                    continue;
+                };
+
+                if let Some(note) = sub.strip_prefix("Note:") {
+                    entry.note = Some(note.trim().to_string());
+                } else if let Some(tags) = sub.strip_prefix("Tags:") {
+                    entry.tags = tags
+                        .split_whitespace()
+                        .map(|t| strip_wikilink(t.trim_start_matches('#')).to_string())
+                        .filter(|t| !t.is_empty())
+                        .collect();
+                }
+            }
+            // Highlights with several paragraphs continue onto the following lines.
+            _ if !trimmed.is_empty() => {
+                if let Some(entry) = entries.last_mut() {
+                    entry.text.push('\n');
+                    entry.text.push_str(trimmed);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let Some(title) = title else {
+        return Vec::new();
+    };
+
+    entries
+        .into_iter()
+        .map(|entry| {
+            let (text, link) = split_highlight_link(&entry.text);
+            let (label, link) = match link {
+                Some((label, link)) => (Some(label), Some(link)),
+                None => (None, None),
+            };
+
+            let position = label
+                .and_then(|l| l.split_whitespace().nth(1))
+                .and_then(|n| n.parse::<u64>().ok());
+            let location = label
+                .filter(|l| l.starts_with("Location ") || l.starts_with("Page "))
+                .map(String::from)
+                .or_else(|| url.clone());
+
+            Highlight {
+                id: link.map(String::from).unwrap_or_else(|| {
+                    format!("readwise:{title}#{}", content_hash(&[&title, text]))
+                }),
+                document: title.clone(),
+                title: title.clone(),
+                author: author.clone(),
+                location,
+                position,
+                time: None,
+                text: text.to_string(),
+                note: entry.note,
+                tags: entry.tags,
+            }
+        })
+        .collect()
+}
+
+struct MarkdownEntry {
+    text: String,
+    note: Option<String>,
+    tags: Vec<String>,
+}
+
+fn strip_wikilink(value: &str) -> &str {
+    value
+        .strip_prefix("[[")
+        .and_then(|v| v.strip_suffix("]]"))
+        .unwrap_or(value)
+}
+
+/// Split the link that Readwise adds to the end of each highlight, like
+/// `([Location 150](https://readwise.io/...))`, from the highlight's text.
+fn split_highlight_link(text: &str) -> (&str, Option<(&str, &str)>) {
+    let trimmed = text.trim_end();
+    let link = trimmed.strip_suffix("))").and_then(|rest| {
+        let start = rest.rfind(" ([")?;
+        let (label, url) = rest[start + 3..].split_once("](")?;
+        Some((start, label, url))
+    });
+
+    match link {
+        Some((start, label, url)) => (trimmed[..start].trim_end(), Some((label, url))),
+        None => (trimmed, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_export() {
+        let content = "Highlight,Book Title,Book Author,Amazon Book ID,Note,Color,Tags,Location Type,Location,Highlighted at,Document tags
+\"A highlight, with \"\"quotes\"\"\",The Book,Jane Author,B000,My note,yellow,\"one,two\",location,150,2023-01-10 12:00:00+00:00,
+Another,An Article,,,,,,order,,,
+";
+
+        let highlights = parse_readwise_csv(content).unwrap();
+        assert_eq!(
+            highlights,
+            vec![
+                Highlight {
+                    id: "readwise:The Book#location-150".to_string(),
+                    document: "The Book".to_string(),
+                    title: "The Book".to_string(),
+                    author: Some("Jane Author".to_string()),
+                    location: Some("Location 150".to_string()),
+                    position: Some(150),
+                    time: Some(OffsetDateTime::from_unix_timestamp(1673352000).unwrap()),
+                    text: "A highlight, with \"quotes\"".to_string(),
+                    note: Some("My note".to_string()),
+                    tags: vec!["one".to_string(), "two".to_string()],
+                },
+                Highlight {
+                    id: format!(
+                        "readwise:An Article#{}",
+                        content_hash(&["An Article", "Another"])
+                    ),
+                    document: "An Article".to_string(),
+                    title: "An Article".to_string(),
+                    author: None,
+                    location: None,
+                    position: None,
+                    time: None,
+                    text: "Another".to_string(),
+                    note: None,
+                    tags: Vec::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn markdown_export() {
+        let content = "# The Article
+
+![rw-book-cover](https://example.com/cover.png)
+
+## Metadata
+- Author: [[Jane Author]]
+- Full Title: The Article: A Longer Title
+- Category: #articles
+- URL: https://example.com/article
+
+## Highlights
+- The first highlight ([View Highlight](https://read.readwise.io/read/01abc))
+    - Note: A note
+    - Tags: [[rust]] #favorite
+- A second paragraph
+that continues ([Location 150](https://readwise.io/to_kindle?location=150))
+- No link
+";
+
+        let highlights = parse_readwise_markdown(content);
+        assert_eq!(highlights.len(), 3);
+
+        assert_eq!(
+            highlights[0],
+            Highlight {
+                id: "https://read.readwise.io/read/01abc".to_string(),
+                document: "The Article: A Longer Title".to_string(),
+                title: "The Article: A Longer Title".to_string(),
+                author: Some("Jane Author".to_string()),
+                location: Some("https://example.com/article".to_string()),
+                position: None,
+                time: None,
+                text: "The first highlight".to_string(),
+                note: Some("A note".to_string()),
+                tags: vec!["rust".to_string(), "favorite".to_string()],
+            }
+        );
+
+        assert_eq!(highlights[1].text, "A second paragraph\nthat continues");
+        assert_eq!(highlights[1].location.as_deref(), Some("Location 150"));
+        assert_eq!(highlights[1].position, Some(150));
+
+        assert_eq!(
+            highlights[2].id,
+            format!(
+                "readwise:The Article: A Longer Title#{}",
+                content_hash(&["The Article: A Longer Title", "No link"])
+            )
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use std::str::FromStr;
+
+use rusqlite::{named_params, params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use thiserror::Error;
+use time::OffsetDateTime;
+
+use super::extract::ExtractError;
+use crate::db::{Database, DbError};
+
+/// How long to wait before retrying an item after its first failure. This doubles with each
+/// failed attempt.
+pub const RETRY_BASE_DELAY: i64 = 60 * 60;
+
+/// How many times to try reading an item that keeps failing before giving up on it.
+pub const MAX_RETRY_ATTEMPTS: u32 = 6;
+
+/// How long to wait before reading an item again after it has failed `attempts` times in a row.
+pub fn retry_delay(attempts: u32) -> i64 {
+    RETRY_BASE_DELAY << attempts.saturating_sub(1).min(16)
+}
+
+/// Returns true if an item has failed so many times that the next failure should be its last.
+pub fn is_last_attempt(attempts: u32) -> bool {
+    attempts + 1 >= MAX_RETRY_ATTEMPTS
+}
+
+/// An error that may go away if the item is read again later, such as a timeout or a server
+/// error.
+#[derive(Debug, Error)]
+#[error("{message}")]
+pub struct TransientError {
+    pub kind: ItemErrorKind,
+    pub message: String,
+}
+
+/// The general category of an error encountered while reading an item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display, EnumString)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[strum(serialize_all = "snake_case")]
+pub enum ItemErrorKind {
+    /// A network or HTTP error
+    Fetch,
+    /// A filesystem error
+    Io,
+    /// The content was read but could not be parsed
+    Parse,
+    Other,
+}
+
+impl ItemErrorKind {
+    pub fn from_error(e: &eyre::Report) -> ItemErrorKind {
+        if let Some(transient) = e.downcast_ref::<TransientError>() {
+            return transient.kind;
+        }
+
+        #[cfg(feature = "browser-history")]
+        if e.downcast_ref::<reqwest::Error>().is_some() {
+            return ItemErrorKind::Fetch;
+        }
+
+        if e.downcast_ref::<std::io::Error>().is_some() {
+            ItemErrorKind::Io
+        } else if e.downcast_ref::<serde_json::Error>().is_some()
+            || e.downcast_ref::<std::string::FromUtf8Error>().is_some()
+            || e.downcast_ref::<ExtractError>().is_some()
+        {
+            ItemErrorKind::Parse
+        } else {
+            ItemErrorKind::Other
+        }
+    }
+}
+
+/// A recorded failure to read an item from a source.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemError {
+    pub source_id: i64,
+    pub external_id: String,
+    pub kind: ItemErrorKind,
+    pub message: String,
+    /// How many scans in a row have failed to read this item.
+    pub attempts: u32,
+    pub first_seen: OffsetDateTime,
+    pub last_seen: OffsetDateTime,
+    /// When the item will be read again. Only sources that fetch items over the network wait
+    /// for this.
+    pub next_retry_at: Option<OffsetDateTime>,
+}
+
+/// Record an error for an item, incrementing the attempt count if it had already failed before.
+/// The item is scheduled for a retry, with the delay doubling after each attempt.
+pub fn record_item_error(
+    database: &Database,
+    source_id: i64,
+    external_id: &str,
+    kind: ItemErrorKind,
+    message: &str,
+) -> Result<(), DbError> {
+    let conn = database.write_conn.lock();
+    let previous_attempts = conn
+        .prepare_cached("SELECT attempts FROM item_errors WHERE source_id = ? AND external_id = ?")?
+        .query_row(params![source_id, external_id], |row| row.get::<_, u32>(0))
+        .optional()?
+        .unwrap_or(0);
+
+    let mut stmt = conn.prepare_cached(
+        r##"INSERT INTO item_errors (source_id, external_id, kind, message, attempts, first_seen,
+                last_seen, next_retry_at)
+            VALUES (:source_id, :external_id, :kind, :message, :attempts, :now, :now,
+                :next_retry_at)
+            ON CONFLICT (source_id, external_id) DO UPDATE
+                SET kind=EXCLUDED.kind,
+                    message=EXCLUDED.message,
+                    attempts=EXCLUDED.attempts,
+                    last_seen=EXCLUDED.last_seen,
+                    next_retry_at=EXCLUDED.next_retry_at"##,
+    )?;
+
+    let attempts = previous_attempts + 1;
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    stmt.execute(named_params! {
+        ":source_id": source_id,
+        ":external_id": external_id,
+        ":kind": kind.to_string(),
+        ":message": message,
+        ":attempts": attempts,
+        ":now": now,
+        ":next_retry_at": now + retry_delay(attempts),
+    })?;
+
+    Ok(())
+}
+
+/// Remove the error for an item, after it was read successfully.
+pub fn clear_item_error(
+    database: &Database,
+    source_id: i64,
+    external_id: &str,
+) -> Result<(), DbError> {
+    let conn = database.write_conn.lock();
+    let mut stmt =
+        conn.prepare_cached("DELETE FROM item_errors WHERE source_id = ? AND external_id = ?")?;
+    stmt.execute(params![source_id, external_id])?;
+    Ok(())
+}
+
+/// List the errors for a source, most recent first, optionally filtered to a single kind.
+pub fn list_item_errors(
+    database: &Database,
+    source_id: i64,
+    kind: Option<ItemErrorKind>,
+) -> Result<Vec<ItemError>, DbError> {
+    let conn = database.read_pool.get()?;
+    let mut stmt = conn.prepare_cached(
+        r##"SELECT source_id, external_id, kind, message, attempts, first_seen, last_seen,
+            next_retry_at
+        FROM item_errors
+        WHERE source_id = ? AND (?2 IS NULL OR kind = ?2)
+        ORDER BY last_seen DESC, external_id"##,
+    )?;
+
+    let timestamp =
+        |t: i64| OffsetDateTime::from_unix_timestamp(t).unwrap_or(OffsetDateTime::UNIX_EPOCH);
+
+    let rows = stmt
+        .query_and_then(params![source_id, kind.map(|k| k.to_string())], |row| {
+            Ok::<_, DbError>(ItemError {
+                source_id: row.get(0)?,
+                external_id: row.get(1)?,
+                kind: ItemErrorKind::from_str(row.get_ref(2)?.as_str().map_err(DbError::query)?)
+                    .unwrap_or(ItemErrorKind::Other),
+                message: row.get(3)?,
+                attempts: row.get(4)?,
+                first_seen: timestamp(row.get(5)?),
+                last_seen: timestamp(row.get(6)?),
+                next_retry_at: row.get::<_, Option<i64>>(7)?.map(timestamp),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(rows)
+}
+
+/// Count the errors for a source, grouped by kind.
+pub fn count_item_errors(
+    database: &Database,
+    source_id: i64,
+) -> Result<Vec<(ItemErrorKind, u64)>, DbError> {
+    let conn = database.read_pool.get()?;
+    let mut stmt = conn.prepare_cached(
+        "SELECT kind, COUNT(*) FROM item_errors WHERE source_id = ? GROUP BY kind ORDER BY kind",
+    )?;
+
+    let rows = stmt
+        .query_and_then([source_id], |row| {
+            let kind = ItemErrorKind::from_str(row.get_ref(0)?.as_str().map_err(DbError::query)?)
+                .unwrap_or(ItemErrorKind::Other);
+            Ok::<_, DbError>((kind, row.get::<_, u64>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_backoff() {
+        assert_eq!(retry_delay(1), RETRY_BASE_DELAY);
+        assert_eq!(retry_delay(2), RETRY_BASE_DELAY * 2);
+        assert_eq!(retry_delay(3), RETRY_BASE_DELAY * 4);
+        assert_eq!(retry_delay(17), RETRY_BASE_DELAY << 16);
+        // The delay stops growing so that it can't overflow.
+        assert_eq!(retry_delay(100), RETRY_BASE_DELAY << 16);
+    }
+
+    #[test]
+    fn retry_cap() {
+        let last = (0..20).find(|&attempts| is_last_attempt(attempts)).unwrap();
+        assert_eq!(last, MAX_RETRY_ATTEMPTS - 1);
+        assert!(!is_last_attempt(0));
+        assert!(is_last_attempt(MAX_RETRY_ATTEMPTS));
+    }
+}
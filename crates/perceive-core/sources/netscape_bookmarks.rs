@@ -0,0 +1,406 @@
+use std::path::PathBuf;
+
+use ahash::HashMap;
+use eyre::{eyre, Context, Result};
+use itertools::Itertools;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use super::{
+    parse_html::{read_web_page, reprocess_html_article, should_skip, HTML_PROCESS_VERSION},
+    pipeline::{CountingVecSender, FoundItem, SourceScanner, SourceScannerReadResult},
+    web_fetcher::{FetchConfig, WebFetcher},
+    ItemCompareStrategy,
+};
+use crate::{cancel::CancellationToken, Item, ItemMetadata};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NetscapeBookmarksConfig {
+    /// Domains that we should never check
+    pub skip: Vec<String>,
+    /// Add the names of the folders containing each bookmark to its tags.
+    #[serde(default)]
+    pub folder_tags: bool,
+    #[serde(default)]
+    pub fetch: FetchConfig,
+}
+
+/// A bookmark read from a Netscape bookmarks file.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct NetscapeBookmark {
+    pub url: String,
+    pub title: String,
+    /// The text from the `<DD>` element following the bookmark.
+    pub description: Option<String>,
+    pub add_date: Option<i64>,
+    pub last_modified: Option<i64>,
+    pub tags: Vec<String>,
+    /// The names of the folders containing the bookmark, from the outermost in.
+    pub folders: Vec<String>,
+}
+
+/// Parse a bookmarks file in the Netscape format that browsers and bookmarking services export.
+/// The format is only loosely HTML, so this just looks at the tags that matter instead of
+/// parsing a full document.
+pub fn parse_bookmarks_html(content: &str) -> Vec<NetscapeBookmark> {
+    let mut bookmarks: Vec<NetscapeBookmark> = Vec::new();
+    // Each open `<DL>`, with the name of the folder if it has one.
+    let mut folders: Vec<Option<String>> = Vec::new();
+    // The name from the last `<H3>`, which applies to the next `<DL>`.
+    let mut pending_folder = None;
+    // True if a `<DD>` would describe the last bookmark, rather than a folder.
+    let mut after_bookmark = false;
+
+    let mut rest = content;
+    while let Some(start) = rest.find('<') {
+        let after_start = &rest[start + 1..];
+        let Some(end) = after_start.find('>') else {
+            break;
+        };
+
+        let tag = &after_start[..end];
+        rest = &after_start[end + 1..];
+
+        let (name, attrs) = match tag.find(char::is_whitespace) {
+            Some(pos) => (&tag[..pos], &tag[pos..]),
+            None => (tag, ""),
+        };
+
+        // The text up to the next tag.
+        let text = || rest[..rest.find('<').unwrap_or(rest.len())].trim();
+
+        match name.to_ascii_uppercase().as_str() {
+            "H3" => {
+                pending_folder = Some(unescape_html(text()));
+                after_bookmark = false;
+            }
+            "DL" => {
+                folders.push(pending_folder.take());
+                after_bookmark = false;
+            }
+            "/DL" => {
+                folders.pop();
+                after_bookmark = false;
+            }
+            "A" => {
+                let attrs = parse_attributes(attrs);
+                let Some(url) = attrs.get("HREF") else {
+                    continue;
+                };
+
+                bookmarks.push(NetscapeBookmark {
+                    url: unescape_html(url),
+                    title: unescape_html(text()),
+                    description: None,
+                    add_date: attrs.get("ADD_DATE").and_then(|d| parse_timestamp(d)),
+                    last_modified: attrs.get("LAST_MODIFIED").and_then(|d| parse_timestamp(d)),
+                    tags: attrs
+                        .get("TAGS")
+                        .map(|tags| {
+                            tags.split(',')
+                                .map(|t| unescape_html(t.trim()))
+                                .filter(|t| !t.is_empty())
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                    folders: folders.iter().flatten().cloned().collect(),
+                });
+                after_bookmark = true;
+            }
+            "DD" => {
+                if after_bookmark {
+                    let description = unescape_html(text());
+                    if let Some(last) = bookmarks.last_mut() {
+                        last.description = Some(description).filter(|d| !d.is_empty());
+                    }
+                }
+                after_bookmark = false;
+            }
+            _ => {}
+        }
+    }
+
+    bookmarks
+}
+
+/// Parse the attributes of a tag into a map keyed by the uppercased attribute name.
+fn parse_attributes(mut input: &str) -> HashMap<String, String> {
+    let mut attrs = HashMap::default();
+    loop {
+        input = input.trim_start();
+        let Some(eq) = input.find('=') else {
+            break;
+        };
+
+        let name = input[..eq].trim().to_ascii_uppercase();
+        let value_start = input[eq + 1..].trim_start();
+        let (value, remaining) = match value_start.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let value = &value_start[1..];
+                match value.find(quote) {
+                    Some(end) => (&value[..end], &value[end + 1..]),
+                    None => (value, ""),
+                }
+            }
+            _ => {
+                let end = value_start
+                    .find(char::is_whitespace)
+                    .unwrap_or(value_start.len());
+                (&value_start[..end], &value_start[end..])
+            }
+        };
+
+        // A name may be preceded by attributes without values, like `PRIVATE`.
+        let name = name.rsplit(char::is_whitespace).next().unwrap_or_default();
+        attrs.insert(name.to_string(), value.to_string());
+        input = remaining;
+    }
+
+    attrs
+}
+
+/// Parse a timestamp in seconds since the Unix epoch. Some exporters write milliseconds or
+/// microseconds instead, so very large values are scaled down.
+fn parse_timestamp(value: &str) -> Option<i64> {
+    let value = value.trim().parse::<i64>().ok().filter(|v| *v > 0)?;
+    Some(match value {
+        v if v > 100_000_000_000_000 => v / 1_000_000,
+        v if v > 100_000_000_000 => v / 1000,
+        v => v,
+    })
+}
+
+fn unescape_html(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 10)
+            .map(|end| (&rest[1..end + 1], end + 2));
+        let decoded = entity.and_then(|(name, len)| {
+            let c = match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ => name
+                    .strip_prefix("#x")
+                    .or_else(|| name.strip_prefix("#X"))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| name.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, len))
+        });
+
+        match decoded {
+            Some((c, len)) => {
+                output.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+pub struct NetscapeBookmarksScanner {
+    pub source_id: i64,
+    /// The path to the bookmarks file
+    pub location: String,
+    pub config: NetscapeBookmarksConfig,
+    pub fetcher: WebFetcher,
+}
+
+impl NetscapeBookmarksScanner {
+    pub fn new(source_id: i64, location: String, config: NetscapeBookmarksConfig) -> Result<Self> {
+        let fetcher = WebFetcher::new(config.fetch.clone(), true)?;
+        Ok(Self {
+            source_id,
+            location,
+            config,
+            fetcher,
+        })
+    }
+}
+
+impl SourceScanner for NetscapeBookmarksScanner {
+    fn scan(
+        &self,
+        tx: CountingVecSender<Item>,
+        cancel: &CancellationToken,
+    ) -> Result<(), eyre::Report> {
+        let path = PathBuf::from(&self.location);
+        let content =
+            std::fs::read_to_string(&path).wrap_err_with(|| eyre!("Reading {}", path.display()))?;
+
+        let timestamp = |t: i64| OffsetDateTime::from_unix_timestamp(t).ok();
+
+        // The same page may be bookmarked in multiple folders.
+        let mut output: HashMap<String, Item> = HashMap::default();
+        for bookmark in parse_bookmarks_html(&content) {
+            let Ok(parsed) = Url::parse(&bookmark.url) else {
+                continue;
+            };
+
+            if !parsed.scheme().starts_with("http") || should_skip(&self.config.skip, &parsed) {
+                continue;
+            }
+
+            let mut tags = bookmark.tags;
+            if self.config.folder_tags {
+                tags.extend(bookmark.folders);
+            }
+
+            let item = output.entry(bookmark.url.clone()).or_insert_with(|| Item {
+                id: -1,
+                external_id: bookmark.url,
+                source_id: self.source_id,
+                content: None,
+                raw_content: None,
+                hash: None,
+                process_version: HTML_PROCESS_VERSION,
+                skipped: None,
+                metadata: ItemMetadata {
+                    name: Some(bookmark.title).filter(|t| !t.is_empty()),
+                    description: bookmark.description,
+                    atime: bookmark
+                        .last_modified
+                        .or(bookmark.add_date)
+                        .and_then(timestamp),
+                    tags: Some(Vec::new()),
+                    ..Default::default()
+                },
+            });
+
+            let item_tags = item.metadata.tags.get_or_insert_with(Vec::new);
+            for tag in tags {
+                if !item_tags.contains(&tag) {
+                    item_tags.push(tag);
+                }
+            }
+        }
+
+        for batch in &output.into_values().chunks(64) {
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            tx.send(batch.collect())?;
+        }
+
+        Ok(())
+    }
+
+    fn read(
+        &self,
+        existing: Option<&FoundItem>,
+        compare_strategy: ItemCompareStrategy,
+        item: &mut Item,
+    ) -> Result<SourceScannerReadResult, eyre::Report> {
+        read_web_page(&self.fetcher, existing, compare_strategy, item)
+    }
+
+    fn latest_process_version(&self) -> i32 {
+        HTML_PROCESS_VERSION
+    }
+
+    fn reprocess(&self, item: &mut Item) -> Result<SourceScannerReadResult, eyre::Report> {
+        reprocess_html_article(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOOKMARKS: &str = r##"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<!-- This is an automatically generated file. -->
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+<DL><p>
+    <DT><A HREF="https://example.com/top" ADD_DATE="1670000000">Top level</A>
+    <DT><H3 ADD_DATE="1670000001" LAST_MODIFIED="1670000002">Rust &amp; Friends</H3>
+    <DD>A folder description
+    <DL><p>
+        <DT><A HREF="https://example.com/a?x=1&amp;y=2" ADD_DATE="1670000003000" LAST_MODIFIED="1670000004" TAGS="rust,async">Article A</A>
+        <DD>The description
+of article A
+        <DT><H3>Nested</H3>
+        <DL><p>
+            <DT><A HREF='https://example.com/b' PRIVATE="0">B &lt;3</A>
+        </DL><p>
+    </DL><p>
+    <DT><A HREF="https://example.com/c">C</A>
+</DL><p>
+"##;
+
+    #[test]
+    fn parse_file() {
+        let bookmarks = parse_bookmarks_html(BOOKMARKS);
+        assert_eq!(
+            bookmarks,
+            vec![
+                NetscapeBookmark {
+                    url: "https://example.com/top".to_string(),
+                    title: "Top level".to_string(),
+                    add_date: Some(1670000000),
+                    ..Default::default()
+                },
+                NetscapeBookmark {
+                    url: "https://example.com/a?x=1&y=2".to_string(),
+                    title: "Article A".to_string(),
+                    description: Some("The description\nof article A".to_string()),
+                    add_date: Some(1670000003),
+                    last_modified: Some(1670000004),
+                    tags: vec!["rust".to_string(), "async".to_string()],
+                    folders: vec!["Rust & Friends".to_string()],
+                },
+                NetscapeBookmark {
+                    url: "https://example.com/b".to_string(),
+                    title: "B <3".to_string(),
+                    folders: vec!["Rust & Friends".to_string(), "Nested".to_string()],
+                    ..Default::default()
+                },
+                NetscapeBookmark {
+                    url: "https://example.com/c".to_string(),
+                    title: "C".to_string(),
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn attributes() {
+        let attrs = parse_attributes(r#" HREF="a b" add_date=123 TAGS='x' "#);
+        assert_eq!(attrs.get("HREF").map(|s| s.as_str()), Some("a b"));
+        assert_eq!(attrs.get("ADD_DATE").map(|s| s.as_str()), Some("123"));
+        assert_eq!(attrs.get("TAGS").map(|s| s.as_str()), Some("x"));
+    }
+
+    #[test]
+    fn entities() {
+        assert_eq!(
+            unescape_html("a &amp; b &#39;c&#x27; &bogus"),
+            "a & b 'c' &bogus"
+        );
+    }
+}
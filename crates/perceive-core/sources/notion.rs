@@ -0,0 +1,535 @@
+use std::{
+    fs::File,
+    io::{Cursor, Read, Seek},
+    path::Path,
+};
+
+use ahash::{HashMap, HashSet};
+use eyre::{eyre, Context, Result};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use time::{Date, OffsetDateTime, PrimitiveDateTime};
+use zip::ZipArchive;
+
+use super::{
+    pipeline::{
+        prefilled_read, CountingVecSender, FoundItem, SourceScanner, SourceScannerReadResult,
+        SCAN_BATCH_SIZE,
+    },
+    ItemCompareStrategy,
+};
+use crate::{batch_sender::BatchSender, cancel::CancellationToken, Item, ItemMetadata};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct NotionConfig {}
+
+/// A page or database row read from a Notion export.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotionPage {
+    pub external_id: String,
+    pub title: String,
+    /// The titles of the pages above this one, starting from the top.
+    pub parents: Vec<String>,
+    /// The external IDs of the parent page and the pages that this page links to
+    pub links: Vec<String>,
+    pub created: Option<OffsetDateTime>,
+    pub updated: Option<OffsetDateTime>,
+    pub tags: Vec<String>,
+    pub text: String,
+}
+
+/// A Markdown or CSV file from the export, with its path inside the export.
+struct ExportFile {
+    path: String,
+    content: String,
+}
+
+/// Read the Markdown and CSV files from an export zip, or from the directory that it was
+/// extracted to. Large exports are split into several zips inside the main one.
+fn read_export(location: &str) -> Result<Vec<ExportFile>> {
+    let root = Path::new(location);
+    if root.is_dir() {
+        return ignore::WalkBuilder::new(root)
+            .build()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.into_path())
+            .filter(|path| is_export_file(&path.to_string_lossy()))
+            .map(|path| {
+                let content = std::fs::read_to_string(&path)
+                    .wrap_err_with(|| eyre!("Reading {}", path.display()))?;
+                let relative = path.strip_prefix(root).unwrap_or(&path);
+                Ok(ExportFile {
+                    path: relative.to_string_lossy().replace('\\', "/"),
+                    content,
+                })
+            })
+            .collect();
+    }
+
+    let file = File::open(root).wrap_err_with(|| eyre!("Opening {location}"))?;
+    let mut zip = ZipArchive::new(file).wrap_err_with(|| eyre!("Reading {location}"))?;
+    let mut files = Vec::new();
+    read_zip(&mut zip, &mut files, true)?;
+    Ok(files)
+}
+
+fn read_zip<R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    files: &mut Vec<ExportFile>,
+    allow_nested: bool,
+) -> Result<()> {
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        let path = entry.name().to_string();
+        if allow_nested && path.to_ascii_lowercase().ends_with(".zip") {
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content)?;
+            let mut nested =
+                ZipArchive::new(Cursor::new(content)).wrap_err_with(|| eyre!("Reading {path}"))?;
+            read_zip(&mut nested, files, false)?;
+        } else if is_export_file(&path) {
+            let mut content = String::new();
+            entry
+                .read_to_string(&mut content)
+                .wrap_err_with(|| eyre!("Reading {path}"))?;
+            files.push(ExportFile { path, content });
+        }
+    }
+
+    Ok(())
+}
+
+fn is_export_file(path: &str) -> bool {
+    let path = path.to_ascii_lowercase();
+    path.ends_with(".md") || path.ends_with(".csv")
+}
+
+/// Split a file or directory name like `Page Title 0123456789abcdef0123456789abcdef` into the
+/// title and Notion's ID for the page.
+fn split_name(name: &str) -> (&str, Option<&str>) {
+    match name.rsplit_once(' ') {
+        Some((title, id)) if is_notion_id(id) => (title, Some(id)),
+        _ => (name, None),
+    }
+}
+
+fn is_notion_id(id: &str) -> bool {
+    id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn page_url(id: &str) -> String {
+    format!("https://www.notion.so/{id}")
+}
+
+/// Read the pages and database rows from a Notion export. Database rows that were exported as
+/// pages are read from their Markdown files, and the rest come from the database's CSV file.
+fn parse_notion_export(files: Vec<ExportFile>) -> Vec<NotionPage> {
+    let (csvs, pages): (Vec<_>, Vec<_>) = files
+        .into_iter()
+        .partition(|f| f.path.to_ascii_lowercase().ends_with(".csv"));
+
+    // The titles of the pages in each directory, for finding the database rows that have their
+    // own page.
+    let mut titles_by_dir: HashMap<String, HashSet<String>> = HashMap::default();
+    let mut output = Vec::new();
+    for file in pages {
+        let (dir, name) = file.path.rsplit_once('/').unwrap_or(("", &file.path));
+        let stem = name.strip_suffix(".md").unwrap_or(name);
+        let (title, id) = split_name(stem);
+        let external_id = id.map(page_url).unwrap_or_else(|| file.path.clone());
+
+        let page = markdown_page(external_id, title, dir, &file.content);
+        titles_by_dir
+            .entry(dir.to_string())
+            .or_default()
+            .insert(page.title.clone());
+        output.push(page);
+    }
+
+    // Newer exports include both the rows in the database's view and a `_all` file with every
+    // row, so skip the partial file when both exist.
+    let csv_paths = csvs.iter().map(|f| f.path.clone()).collect::<HashSet<_>>();
+    for file in &csvs {
+        let stem = file.path.strip_suffix(".csv").unwrap_or(&file.path);
+        if csv_paths.contains(&format!("{stem}_all.csv")) {
+            continue;
+        }
+
+        let database_dir = stem.strip_suffix("_all").unwrap_or(stem);
+        let exported_rows = titles_by_dir.get(database_dir);
+        let parents = parent_titles(database_dir);
+        output.extend(
+            csv_rows(file, &parents)
+                .into_iter()
+                .filter(|row| !exported_rows.map_or(false, |titles| titles.contains(&row.title))),
+        );
+    }
+
+    output
+}
+
+/// The titles of the pages that a directory in the export is nested under.
+fn parent_titles(dir: &str) -> Vec<String> {
+    dir.split('/')
+        .filter(|part| !part.is_empty())
+        .map(|part| split_name(part).0.to_string())
+        .collect()
+}
+
+/// Read a page exported as Markdown, which starts with the title, followed by the properties
+/// for database rows.
+fn markdown_page(external_id: String, file_title: &str, dir: &str, content: &str) -> NotionPage {
+    let content = content.trim_start_matches('\u{feff}').trim();
+    let (title, body) = match content.strip_prefix("# ") {
+        Some(rest) => {
+            let (title, body) = rest.split_once('\n').unwrap_or((rest, ""));
+            (title.trim(), body.trim())
+        }
+        None => (file_title, content),
+    };
+
+    let mut links = Vec::new();
+    // The directory holding a page is named after its parent page.
+    if let Some((_, Some(parent_id))) = dir.rsplit('/').next().map(split_name) {
+        links.push(page_url(parent_id));
+    }
+    links.extend(page_links(body).into_iter().map(|id| page_url(&id)));
+
+    let properties = body
+        .split("\n\n")
+        .next()
+        .filter(|block| block.lines().all(|line| property(line).is_some()))
+        .map(|block| block.lines().filter_map(property).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let (created, updated, tags) = page_properties(properties);
+
+    NotionPage {
+        external_id,
+        title: title.to_string(),
+        parents: parent_titles(dir),
+        links: links.into_iter().unique().collect(),
+        created,
+        updated,
+        tags,
+        text: body.to_string(),
+    }
+}
+
+/// Parse a property line like `Status: Done`.
+fn property(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(": ")?;
+    let valid_key = !key.is_empty()
+        && key.len() <= 50
+        && !key.starts_with(['#', '-', '*', '>', '|', '!', '['])
+        && !key.contains("](");
+    valid_key.then_some((key.trim(), value.trim()))
+}
+
+/// Get the created and updated times, and the tags, from a page's properties.
+fn page_properties<'a>(
+    properties: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> (Option<OffsetDateTime>, Option<OffsetDateTime>, Vec<String>) {
+    let mut created = None;
+    let mut updated = None;
+    let mut tags = Vec::new();
+    for (key, value) in properties {
+        match key.to_ascii_lowercase().as_str() {
+            "created" | "created time" | "date created" => created = parse_notion_date(value),
+            "last edited time" | "last edited" | "updated" => updated = parse_notion_date(value),
+            "tags" => {
+                tags = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|t| !t.is_empty())
+                    .map(String::from)
+                    .collect()
+            }
+            _ => {}
+        }
+    }
+
+    (created, updated, tags)
+}
+
+/// Find the IDs of the pages linked from a page. Links to other pages in the export point to
+/// their Markdown files, like `[Page](Parent%20abc/Page%200123...cdef.md)`.
+fn page_links(body: &str) -> Vec<String> {
+    let mut ids = Vec::new();
+    let mut rest = body;
+    while let Some(start) = rest.find("](") {
+        rest = &rest[start + 2..];
+        let Some(end) = rest.find(')') else {
+            break;
+        };
+
+        let target = &rest[..end];
+        rest = &rest[end..];
+        if let Some(stem) = target.strip_suffix(".md") {
+            let start = stem.len().saturating_sub(32);
+            if let Some(id) = stem.get(start..).filter(|id| is_notion_id(id)) {
+                ids.push(id.to_string());
+            }
+        }
+    }
+
+    ids
+}
+
+/// Parse a date like `January 10, 2023 12:00 PM` or `January 10, 2023`. Exports don't include
+/// the time zone, so this assumes UTC.
+fn parse_notion_date(date: &str) -> Option<OffsetDateTime> {
+    let date = date.trim();
+    let with_time = time::format_description::parse(
+        "[month repr:long] [day padding:none], [year] [hour repr:12 padding:none]:[minute] [period]",
+    )
+    .ok()?;
+    if let Ok(t) = PrimitiveDateTime::parse(date, &with_time) {
+        return Some(t.assume_utc());
+    }
+
+    let date_only =
+        time::format_description::parse("[month repr:long] [day padding:none], [year]").ok()?;
+    Date::parse(date, &date_only)
+        .ok()
+        .map(|d| d.midnight().assume_utc())
+}
+
+/// Read the rows from a database's CSV file. The first column is the row's title.
+fn csv_rows(file: &ExportFile, parents: &[String]) -> Vec<NotionPage> {
+    let mut reader =
+        csv::Reader::from_reader(file.content.trim_start_matches('\u{feff}').as_bytes());
+    let Ok(headers) = reader.headers().cloned() else {
+        return Vec::new();
+    };
+
+    // Rows are identified by their title, which doesn't have to be unique, so number the
+    // repeats.
+    let mut seen = HashSet::default();
+    reader
+        .records()
+        .filter_map(|record| record.ok())
+        .filter_map(|record| {
+            let title = record.get(0)?.trim();
+            if title.is_empty() {
+                return None;
+            }
+
+            let mut external_id = format!("{}#{title}", file.path);
+            let mut n = 2;
+            while !seen.insert(external_id.clone()) {
+                external_id = format!("{}#{title}-{n}", file.path);
+                n += 1;
+            }
+
+            let properties = headers
+                .iter()
+                .zip(record.iter())
+                .skip(1)
+                .filter(|(_, value)| !value.trim().is_empty())
+                .collect::<Vec<_>>();
+            let text = properties
+                .iter()
+                .map(|(key, value)| format!("{key}: {}", value.trim()))
+                .join("\n");
+            let (created, updated, tags) = page_properties(properties);
+
+            Some(NotionPage {
+                external_id,
+                title: title.to_string(),
+                parents: parents.to_vec(),
+                links: Vec::new(),
+                created,
+                updated,
+                tags,
+                text,
+            })
+        })
+        .collect()
+}
+
+/// Reads the pages and database rows from a Notion export zip. Each page links to its parent
+/// page and to the pages that it mentions.
+pub struct NotionScanner {
+    pub source_id: i64,
+    pub location: String,
+    pub config: NotionConfig,
+}
+
+impl SourceScanner for NotionScanner {
+    fn scan(&self, output: CountingVecSender<Item>, cancel: &CancellationToken) -> Result<()> {
+        let pages = parse_notion_export(read_export(&self.location)?);
+        let sender = BatchSender::new(SCAN_BATCH_SIZE, output);
+        let mut seen = HashSet::default();
+        for page in pages {
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            if page.text.is_empty() || !seen.insert(page.external_id.clone()) {
+                continue;
+            }
+
+            let description = if page.parents.is_empty() {
+                None
+            } else {
+                Some(page.parents.join(" / "))
+            };
+
+            sender.add(Item {
+                id: -1,
+                source_id: self.source_id,
+                external_id: page.external_id,
+                hash: None,
+                content: Some(page.text),
+                raw_content: None,
+                skipped: None,
+                process_version: 0,
+                metadata: ItemMetadata {
+                    name: Some(page.title),
+                    description,
+                    mtime: page.updated.or(page.created),
+                    created: page.created,
+                    tags: Some(page.tags),
+                    links: Some(page.links),
+                    ..Default::default()
+                },
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn read(
+        &self,
+        existing: Option<&FoundItem>,
+        compare_strategy: ItemCompareStrategy,
+        item: &mut Item,
+    ) -> Result<SourceScannerReadResult> {
+        // The pages were read from the export while scanning.
+        Ok(prefilled_read(existing, compare_strategy, item))
+    }
+
+    fn latest_process_version(&self) -> i32 {
+        0
+    }
+
+    fn reprocess(&self, _item: &mut Item) -> Result<SourceScannerReadResult> {
+        Ok(SourceScannerReadResult::Unchanged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARENT_ID: &str = "0123456789abcdef0123456789abcdef";
+    const CHILD_ID: &str = "11112222333344445555666677778888";
+    const DB_ID: &str = "aaaabbbbccccddddeeeeffff00001111";
+    const ROW_ID: &str = "99998888777766665555444433332222";
+
+    fn file(path: &str, content: &str) -> ExportFile {
+        ExportFile {
+            path: path.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn export() {
+        let files = vec![
+            file(
+                &format!("Projects {PARENT_ID}.md"),
+                &format!(
+                    "# Projects\n\nAll of the projects. See [Launch](Projects%20{PARENT_ID}/Launch%20{CHILD_ID}.md).\n"
+                ),
+            ),
+            file(
+                &format!("Projects {PARENT_ID}/Launch {CHILD_ID}.md"),
+                "# Launch\n\nCreated: January 10, 2023 12:00 PM\nTags: work, urgent\n\nShip it: soon.\n",
+            ),
+            file(
+                &format!("Projects {PARENT_ID}/Tasks {DB_ID}.csv"),
+                "\u{feff}Name,Status,Created\nWrite docs,Done,\"January 10, 2023\"\nFix bug,,\n",
+            ),
+            file(
+                &format!("Projects {PARENT_ID}/Tasks {DB_ID}_all.csv"),
+                "Name,Status,Created\nWrite docs,Done,\"January 10, 2023\"\nFix bug,,\nOld task,Done,\n",
+            ),
+            file(
+                &format!("Projects {PARENT_ID}/Tasks {DB_ID}/Write docs {ROW_ID}.md"),
+                "# Write docs\n\nStatus: Done\nCreated: January 10, 2023\n",
+            ),
+        ];
+
+        let pages = parse_notion_export(files);
+        let titles = pages.iter().map(|p| p.title.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            titles,
+            vec!["Projects", "Launch", "Write docs", "Fix bug", "Old task"]
+        );
+
+        assert_eq!(
+            pages[0].external_id,
+            format!("https://www.notion.so/{PARENT_ID}")
+        );
+        assert_eq!(
+            pages[0].links,
+            vec![format!("https://www.notion.so/{CHILD_ID}")]
+        );
+        assert!(pages[0].parents.is_empty());
+
+        assert_eq!(
+            pages[1],
+            NotionPage {
+                external_id: format!("https://www.notion.so/{CHILD_ID}"),
+                title: "Launch".to_string(),
+                parents: vec!["Projects".to_string()],
+                links: vec![format!("https://www.notion.so/{PARENT_ID}")],
+                created: Some(OffsetDateTime::from_unix_timestamp(1673352000).unwrap()),
+                updated: None,
+                tags: vec!["work".to_string(), "urgent".to_string()],
+                text: "Created: January 10, 2023 12:00 PM\nTags: work, urgent\n\nShip it: soon."
+                    .to_string(),
+            }
+        );
+
+        assert_eq!(pages[2].parents, vec!["Projects", "Tasks"]);
+        assert_eq!(
+            pages[2].created,
+            Some(OffsetDateTime::from_unix_timestamp(1673308800).unwrap())
+        );
+
+        assert_eq!(
+            pages[3].external_id,
+            format!("Projects {PARENT_ID}/Tasks {DB_ID}_all.csv#Fix bug")
+        );
+        assert_eq!(pages[3].parents, vec!["Projects", "Tasks"]);
+        assert_eq!(pages[3].text, "");
+        assert_eq!(pages[4].text, "Status: Done");
+    }
+
+    #[test]
+    fn duplicate_row_titles() {
+        let path = format!("Tasks {DB_ID}.csv");
+        let files = vec![file(
+            &path,
+            "Name,Status\nUntitled,Done\nUntitled,Open\nOther,Open\n",
+        )];
+
+        let pages = parse_notion_export(files);
+        let ids = pages
+            .iter()
+            .map(|p| p.external_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            vec![
+                format!("{path}#Untitled"),
+                format!("{path}#Untitled-2"),
+                format!("{path}#Other"),
+            ]
+        );
+        assert_eq!(pages[0].text, "Status: Done");
+        assert_eq!(pages[1].text, "Status: Open");
+    }
+}
@@ -1,12 +1,18 @@
-use std::{io::Cursor, time::SystemTime};
+use std::io::Cursor;
 
 use eyre::Context;
 use http::{HeaderValue, StatusCode};
-use reqwest::{blocking::Client, Url};
+use reqwest::Url;
 use time::OffsetDateTime;
 
-use super::pipeline::{FoundItem, SourceScannerReadResult};
-use crate::{Item, SkipReason};
+use super::{
+    extract::extractors,
+    item_errors::{is_last_attempt, ItemErrorKind, TransientError},
+    pipeline::{FoundItem, SourceScannerReadResult},
+    web_fetcher::WebFetcher,
+    ItemCompareStrategy,
+};
+use crate::{HttpCache, Item, SkipReason};
 
 pub const ALWAYS_SKIP: [&str; 5] = [
     // Signin pages. These show up a lot but never contain searchable content.
@@ -27,8 +33,59 @@ pub fn should_skip(skip: &[impl AsRef<str>], url: &Url) -> bool {
         .any(|skip| host.ends_with(skip))
 }
 
+/// Normalize a URL from a browser's history, so that visits to the same page can be merged.
+/// Returns the URL to fetch, along with a key that is the same for duplicate entries, or None
+/// if the URL should not be indexed.
+pub fn normalize_history_url(
+    skip: &[impl AsRef<str>],
+    mut url_str: String,
+) -> Option<(String, String)> {
+    // If it doesn't parse here then there's no point in continuing.
+    let mut url = Url::parse(&url_str).ok()?;
+
+    // The history contains lots of entries with the same URL but different fragment.
+    // Clear it out so that we can dedupe.
+    if url.scheme() != "https" || url.fragment().is_some() {
+        url.set_scheme("https").ok();
+        url.set_fragment(None);
+        url_str = url.to_string();
+    }
+
+    if should_skip(skip, &url) {
+        return None;
+    }
+
+    let dedupe_path = url.path().trim_end_matches('/');
+    let dedupe_key = if dedupe_path != url.path() {
+        // Remove trailing slash, but only from the dedupe key, since removing it from
+        // the URL that we actually request may cause problems with some sites.
+        let mut dedupe_url = url.clone();
+        dedupe_url.set_path(dedupe_path);
+        dedupe_url.to_string()
+    } else {
+        url_str.clone()
+    };
+
+    Some((url_str, dedupe_key))
+}
+
 pub const HTML_PROCESS_VERSION: i32 = 1;
 
+/// Convert a fragment of HTML, like a feed entry or a social media post, to plain text.
+pub fn html_to_text(html: &str) -> String {
+    if !html.contains('<') && !html.contains('&') {
+        return html.trim().to_string();
+    }
+
+    html2text::from_read_with_decorator(
+        html.as_bytes(),
+        10_000,
+        html2text::render::text_renderer::TrivialDecorator::new(),
+    )
+    .trim()
+    .to_string()
+}
+
 pub fn extract_html_article(
     url: &str,
     raw_content: &[u8],
@@ -45,6 +102,20 @@ pub fn reprocess_html_article(item: &mut Item) -> Result<SourceScannerReadResult
 
     let raw_content = zstd::decode_all(raw_content.as_slice())
         .wrap_err_with(|| format!("{} - decompressing content", item.external_id))?;
+    if let Some(extractor) = extractors().detect(&raw_content) {
+        let extracted = extractor
+            .extract(&raw_content)
+            .wrap_err_with(|| format!("{} - extracting content", item.external_id))?;
+        let changed = item.content.as_deref() != Some(extracted.text.as_str());
+        extracted.apply(item);
+        item.process_version = HTML_PROCESS_VERSION;
+        return Ok(if changed {
+            SourceScannerReadResult::Found
+        } else {
+            SourceScannerReadResult::Unchanged
+        });
+    }
+
     let doc = extract_html_article(&item.external_id, &raw_content)
         .wrap_err_with(|| format!("{} - extracting content", item.external_id))?;
 
@@ -71,32 +142,183 @@ pub fn reprocess_html_article(item: &mut Item) -> Result<SourceScannerReadResult
     Ok(SourceScannerReadResult::Found)
 }
 
-pub fn fetch_html(
-    client: &Client,
+/// Read a web page found by a browser source. This skips pages that failed permanently before,
+/// pages that haven't been visited since they were last read, and pages that are waiting to be
+/// retried after a failure.
+pub fn read_web_page(
+    fetcher: &WebFetcher,
     existing: Option<&FoundItem>,
+    compare_strategy: ItemCompareStrategy,
     item: &mut Item,
 ) -> Result<SourceScannerReadResult, eyre::Report> {
-    let mut req_headers = reqwest::header::HeaderMap::with_capacity(2);
-    if let Some(mtime) = item.metadata.mtime {
-        let systime =
-            SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(mtime.unix_timestamp() as u64);
-        if let Ok(value) = HeaderValue::from_str(&httpdate::fmt_http_date(systime)) {
-            req_headers.insert(reqwest::header::IF_MODIFIED_SINCE, value);
+    let url = item.external_id.clone();
+    read_web_page_from(fetcher, &url, existing, compare_strategy, item)
+}
+
+/// Like [read_web_page], but for items whose external ID is not the URL of the page.
+pub fn read_web_page_from(
+    fetcher: &WebFetcher,
+    url: &str,
+    existing: Option<&FoundItem>,
+    compare_strategy: ItemCompareStrategy,
+    item: &mut Item,
+) -> Result<SourceScannerReadResult, eyre::Report> {
+    if compare_strategy != ItemCompareStrategy::Force {
+        if let Some(skipped) = existing.and_then(|e| e.skipped) {
+            if skipped.permanent() {
+                // We skipped it last time, so continue skipping it.
+                item.skipped = Some(skipped);
+                return Ok(SourceScannerReadResult::Unchanged);
+            }
+        }
+
+        let retrying = existing.map(|e| e.error_attempts > 0).unwrap_or(false);
+        if retrying {
+            let now = OffsetDateTime::now_utc().unix_timestamp();
+            let waiting = existing
+                .and_then(|e| e.next_retry_at)
+                .map(|retry_at| retry_at > now)
+                .unwrap_or(false);
+            if waiting {
+                return Ok(SourceScannerReadResult::Unchanged);
+            }
+        } else {
+            let fresh = existing
+                .and_then(|e| e.expires_at)
+                .map(|expires_at| expires_at > OffsetDateTime::now_utc().unix_timestamp())
+                .unwrap_or(false);
+            if fresh {
+                // The server said that the page won't change before this time.
+                return Ok(SourceScannerReadResult::Unchanged);
+            }
+
+            let existing_atime = existing.and_then(|e| e.last_accessed);
+            let new_atime = item.metadata.atime.map(|a| a.unix_timestamp());
+
+            let newer_access = new_atime
+                .zip(existing_atime)
+                .map(|(n, e)| n > e)
+                .unwrap_or(true);
+            if !newer_access {
+                return Ok(SourceScannerReadResult::Unchanged);
+            }
         }
     }
 
-    let etag = item
-        .hash
-        .as_ref()
-        .or_else(|| existing.map(|e| &e.hash))
-        .and_then(|e| HeaderValue::from_str(e).ok());
-    if let Some(etag) = etag {
+    let attempts = existing.map(|e| e.error_attempts).unwrap_or(0);
+    // When forcing a refetch, don't send the validators from last time so that the server always
+    // returns the full page.
+    let validators = existing.filter(|_| compare_strategy != ItemCompareStrategy::Force);
+    match fetch_html(fetcher, url, validators, item) {
+        Err(_) if is_last_attempt(attempts) => {
+            // This has failed too many times in a row, so stop trying.
+            item.skipped = Some(SkipReason::FetchError);
+            Ok(SourceScannerReadResult::Found)
+        }
+        result => result,
+    }
+}
+
+fn transient_fetch_error(message: String) -> eyre::Report {
+    TransientError {
+        kind: ItemErrorKind::Fetch,
+        message,
+    }
+    .into()
+}
+
+/// The longest that a page will be considered fresh, regardless of what the server says.
+const MAX_FRESHNESS: time::Duration = time::Duration::days(30);
+
+/// Figure out how long a response can be reused without checking with the server again, from
+/// its Cache-Control and Expires headers. A response that sat in a shared cache has an Age header,
+/// which counts against its max-age.
+fn freshness_expiry(headers: &http::HeaderMap, now: OffsetDateTime) -> Option<OffsetDateTime> {
+    let cache_control = headers
+        .get_all(http::header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|directive| directive.trim().to_ascii_lowercase())
+        .collect::<Vec<_>>();
+
+    if cache_control
+        .iter()
+        .any(|d| d == "no-store" || d == "no-cache")
+    {
+        return None;
+    }
+
+    let max_age = cache_control
+        .iter()
+        .filter_map(|d| d.strip_prefix("max-age="))
+        .find_map(|age| age.trim_matches('"').parse::<i64>().ok());
+
+    let expires = match max_age {
+        Some(max_age) => {
+            let age = headers
+                .get(http::header::AGE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<i64>().ok())
+                .unwrap_or(0);
+            now + time::Duration::seconds(max_age - age)
+        }
+        None => headers
+            .get(http::header::EXPIRES)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| httpdate::parse_http_date(v).ok())
+            .map(OffsetDateTime::from)?,
+    };
+
+    (expires > now).then(|| expires.min(now + MAX_FRESHNESS))
+}
+
+/// Fetch the web page at `page_url` into `item`. If `existing` is given, its ETag and
+/// Last-Modified values are sent so that the server can reply that the page hasn't changed, in
+/// which case this returns [SourceScannerReadResult::Revalidated].
+/// Failures that may succeed later, such as timeouts, dropped connections, rate limiting, and
+/// server errors, are returned as a [TransientError]. Other failures mark the item as skipped.
+pub fn fetch_html(
+    fetcher: &WebFetcher,
+    page_url: &str,
+    existing: Option<&FoundItem>,
+    item: &mut Item,
+) -> Result<SourceScannerReadResult, eyre::Report> {
+    // Only revalidate if the existing item actually has content to fall back on.
+    let existing = existing.filter(|e| e.skipped.is_none());
+
+    let mut req_headers = reqwest::header::HeaderMap::with_capacity(2);
+    if let Some(etag) = existing
+        .and_then(|e| e.etag.as_deref())
+        .and_then(|e| HeaderValue::from_str(e).ok())
+    {
         req_headers.insert(http::header::IF_NONE_MATCH, etag);
     }
 
-    let response = client.get(&item.external_id).send();
+    if let Some(last_modified) = existing
+        .and_then(|e| e.last_modified.as_deref())
+        .and_then(|e| HeaderValue::from_str(e).ok())
+    {
+        req_headers.insert(http::header::IF_MODIFIED_SINCE, last_modified);
+    }
+
+    let Ok(url) = Url::parse(page_url) else {
+        item.skipped = Some(SkipReason::FetchError);
+        return Ok(SourceScannerReadResult::Found);
+    };
+
+    if !fetcher.allowed(&url) {
+        item.skipped = Some(SkipReason::Disallowed);
+        return Ok(SourceScannerReadResult::Found);
+    }
+
+    let permit = fetcher.acquire(&url);
+    let response = fetcher.client().get(url).headers(req_headers).send();
     let response = match response {
         Ok(r) => r,
+        Err(e) if e.is_timeout() || e.is_connect() || e.is_request() || e.is_body() => {
+            return Err(transient_fetch_error(e.to_string()));
+        }
         Err(_) => {
             item.skipped = Some(SkipReason::FetchError);
             return Ok(SourceScannerReadResult::Found);
@@ -104,17 +326,37 @@ pub fn fetch_html(
     };
 
     let status = response.status();
+    if status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status.is_server_error()
+    {
+        return Err(transient_fetch_error(format!(
+            "{page_url} returned status {status}"
+        )));
+    }
 
-    let unchanged = matches!(status, StatusCode::NOT_MODIFIED);
-    if unchanged {
-        return Ok(SourceScannerReadResult::Unchanged);
+    let now = OffsetDateTime::now_utc();
+    if status == StatusCode::NOT_MODIFIED {
+        // Keep the old validators unless the server sent new ones, and update the freshness.
+        let headers = response.headers();
+        let header = |name: http::header::HeaderName| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from)
+        };
+        item.metadata.http_cache = Some(HttpCache {
+            etag: header(http::header::ETAG).or_else(|| existing.and_then(|e| e.etag.clone())),
+            last_modified: header(http::header::LAST_MODIFIED)
+                .or_else(|| existing.and_then(|e| e.last_modified.clone())),
+            expires_at: freshness_expiry(headers, now),
+        });
+        return Ok(SourceScannerReadResult::Revalidated);
     }
 
     let skip_reason = match status {
         StatusCode::FORBIDDEN | StatusCode::UNAUTHORIZED => Some(SkipReason::Unauthorized),
         StatusCode::NOT_FOUND => Some(SkipReason::NotFound),
-        // This is handled later, but we don't want it to fall in the redirect case.
-        StatusCode::NOT_MODIFIED => None,
         s if s.is_redirection() => Some(SkipReason::Redirected),
         s if s.is_client_error() || s.is_server_error() => Some(SkipReason::FetchError),
         _ => None,
@@ -136,26 +378,51 @@ pub fn fetch_html(
             }
         })
         .unwrap_or("text/plain");
-    item.hash = headers
-        .get(http::header::ETAG)
-        .and_then(|v| v.to_str().map(String::from).ok());
-    item.metadata.mtime = headers
+    let last_modified = headers
         .get(http::header::LAST_MODIFIED)
-        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.to_str().ok());
+    item.metadata.mtime = last_modified
         .and_then(|v| httpdate::parse_http_date(v).ok())
         .map(OffsetDateTime::from);
+    item.metadata.http_cache = Some(HttpCache {
+        etag: headers
+            .get(http::header::ETAG)
+            .and_then(|v| v.to_str().map(String::from).ok()),
+        last_modified: last_modified.map(String::from),
+        expires_at: freshness_expiry(headers, now),
+    });
+
+    if let Some(extractor) = extractors().for_mime_type(content_type) {
+        let raw_content = response
+            .bytes()
+            .map_err(|e| transient_fetch_error(e.to_string()))?;
+        drop(permit);
+
+        let (extracted, raw_compressed) = rayon::join(
+            || extractor.extract(&raw_content),
+            || zstd::encode_all(&raw_content[..], 3),
+        );
+
+        item.raw_content = Some(raw_compressed?);
+        extracted?.apply(item);
+        item.process_version = HTML_PROCESS_VERSION;
+        return Ok(SourceScannerReadResult::Found);
+    }
 
     if !content_type.starts_with("text/") {
-        // Save the item but with empty content. This leaves us with the title, which can be
-        // useful for PDFs, and also helps us to store the etag, modified
-        // date, etc. so that we aren't doing full fetches over and over again.
+        // Save the item but with empty content. This leaves us with the title, and also helps us
+        // to store the etag, modified date, etc. so that we aren't doing full fetches over and
+        // over again.
         item.content = Some(String::new());
         return Ok(SourceScannerReadResult::Found);
     }
 
     let is_html = content_type.starts_with("text/html");
 
-    let raw_content = response.text()?;
+    let raw_content = response
+        .text()
+        .map_err(|e| transient_fetch_error(e.to_string()))?;
+    drop(permit);
     if raw_content.is_empty() {
         item.skipped = Some(SkipReason::NoContent);
         return Ok(SourceScannerReadResult::Found);
@@ -163,7 +430,7 @@ pub fn fetch_html(
 
     if is_html {
         let (doc, raw_compressed) = rayon::join(
-            || extract_html_article(&item.external_id, raw_content.as_bytes()),
+            || extract_html_article(page_url, raw_content.as_bytes()),
             || zstd::encode_all(raw_content.as_bytes(), 3),
         );
 
@@ -180,3 +447,183 @@ pub fn fetch_html(
 
     Ok(SourceScannerReadResult::Found)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+    };
+
+    use http::{header, HeaderMap, HeaderValue};
+
+    use super::*;
+    use crate::{
+        sources::{timeline::test_found_item, web_fetcher::FetchConfig},
+        ItemMetadata,
+    };
+
+    fn headers(values: &[(header::HeaderName, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in values {
+            headers.append(name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn freshness_max_age() {
+        let now = OffsetDateTime::from_unix_timestamp(1673352000).unwrap();
+        let h = headers(&[(header::CACHE_CONTROL, "public, max-age=3600")]);
+        assert_eq!(
+            freshness_expiry(&h, now),
+            Some(now + time::Duration::hours(1))
+        );
+
+        // max-age takes precedence over Expires.
+        let h = headers(&[
+            (header::CACHE_CONTROL, "max-age=60"),
+            (header::EXPIRES, "Wed, 11 Jan 2023 12:00:00 GMT"),
+        ]);
+        assert_eq!(
+            freshness_expiry(&h, now),
+            Some(now + time::Duration::minutes(1))
+        );
+    }
+
+    #[test]
+    fn freshness_age() {
+        let now = OffsetDateTime::from_unix_timestamp(1673352000).unwrap();
+        let h = headers(&[
+            (header::CACHE_CONTROL, "max-age=3600"),
+            (header::AGE, "600"),
+        ]);
+        assert_eq!(
+            freshness_expiry(&h, now),
+            Some(now + time::Duration::minutes(50))
+        );
+
+        // Already stale by the time we got it.
+        let h = headers(&[
+            (header::CACHE_CONTROL, "max-age=3600"),
+            (header::AGE, "7200"),
+        ]);
+        assert_eq!(freshness_expiry(&h, now), None);
+    }
+
+    #[test]
+    fn freshness_expires() {
+        let now = OffsetDateTime::from_unix_timestamp(1673352000).unwrap();
+        let h = headers(&[(header::EXPIRES, "Tue, 10 Jan 2023 14:00:00 GMT")]);
+        assert_eq!(
+            freshness_expiry(&h, now),
+            Some(now + time::Duration::hours(2))
+        );
+
+        let h = headers(&[(header::EXPIRES, "Tue, 10 Jan 2023 11:00:00 GMT")]);
+        assert_eq!(freshness_expiry(&h, now), None);
+
+        let h = headers(&[(header::EXPIRES, "0")]);
+        assert_eq!(freshness_expiry(&h, now), None);
+    }
+
+    #[test]
+    fn freshness_limits() {
+        let now = OffsetDateTime::from_unix_timestamp(1673352000).unwrap();
+        let h = headers(&[(header::CACHE_CONTROL, "no-cache, max-age=3600")]);
+        assert_eq!(freshness_expiry(&h, now), None);
+
+        let h = headers(&[(header::CACHE_CONTROL, "No-Store")]);
+        assert_eq!(freshness_expiry(&h, now), None);
+
+        let h = headers(&[(header::CACHE_CONTROL, "max-age=31536000")]);
+        assert_eq!(freshness_expiry(&h, now), Some(now + MAX_FRESHNESS));
+    }
+
+    /// Serve a page that replies 304 Not Modified to requests with an If-None-Match header, and
+    /// return its URL.
+    fn serve_revalidated_page() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/page", listener.local_addr().unwrap());
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+
+                let mut reader = BufReader::new(&stream);
+                let mut conditional = false;
+                let mut line = String::new();
+                while reader.read_line(&mut line).map_or(false, |n| n > 2) {
+                    conditional |= line.to_ascii_lowercase().starts_with("if-none-match:");
+                    line.clear();
+                }
+
+                let (status, body) = if conditional {
+                    ("304 Not Modified", "")
+                } else {
+                    ("200 OK", "The page")
+                };
+
+                write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\nContent-Type: text/plain\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .ok();
+            }
+        });
+
+        url
+    }
+
+    #[test]
+    fn revalidate_after_error() {
+        let url = serve_revalidated_page();
+        let fetcher = WebFetcher::new(
+            FetchConfig {
+                host_delay_ms: 0,
+                respect_robots_txt: false,
+                ..Default::default()
+            },
+            false,
+        )
+        .unwrap();
+
+        // The last read failed, and it's now time to retry it.
+        let existing = FoundItem {
+            error_attempts: 1,
+            next_retry_at: Some(0),
+            etag: Some("\"v1\"".to_string()),
+            ..test_found_item("", "The page")
+        };
+        let mut item = Item {
+            id: -1,
+            source_id: 1,
+            external_id: url,
+            hash: None,
+            content: None,
+            raw_content: None,
+            process_version: 0,
+            metadata: ItemMetadata::default(),
+            skipped: None,
+        };
+
+        let result = read_web_page(
+            &fetcher,
+            Some(&existing),
+            ItemCompareStrategy::MTimeAndContent,
+            &mut item,
+        )
+        .unwrap();
+
+        // This counts as a successful read, so the pipeline clears the error.
+        assert!(matches!(result, SourceScannerReadResult::Revalidated));
+        assert!(item.skipped.is_none());
+        assert_eq!(
+            item.metadata.http_cache.and_then(|c| c.etag).as_deref(),
+            Some("\"v1\"")
+        );
+    }
+}
@@ -1,21 +1,28 @@
 use std::{
     fmt::Display,
+    ops::Range,
     sync::atomic::{AtomicU64, Ordering},
 };
 
+use sha2::{Digest, Sha256};
 use smallvec::SmallVec;
 
-use crate::{time_tracker::TimeTracker, Item, SkipReason};
+use crate::{cancel::CancellationToken, time_tracker::TimeTracker, Item, SkipReason};
 
 mod calculate_embeddings;
+pub mod events;
 mod import;
 mod match_existing_items;
 mod read_items;
+mod remove_missing_items;
 pub mod reprocess;
 mod update_db;
+mod watch;
 
-pub use import::scan_source;
+pub use events::{ScanEvent, ScanEvents, ScanStage};
+pub use import::{scan_source, ScanResult};
 pub use reprocess::reprocess_source;
+pub use watch::watch_source;
 
 use super::ItemCompareStrategy;
 
@@ -27,6 +34,10 @@ pub enum SourceScannerReadResult {
     /// The pipeline generally determines this, but the scanner may have additional methods,
     /// such as an HTTP request using caching headers to determine if the data is unchanged.
     Unchanged,
+    /// The scanner checked with the source that the data hasn't changed since it was last read,
+    /// for example with an HTTP request that returned 304 Not Modified. Unlike
+    /// [SourceScannerReadResult::Unchanged], this means that the item was read successfully.
+    Revalidated,
     /// Although the data showed up in the scanner, it failed to actually read it. For example,
     /// the scanner may be reading from the browser history but when it comes time to actually read
     /// the page, the server returns a 404 error.
@@ -34,8 +45,13 @@ pub enum SourceScannerReadResult {
 }
 
 pub trait SourceScanner: Send + Sync {
-    /// Scan the sources and output batches of found items
-    fn scan(&self, output: CountingVecSender<Item>) -> Result<(), eyre::Report>;
+    /// Scan the sources and output batches of found items. The scanner should stop early
+    /// if `cancel` is triggered.
+    fn scan(
+        &self,
+        output: CountingVecSender<Item>,
+        cancel: &CancellationToken,
+    ) -> Result<(), eyre::Report>;
     /// Read the full content of a single item. If the function reads the file and determines that
     /// it should not be indexed, it should return SourceScannerReadResult and the original item.
     fn read(
@@ -74,7 +90,16 @@ pub struct ScanItem {
 }
 
 pub const EMBEDDING_BATCH_SIZE: usize = 64;
-pub type EmbeddingsOutput = SmallVec<[(ScanItem, Option<Vec<f32>>); 1]>;
+/// How many items a scanner sends to the pipeline at once.
+pub const SCAN_BATCH_SIZE: usize = 64;
+pub type EmbeddingsOutput = SmallVec<[(ScanItem, Option<Vec<PassageEmbedding>>); 1]>;
+
+/// The embedding for a single passage of an item.
+pub struct PassageEmbedding {
+    /// The location of the passage in the item's content, in bytes.
+    pub range: Range<usize>,
+    pub embedding: Vec<f32>,
+}
 
 pub struct FoundItem {
     pub hash: String,
@@ -83,6 +108,50 @@ pub struct FoundItem {
     pub last_accessed: Option<i64>,
     pub skipped: Option<SkipReason>,
     pub has_embedding: bool,
+    /// How many times in a row reading this item has failed.
+    pub error_attempts: u32,
+    /// When to try reading the item again, if it failed before.
+    pub next_retry_at: Option<i64>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// When the stored content stops being fresh, according to the HTTP caching headers.
+    pub expires_at: Option<i64>,
+}
+
+/// Hash some text into a short ID, for items that the source doesn't give an ID to. Unlike the
+/// hashers in `std` and `ahash`, this gives the same result on every run.
+pub fn content_hash(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        // Keep ["ab", "c"] and ["a", "bc"] apart.
+        hasher.update([0]);
+    }
+
+    hasher.finalize()[..8]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// The `read` step for sources that fill in the content while scanning, such as exports that
+/// have to be read as a whole. Items without content couldn't be read.
+pub fn prefilled_read(
+    existing: Option<&FoundItem>,
+    compare_strategy: ItemCompareStrategy,
+    item: &Item,
+) -> SourceScannerReadResult {
+    if item.content.is_none() {
+        return SourceScannerReadResult::Omit;
+    }
+
+    let unchanged = compare_strategy != ItemCompareStrategy::Force
+        && existing.map_or(false, |e| Some(e.hash.as_str()) == item.hash.as_deref());
+    if unchanged {
+        return SourceScannerReadResult::Unchanged;
+    }
+
+    SourceScannerReadResult::Found
 }
 
 #[derive(Default)]
@@ -93,6 +162,12 @@ pub struct ScanStats {
     pub added: AtomicU64,
     pub changed: AtomicU64,
     pub unchanged: AtomicU64,
+    /// Items deleted because they were no longer present in the source.
+    pub removed: AtomicU64,
+    /// Items marked stale because they were no longer present in the source.
+    pub stale: AtomicU64,
+    /// Items that could not be read. These are recorded in the item error table.
+    pub errored: AtomicU64,
 
     pub reading: AtomicU64,
     pub embedding: AtomicU64,
@@ -101,6 +176,19 @@ pub struct ScanStats {
     pub read_time: TimeTracker,
     pub encode_time: TimeTracker,
     pub write_time: TimeTracker,
+
+    /// Events for each item as it moves through the pipeline.
+    pub events: ScanEvents,
+}
+
+impl ScanStats {
+    /// Create stats that publish events to an existing set of subscribers.
+    pub fn with_events(events: ScanEvents) -> ScanStats {
+        ScanStats {
+            events,
+            ..Default::default()
+        }
+    }
 }
 
 pub struct CountingVecSender<'a, T> {
@@ -1,48 +1,85 @@
-use std::sync::atomic::Ordering;
+use std::{ops::Range, sync::atomic::Ordering};
 
 use itertools::Itertools;
 use smallvec::{smallvec, SmallVec};
 
-use super::{EmbeddingsOutput, ScanItem, ScanItemState, ScanStats, EMBEDDING_BATCH_SIZE};
-use crate::model::Model;
+use super::{
+    EmbeddingsOutput, PassageEmbedding, ScanEvent, ScanItem, ScanItemState, ScanStats,
+    EMBEDDING_BATCH_SIZE,
+};
+use crate::{cancel::CancellationToken, model::Model};
+
+/// A passage waiting to be encoded.
+struct PendingPassage {
+    /// The index of the passage's item in the batch
+    item_index: usize,
+    range: Range<usize>,
+    text: String,
+}
 
 fn calculate_embeddings_batch(
     stats: &ScanStats,
     model: &Model,
     batch: &mut Vec<ScanItem>,
-    documents: &Vec<String>,
+    passages: &mut Vec<PendingPassage>,
 ) -> Result<EmbeddingsOutput, eyre::Report> {
     let _track = stats.encode_time.begin();
 
     stats
         .embedding
-        .fetch_add(documents.len() as u64, Ordering::Relaxed);
+        .fetch_add(batch.len() as u64, Ordering::Relaxed);
+
+    let mut embeddings: Vec<Vec<f32>> = Vec::with_capacity(passages.len());
+    for chunk in passages.chunks(EMBEDDING_BATCH_SIZE) {
+        let texts = chunk.iter().map(|p| p.text.as_str()).collect::<Vec<_>>();
+        let chunk_embeddings: Vec<Vec<f32>> = model.encode(&texts)?.into();
+        embeddings.extend(chunk_embeddings);
+    }
 
-    let embeddings: Vec<Vec<f32>> = model.encode(documents)?.into();
     stats
         .embedding
-        .fetch_sub(documents.len() as u64, Ordering::Relaxed);
+        .fetch_sub(batch.len() as u64, Ordering::Relaxed);
 
     stats
         .encoded
-        .fetch_add(embeddings.len() as u64, Ordering::Relaxed);
+        .fetch_add(batch.len() as u64, Ordering::Relaxed);
+
+    let mut item_passages = batch.iter().map(|_| Vec::new()).collect::<Vec<_>>();
+    for (passage, embedding) in passages.drain(..).zip(embeddings.into_iter()) {
+        item_passages[passage.item_index].push(PassageEmbedding {
+            range: passage.range,
+            embedding,
+        });
+    }
+
+    for (item, passages) in batch.iter().zip(item_passages.iter()) {
+        stats.events.emit(|| ScanEvent::Embedded {
+            source_id: item.item.source_id,
+            external_id: item.item.external_id.clone(),
+            passages: passages.len(),
+        });
+    }
 
     let output = batch
         .drain(..)
-        .zip(embeddings.into_iter().map(Some))
+        .zip(item_passages.into_iter().map(Some))
         .collect::<SmallVec<_>>();
 
     Ok(output)
 }
 
+/// Calculate the embeddings for each passage of the items that need them. When the scan is
+/// cancelled, any items still on their way through the pipeline are encoded without waiting for
+/// a full batch, so that the work done to read them is not lost.
 pub(super) fn calculate_embeddings(
     stats: &ScanStats,
     model: &Model,
+    cancel: &CancellationToken,
     rx: flume::Receiver<ScanItem>,
     tx: flume::Sender<EmbeddingsOutput>,
 ) -> Result<(), eyre::Report> {
     let mut batch = Vec::with_capacity(EMBEDDING_BATCH_SIZE);
-    let mut documents = Vec::with_capacity(EMBEDDING_BATCH_SIZE);
+    let mut passages = Vec::with_capacity(EMBEDDING_BATCH_SIZE);
 
     for item in rx {
         if matches!(item.state, ScanItemState::Unchanged | ScanItemState::Found)
@@ -52,47 +89,70 @@ pub(super) fn calculate_embeddings(
             continue;
         }
 
-        let document =
-            if item.item.metadata.name.is_none() && item.item.metadata.description.is_none() {
-                item.item.content.as_deref().map(|s| s.trim().to_string())
-            } else {
-                let document = [
-                    item.item.metadata.name.as_deref(),
-                    item.item.metadata.description.as_deref(),
-                    item.item.content.as_deref(),
-                ]
-                .into_iter()
-                .flatten()
-                .filter(|s| !s.trim().is_empty())
-                .join("\n");
-
-                if document.is_empty() {
-                    None
+        // The title, description, and tags are prepended to every passage so that each one
+        // carries some context about the item.
+        let tags = item
+            .item
+            .metadata
+            .tags
+            .as_ref()
+            .filter(|tags| !tags.is_empty())
+            .map(|tags| format!("Tags: {}", tags.join(", ")));
+        let header = [
+            item.item.metadata.name.as_deref(),
+            item.item.metadata.description.as_deref(),
+            tags.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|s| !s.trim().is_empty())
+        .join("\n");
+
+        let content = item.item.content.as_deref().unwrap_or_default();
+        let (header_len, content_passages) = model.passages(&header, content);
+        let header = header[..header_len].trim_end();
+
+        let item_index = batch.len();
+        if content_passages.is_empty() {
+            if header.is_empty() {
+                tx.send(smallvec![(item, None)])?;
+                continue;
+            }
+
+            passages.push(PendingPassage {
+                item_index,
+                range: 0..0,
+                text: header.to_string(),
+            });
+        } else {
+            for range in content_passages {
+                let passage = content[range.clone()].trim();
+                let text = if header.is_empty() {
+                    passage.to_string()
                 } else {
-                    Some(document)
-                }
-            };
-
-        let Some(document) = document else {
-            tx.send(smallvec![(item, None)])?;
-            continue;
-        };
+                    format!("{header}\n{passage}")
+                };
+
+                passages.push(PendingPassage {
+                    item_index,
+                    range,
+                    text,
+                });
+            }
+        }
 
-        documents.push(document);
         batch.push(item);
-        if batch.len() < EMBEDDING_BATCH_SIZE {
+        if passages.len() < EMBEDDING_BATCH_SIZE && !cancel.is_cancelled() {
             continue;
         }
 
-        let output = calculate_embeddings_batch(stats, model, &mut batch, &documents)?;
-        // batch was cleared by the above, so just clear `documents`
-        documents.clear();
-
+        // This clears both `batch` and `passages`.
+        let output = calculate_embeddings_batch(stats, model, &mut batch, &mut passages)?;
         tx.send(output)?;
     }
 
     if !batch.is_empty() {
-        let output = calculate_embeddings_batch(stats, model, &mut batch, &documents)?;
+        let output = calculate_embeddings_batch(stats, model, &mut batch, &mut passages)?;
         tx.send(output)?;
     }
 
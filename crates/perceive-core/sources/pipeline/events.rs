@@ -0,0 +1,131 @@
+use std::{io::Write, sync::Arc, time::Duration};
+
+use parking_lot::RwLock;
+use serde::Serialize;
+
+use crate::{sources::item_errors::ItemErrorKind, SkipReason};
+
+/// A stage of the scan pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanStage {
+    Scan,
+    Read,
+    Embed,
+    Write,
+}
+
+/// Something that happened to an item, or to the pipeline as a whole, during a scan.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ScanEvent {
+    /// The scanner found an item in the source.
+    Discovered { source_id: i64, external_id: String },
+    /// The item has not changed since the last scan.
+    Unchanged { source_id: i64, external_id: String },
+    /// The full content of the item was read.
+    Fetched { source_id: i64, external_id: String },
+    /// The item was read, but won't be indexed.
+    Skipped {
+        source_id: i64,
+        external_id: String,
+        reason: SkipReason,
+    },
+    /// The embeddings for the item's passages were calculated.
+    Embedded {
+        source_id: i64,
+        external_id: String,
+        passages: usize,
+    },
+    /// The item was saved to the database.
+    Written {
+        source_id: i64,
+        external_id: String,
+        /// True if the item was not in the database before.
+        added: bool,
+    },
+    /// The item could not be read.
+    Errored {
+        source_id: i64,
+        external_id: String,
+        kind: ItemErrorKind,
+        message: String,
+    },
+    /// A stage of the pipeline finished. `duration_ms` is the total time that the stage spent
+    /// working, across all of its threads.
+    StageFinished {
+        source_id: i64,
+        stage: ScanStage,
+        duration_ms: u64,
+    },
+}
+
+impl ScanEvent {
+    pub(crate) fn stage_finished(source_id: i64, stage: ScanStage, duration: Duration) -> Self {
+        ScanEvent::StageFinished {
+            source_id,
+            stage,
+            duration_ms: duration.as_millis() as u64,
+        }
+    }
+}
+
+/// Publishes [ScanEvent]s to any number of subscribers. Cloning this returns a handle to the
+/// same set of subscribers.
+#[derive(Clone, Default)]
+pub struct ScanEvents {
+    subscribers: Arc<RwLock<Vec<flume::Sender<ScanEvent>>>>,
+}
+
+impl ScanEvents {
+    pub fn new() -> ScanEvents {
+        ScanEvents::default()
+    }
+
+    /// Receive every event published from now on. The subscription ends when the returned
+    /// receiver is dropped.
+    pub fn subscribe(&self) -> flume::Receiver<ScanEvent> {
+        let (tx, rx) = flume::unbounded();
+        self.subscribers.write().push(tx);
+        rx
+    }
+
+    /// Drop all the subscribers, which ends any loops that are receiving events.
+    pub fn close(&self) {
+        self.subscribers.write().clear();
+    }
+
+    /// Send an event to all the subscribers. The event is only built if someone is listening.
+    pub(crate) fn emit(&self, event: impl FnOnce() -> ScanEvent) {
+        let subscribers = self.subscribers.read();
+        if subscribers.is_empty() {
+            return;
+        }
+
+        let event = event();
+        let mut disconnected = false;
+        for subscriber in subscribers.iter() {
+            disconnected |= subscriber.send(event.clone()).is_err();
+        }
+        drop(subscribers);
+
+        if disconnected {
+            self.subscribers.write().retain(|s| !s.is_disconnected());
+        }
+    }
+}
+
+/// Write each event as a line of JSON until the subscription is closed. This is meant to be run
+/// on its own thread.
+pub fn write_event_log(
+    events: flume::Receiver<ScanEvent>,
+    mut output: impl Write,
+) -> Result<(), eyre::Report> {
+    for event in events {
+        serde_json::to_writer(&mut output, &event)?;
+        output.write_all(b"\n")?;
+    }
+
+    output.flush()?;
+    Ok(())
+}
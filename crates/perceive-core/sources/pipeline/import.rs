@@ -1,14 +1,37 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use super::{
     calculate_embeddings::calculate_embeddings, match_existing_items::match_to_existing_items,
-    read_items::read_items, update_db::update_db, CountingVecSender, ItemCompareStrategy,
-    ScanStats, EMBEDDING_BATCH_SIZE,
+    read_items::read_items, remove_missing_items::remove_missing_items, update_db::update_db,
+    CountingVecSender, ItemCompareStrategy, ScanEvent, ScanStage, ScanStats, EMBEDDING_BATCH_SIZE,
 };
 use crate::{
+    cancel::CancellationToken,
     db::Database,
     model::Model,
     sources::{pipeline::log_thread_error, Source},
+    time_tracker::TimeTracker,
 };
 
+/// The outcome of [scan_source].
+#[derive(Debug, Default)]
+pub struct ScanResult {
+    /// True if every stage of the pipeline finished without an error and the scan was not
+    /// cancelled.
+    pub completed: bool,
+    /// True if the scan was cancelled before it finished.
+    pub cancelled: bool,
+    /// The IDs of items that were deleted because they are no longer present in the source.
+    pub removed_items: Vec<i64>,
+}
+
+/// Scan a source and index its items at the source's current `index_version`.
+///
+/// Items that are already at that version were handled by an earlier run of the scan that was
+/// interrupted, and are not read again, unless the compare strategy is
+/// [ItemCompareStrategy::Force]. When `cancel` is triggered, the pipeline stops reading new
+/// items but still writes the items that are already in progress.
+#[allow(clippy::too_many_arguments)]
 pub fn scan_source(
     times: &ScanStats,
     database: &Database,
@@ -17,11 +40,25 @@ pub fn scan_source(
     model_version: u32,
     source: &Source,
     override_compare_strategy: Option<ItemCompareStrategy>,
-) -> Result<(), eyre::Report> {
+    cancel: &CancellationToken,
+) -> Result<ScanResult, eyre::Report> {
     let scanner = source.create_scanner()?;
     let compare_strategy = override_compare_strategy.unwrap_or(source.compare_strategy);
+    // A forced scan has to read every item again, including those that an interrupted run already
+    // wrote at this version.
+    let resume_version =
+        (compare_strategy != ItemCompareStrategy::Force).then_some(source.index_version);
+    let stage_finished = |stage, tracker: &TimeTracker| {
+        times
+            .events
+            .emit(|| ScanEvent::stage_finished(source.id, stage, tracker.total()));
+    };
 
-    std::thread::scope(|scope| {
+    const READ_PARALLELISM: usize = 8;
+    // The read stage is finished once the last of its threads exits.
+    let readers_running = AtomicUsize::new(READ_PARALLELISM);
+
+    let errored = std::thread::scope(|scope| {
         let (item_tx, item_rx) = flume::unbounded();
         let (matched_tx, matched_rx) = flume::bounded(256);
         let (with_content_tx, with_content_rx) = flume::bounded(EMBEDDING_BATCH_SIZE);
@@ -35,8 +72,12 @@ pub fn scan_source(
         // Make a pipeline with the following stages:
         // - Scan the file system and send out batches of items
         let scan_task = scope.spawn(|| {
-            let _track = times.scan_time.begin();
-            scanner.scan(item_tx)
+            let result = {
+                let _track = times.scan_time.begin();
+                scanner.scan(item_tx, cancel)
+            };
+            stage_finished(ScanStage::Scan, &times.scan_time);
+            result
         });
 
         // STAGE 2
@@ -44,11 +85,13 @@ pub fn scan_source(
         // - If one was found, do a preliminary match (i.e. by mtime) to see if it can be skipped.
         let db_lookup_task = scope.spawn(|| {
             match_to_existing_items(
+                times,
                 database,
                 model_id,
                 model_version,
                 source.id,
                 compare_strategy,
+                resume_version,
                 item_rx,
                 matched_tx,
             )
@@ -58,38 +101,50 @@ pub fn scan_source(
         // - Read the content of the file
         // - Match against the content, if applicable
         // - If the content did not change, skip the item.
-        const READ_PARALLELISM: usize = 8;
         let read_tasks = itertools::repeat_n((matched_rx, with_content_tx), READ_PARALLELISM)
             .into_iter()
             .map(|(matched_rx, with_content_tx)| {
                 scope.spawn(|| {
-                    read_items(
+                    let result = read_items(
                         times,
+                        database,
                         compare_strategy,
                         scanner.as_ref(),
+                        cancel,
                         matched_rx,
                         with_content_tx,
-                    )
+                    );
+
+                    if readers_running.fetch_sub(1, Ordering::Relaxed) == 1 {
+                        stage_finished(ScanStage::Read, &times.read_time);
+                    }
+                    result
                 })
             })
             .collect::<Vec<_>>();
 
         // STAGE 4
         // - Calculate embeddings for the items in this batch that we are keeping.
-        let embed_task =
-            scope.spawn(|| calculate_embeddings(times, model, with_content_rx, with_embeddings_tx));
+        let embed_task = scope.spawn(|| {
+            let result =
+                calculate_embeddings(times, model, cancel, with_content_rx, with_embeddings_tx);
+            stage_finished(ScanStage::Embed, &times.encode_time);
+            result
+        });
 
         // STAGE 5
         // - Update the database for items that will be kept
         let write_db_task = scope.spawn(|| {
-            update_db(
+            let result = update_db(
                 model_id,
                 model_version,
                 times,
                 database,
                 source.index_version,
                 with_embeddings_rx,
-            )
+            );
+            stage_finished(ScanStage::Write, &times.write_time);
+            result
         });
 
         // TODO - handle errors better
@@ -105,12 +160,29 @@ pub fn scan_source(
         if errored {
             println!("Scanning failed");
         }
+
+        errored
     });
 
-    // AFTER PIPELINE TODO
-    // - For source types that require it, delete any items for which the
-    //   index_version did not get updated to the latest vesion. This means
-    //   that they were not found in the scan.
+    if errored {
+        // The scan may not have seen everything, so we can't tell which items were
+        // actually removed from the source.
+        return Ok(ScanResult::default());
+    }
+
+    if cancel.is_cancelled() {
+        // Same as above, and the items we did write will be skipped when the scan resumes.
+        return Ok(ScanResult {
+            cancelled: true,
+            ..Default::default()
+        });
+    }
+
+    let removed_items = remove_missing_items(times, database, source)?;
 
-    Ok(())
+    Ok(ScanResult {
+        completed: true,
+        cancelled: false,
+        removed_items,
+    })
 }
@@ -3,15 +3,22 @@ use std::rc::Rc;
 use ahash::HashMap;
 use rusqlite::params;
 
-use super::{FoundItem, ScanItem, ScanItemState};
+use super::{FoundItem, ScanEvent, ScanItem, ScanItemState, ScanStats};
 use crate::{db::Database, sources::ItemCompareStrategy, Item, SkipReason};
 
+/// Match scanned items against the items already in the database.
+///
+/// When `resume_version` is set, items that were already written at that index version are
+/// passed through as unchanged. This lets a scan that was interrupted pick up where it left off.
+#[allow(clippy::too_many_arguments)]
 pub fn match_to_existing_items(
+    stats: &ScanStats,
     db: &Database,
     model_id: u32,
     model_version: u32,
     source_id: i64,
     compare_strategy: ItemCompareStrategy,
+    resume_version: Option<i64>,
     rx: flume::Receiver<Vec<Item>>,
     tx: flume::Sender<ScanItem>,
 ) -> Result<(), eyre::Report> {
@@ -19,10 +26,12 @@ pub fn match_to_existing_items(
 
     let mut stmt = conn.prepare_cached(
         r##"
-        SELECT external_id, id, hash, modified, last_accessed, skipped, content, ie.item_id IS NOT NULL AS has_embedding
+        SELECT items.external_id, id, hash, modified, last_accessed, skipped, content, ie.item_id IS NOT NULL AS has_embedding,
+            version, COALESCE(ier.attempts, 0), ier.next_retry_at, etag, last_modified, expires_at
         FROM items
         LEFT JOIN item_embeddings ie ON ie.item_id = items.id AND model_id = ? AND model_version = ?
-        WHERE source_id = ? AND external_id IN rarray(?)
+        LEFT JOIN item_errors ier ON ier.source_id = items.source_id AND ier.external_id = items.external_id
+        WHERE items.source_id = ? AND items.external_id IN rarray(?)
     "##,
     )?;
 
@@ -43,6 +52,7 @@ pub fn match_to_existing_items(
                         row.get::<_, String>(0)?,
                         (
                             row.get::<_, i64>(1)?,
+                            row.get::<_, i64>(8)?,
                             FoundItem {
                                 hash: row.get::<_, String>(2)?,
                                 modified: row.get::<_, Option<i64>>(3)?,
@@ -58,6 +68,11 @@ pub fn match_to_existing_items(
                                     String::new()
                                 },
                                 has_embedding: row.get(7)?,
+                                error_attempts: row.get(9)?,
+                                next_retry_at: row.get(10)?,
+                                etag: row.get(11)?,
+                                last_modified: row.get(12)?,
+                                expires_at: row.get(13)?,
                             },
                         ),
                     ))
@@ -67,9 +82,19 @@ pub fn match_to_existing_items(
             .collect::<Result<HashMap<_, _>, _>>()?;
 
         for mut item in batch {
+            stats.events.emit(|| ScanEvent::Discovered {
+                source_id,
+                external_id: item.external_id.clone(),
+            });
+
             let state = found
                 .remove(&item.external_id)
-                .map(|(id, found)| {
+                .map(|(id, version, found)| {
+                    if resume_version == Some(version) {
+                        // This item was already handled by an earlier run of this scan.
+                        return (id, Some(found), ScanItemState::Unchanged);
+                    }
+
                     let same_time = item
                         .metadata
                         .mtime
@@ -1,12 +1,24 @@
 use std::sync::atomic::Ordering;
 
-use super::{ScanItem, ScanItemState, ScanStats, SourceScanner, SourceScannerReadResult};
-use crate::sources::ItemCompareStrategy;
+use super::{
+    ScanEvent, ScanItem, ScanItemState, ScanStats, SourceScanner, SourceScannerReadResult,
+};
+use crate::{
+    cancel::CancellationToken,
+    db::Database,
+    sources::{
+        item_errors::{clear_item_error, record_item_error, ItemErrorKind},
+        ItemCompareStrategy,
+    },
+    SkipReason,
+};
 
 pub fn read_items(
     stats: &ScanStats,
+    database: &Database,
     compare_strategy: ItemCompareStrategy,
     scanner: &dyn SourceScanner,
+    cancel: &CancellationToken,
     rx: flume::Receiver<ScanItem>,
     tx: flume::Sender<ScanItem>,
 ) -> Result<(), eyre::Report> {
@@ -18,6 +30,12 @@ pub fn read_items(
             continue;
         }
 
+        if cancel.is_cancelled() {
+            // Keep draining the channel so that the earlier stages can finish, but don't
+            // start any new reads. These items will be picked up by the next scan.
+            continue;
+        }
+
         let ScanItem {
             mut item,
             existing,
@@ -31,15 +49,68 @@ pub fn read_items(
         stats.reading.fetch_sub(1, Ordering::Relaxed);
         stats.fetched.fetch_add(1, Ordering::Relaxed);
 
+        if let Ok(SourceScannerReadResult::Found) = read_result {
+            stats.events.emit(|| match item.skipped {
+                Some(reason) => ScanEvent::Skipped {
+                    source_id: item.source_id,
+                    external_id: external_id.clone(),
+                    reason,
+                },
+                None => ScanEvent::Fetched {
+                    source_id: item.source_id,
+                    external_id: external_id.clone(),
+                },
+            });
+        }
+
+        // An unchanged result may just mean that the scanner is waiting to retry the item, so
+        // only clear the error once the item has actually been read.
+        let read_succeeded = match read_result {
+            Ok(SourceScannerReadResult::Found) => item.skipped.is_none(),
+            Ok(SourceScannerReadResult::Revalidated | SourceScannerReadResult::Omit) => true,
+            _ => false,
+        };
+        if read_succeeded && existing.as_ref().map_or(false, |e| e.error_attempts > 0) {
+            clear_item_error(database, item.source_id, &external_id)?;
+        }
+
         let state = match read_result {
             Ok(SourceScannerReadResult::Found) => state,
-            Ok(SourceScannerReadResult::Unchanged) => ScanItemState::Unchanged,
+            Ok(SourceScannerReadResult::Unchanged | SourceScannerReadResult::Revalidated) => {
+                ScanItemState::Unchanged
+            }
             Ok(SourceScannerReadResult::Omit) => {
                 continue;
             }
             Err(e) => {
-                // TODO - better error handling
-                eprintln!("{}: {e}", external_id);
+                let kind = ItemErrorKind::from_error(&e);
+                let message = format!("{e:#}");
+                record_item_error(database, item.source_id, &external_id, kind, &message)?;
+                stats.errored.fetch_add(1, Ordering::Relaxed);
+                stats.events.emit(|| ScanEvent::Errored {
+                    source_id: item.source_id,
+                    external_id: external_id.clone(),
+                    kind,
+                    message,
+                });
+
+                if existing.is_some() {
+                    // A failed read doesn't mean that the item is gone from the source, so pass
+                    // along the existing item to keep it from being removed after the scan.
+                    tx.send(ScanItem {
+                        state: ScanItemState::Unchanged,
+                        existing,
+                        item,
+                    })?;
+                } else {
+                    // Save a placeholder so that the scanner can see the retry schedule next time.
+                    item.skipped = Some(SkipReason::Retrying);
+                    tx.send(ScanItem {
+                        state: ScanItemState::New,
+                        existing,
+                        item,
+                    })?;
+                }
                 continue;
             }
         };
@@ -49,7 +120,10 @@ pub fn read_items(
             (ScanItemState::New | ScanItemState::Unchanged | ScanItemState::Changed, _) => state,
             (ScanItemState::Found, None) => ScanItemState::New,
             (ScanItemState::Found, Some(existing)) => {
-                if compare_content
+                if item.skipped != existing.skipped {
+                    // The item started or stopped being skipped.
+                    ScanItemState::Changed
+                } else if compare_content
                     && existing.content != item.content.as_deref().unwrap_or_default()
                 {
                     ScanItemState::Changed
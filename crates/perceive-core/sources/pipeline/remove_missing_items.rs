@@ -0,0 +1,77 @@
+use std::sync::atomic::Ordering;
+
+use rusqlite::params;
+use time::OffsetDateTime;
+
+use super::ScanStats;
+use crate::{
+    db::Database,
+    sources::{RemovedItemPolicy, Source},
+};
+
+/// Handle the items that were not seen during the latest scan, according to the source's
+/// [RemovedItemPolicy]. Every item found by the scan has its version updated to the source's
+/// `index_version`, so anything older than that has disappeared from the source.
+///
+/// Returns the IDs of the items that were deleted.
+pub(super) fn remove_missing_items(
+    stats: &ScanStats,
+    database: &Database,
+    source: &Source,
+) -> Result<Vec<i64>, eyre::Report> {
+    let mut conn = database.write_conn.lock();
+    let tx = conn.transaction()?;
+
+    let removed = match source.config.removed_item_policy() {
+        RemovedItemPolicy::Delete => {
+            // Embeddings and tags are removed by the foreign key cascade.
+            let mut stmt = tx.prepare_cached(
+                "DELETE FROM items WHERE source_id = ? AND version < ? RETURNING id",
+            )?;
+
+            let ids = stmt
+                .query_map(params![source.id, source.index_version], |row| row.get(0))?
+                .collect::<Result<Vec<i64>, _>>()?;
+
+            stats.removed.fetch_add(ids.len() as u64, Ordering::Relaxed);
+            ids
+        }
+        RemovedItemPolicy::MarkStale => {
+            // Items that were stale but showed up again.
+            let mut found_stmt = tx.prepare_cached(
+                r##"UPDATE items SET stale_at = NULL
+                WHERE source_id = ? AND version = ? AND stale_at IS NOT NULL"##,
+            )?;
+            found_stmt.execute(params![source.id, source.index_version])?;
+
+            let mut stale_stmt = tx.prepare_cached(
+                r##"UPDATE items SET stale_at = ?
+                WHERE source_id = ? AND version < ? AND stale_at IS NULL"##,
+            )?;
+
+            let now = OffsetDateTime::now_utc().unix_timestamp();
+            let marked = stale_stmt.execute(params![now, source.id, source.index_version])?;
+
+            stats.stale.fetch_add(marked as u64, Ordering::Relaxed);
+            Vec::new()
+        }
+    };
+
+    // Errors for items that are gone from the source. Anything that failed during this scan has a
+    // `last_seen` after the scan started.
+    let mut errors_stmt = tx.prepare_cached(
+        r##"DELETE FROM item_errors
+        WHERE source_id = ? AND last_seen < ?
+            AND external_id NOT IN (SELECT external_id FROM items WHERE source_id = ?)"##,
+    )?;
+    errors_stmt.execute(params![
+        source.id,
+        source.last_indexed.unix_timestamp(),
+        source.id
+    ])?;
+    drop(errors_stmt);
+
+    tx.commit()?;
+
+    Ok(removed)
+}
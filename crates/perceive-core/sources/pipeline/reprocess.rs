@@ -10,6 +10,7 @@ use super::{
     wrap_thread, ScanItem, ScanItemState, ScanStats, SourceScannerReadResult, EMBEDDING_BATCH_SIZE,
 };
 use crate::{
+    cancel::CancellationToken,
     db::{deserialize_item_row, Database, ITEM_COLUMNS},
     model::Model,
     sources::Source,
@@ -46,6 +47,7 @@ fn read_rows(
 fn reprocess(
     times: &ScanStats,
     source: &Source,
+    cancel: &CancellationToken,
     db_items_rx: flume::Receiver<Vec<Item>>,
     processed_tx: flume::Sender<ScanItem>,
 ) -> Result<()> {
@@ -57,6 +59,11 @@ fn reprocess(
 
     pool.install(|| {
         for batch in db_items_rx {
+            if cancel.is_cancelled() {
+                // Drain the rest of the rows without processing them.
+                continue;
+            }
+
             batch.into_par_iter().try_for_each(|mut item| {
                 times
                     .reading
@@ -89,6 +96,7 @@ pub fn reprocess_source(
     model_id: u32,
     model_version: u32,
     source: &Source,
+    cancel: &CancellationToken,
 ) -> Result<(), eyre::Report> {
     std::thread::scope(|scope| {
         let (db_items_tx, db_items_rx) = flume::unbounded();
@@ -101,12 +109,12 @@ pub fn reprocess_source(
         let process_task = scope.spawn(move || {
             wrap_thread(
                 "reprocess",
-                reprocess(times, source, db_items_rx, processed_tx),
+                reprocess(times, source, cancel, db_items_rx, processed_tx),
             )
         });
 
-        let embed_task =
-            scope.spawn(|| calculate_embeddings(times, model, processed_rx, with_embeddings_tx));
+        let embed_task = scope
+            .spawn(|| calculate_embeddings(times, model, cancel, processed_rx, with_embeddings_tx));
 
         let write_db_task = scope.spawn(|| {
             wrap_thread(
@@ -2,8 +2,10 @@ use std::sync::atomic::Ordering;
 
 use rusqlite::{named_params, params};
 
-use super::{EmbeddingsOutput, ScanItemState, ScanStats};
-use crate::{db::Database, search::serialize_embedding};
+use super::{EmbeddingsOutput, ScanEvent, ScanItemState, ScanStats};
+use crate::{
+    db::Database, links::set_item_links, search::serialize_embedding, tags::set_item_tags,
+};
 
 pub fn update_db(
     model_id: u32,
@@ -35,7 +37,7 @@ pub fn update_db(
                 SET version=:version, hash=:hash, content=:content, raw_content=:raw_content,
                     process_version=:process_version,
                     name=:name, author=:author, description=:description,
-                    modified=:modified, last_accessed=:last_accessed,
+                    modified=:modified, last_accessed=:last_accessed, created=:created,
                     skipped=:skipped
                 WHERE id=:id
                 "##,
@@ -45,9 +47,9 @@ pub fn update_db(
                 r##"
                 INSERT INTO items (source_id, external_id, version, hash, content,
                     raw_content, process_version, name, author,
-                    description, modified, last_accessed, skipped)
+                    description, modified, last_accessed, created, skipped)
                 VALUES (:source_id, :external_id, :version, :hash, :content, :raw_content, :process_version,
-                    :name, :author, :description, :modified, :last_accessed, :skipped);
+                    :name, :author, :description, :modified, :last_accessed, :created, :skipped);
                 "##,
             )?;
 
@@ -60,6 +62,28 @@ pub fn update_db(
                             embedding=EXCLUDED.embedding"##,
             )?;
 
+            let mut http_cache_stmt = tx.prepare_cached(
+                "UPDATE items SET etag = ?, last_modified = ?, expires_at = ? WHERE id = ?",
+            )?;
+
+            // Items that are new to the items table may have failed to read in an earlier scan.
+            let mut clear_error_stmt = tx.prepare_cached(
+                "DELETE FROM item_errors WHERE source_id = ? AND external_id = ?",
+            )?;
+
+            let mut clear_passages_stmt = tx.prepare_cached(
+                r##"DELETE FROM item_passages
+                    WHERE item_id = ? AND model_id = ? AND model_version = ?"##,
+            )?;
+
+            let mut passage_stmt = tx.prepare_cached(
+                r##"INSERT INTO item_passages
+                    (item_id, model_id, model_version, passage_index, start_offset, end_offset,
+                        item_index_version, embedding)
+                    VALUES (:id, :model_id, :model_version, :passage_index, :start_offset, :end_offset,
+                        :version, :embedding)"##,
+            )?;
+
             for (item, embedding) in &batch {
                 let found_item_id = item.item.id;
                 let item_id = match &item.state {
@@ -85,6 +109,7 @@ pub fn update_db(
                             ":description": item.item.metadata.description.as_deref(),
                             ":modified": item.item.metadata.mtime.map(|t| t.unix_timestamp()),
                             ":last_accessed": item.item.metadata.atime.map(|t| t.unix_timestamp()),
+                            ":created": item.item.metadata.created.map(|t| t.unix_timestamp()),
                             ":skipped": item.item.skipped.map(|s| s.to_string()),
                         })?;
 
@@ -105,25 +130,90 @@ pub fn update_db(
                             ":description": item.item.metadata.description.as_deref(),
                             ":modified": item.item.metadata.mtime.map(|t| t.unix_timestamp()),
                             ":last_accessed": item.item.metadata.atime.map(|t| t.unix_timestamp()),
+                            ":created": item.item.metadata.created.map(|t| t.unix_timestamp()),
                             ":skipped": item.item.skipped.map(|s| s.to_string()),
                         })?;
 
                         let row_id = tx.last_insert_rowid();
+                        if item.item.skipped.is_none() {
+                            clear_error_stmt
+                                .execute(params![item.item.source_id, item.item.external_id])?;
+                        }
 
                         new += 1;
                         row_id
                     }
                 };
 
-                if let Some(embedding) = embedding {
-                    let bytes_vec = serialize_embedding(embedding);
-                    embedding_stmt.execute(named_params! {
-                        ":embedding": &bytes_vec,
-                        ":version": index_version,
-                        ":id": item_id,
-                        ":model_id": model_id,
-                        ":model_version": model_version,
-                    })?;
+                stats.events.emit(|| {
+                    let source_id = item.item.source_id;
+                    let external_id = item.item.external_id.clone();
+                    match item.state {
+                        ScanItemState::Unchanged => ScanEvent::Unchanged {
+                            source_id,
+                            external_id,
+                        },
+                        ScanItemState::New => ScanEvent::Written {
+                            source_id,
+                            external_id,
+                            added: true,
+                        },
+                        ScanItemState::Found | ScanItemState::Changed => ScanEvent::Written {
+                            source_id,
+                            external_id,
+                            added: false,
+                        },
+                    }
+                });
+
+                if let Some(cache) = item.item.metadata.http_cache.as_ref() {
+                    http_cache_stmt.execute(params![
+                        cache.etag,
+                        cache.last_modified,
+                        cache.expires_at.map(|t| t.unix_timestamp()),
+                        item_id,
+                    ])?;
+                }
+
+                if let Some(tags) = item.item.metadata.tags.as_ref() {
+                    set_item_tags(&tx, item_id, tags)?;
+                }
+
+                if let Some(links) = item.item.metadata.links.as_ref() {
+                    set_item_links(&tx, item_id, links)?;
+                }
+
+                if let Some(passages) = embedding {
+                    // The first passage also serves as the embedding for the item as a whole.
+                    if let Some(first) = passages.first() {
+                        let bytes_vec = serialize_embedding(&first.embedding);
+                        embedding_stmt.execute(named_params! {
+                            ":embedding": &bytes_vec,
+                            ":version": index_version,
+                            ":id": item_id,
+                            ":model_id": model_id,
+                            ":model_version": model_version,
+                        })?;
+                    }
+
+                    clear_passages_stmt.execute(params![item_id, model_id, model_version])?;
+                    for (passage_index, passage) in passages.iter().enumerate() {
+                        let bytes_vec = serialize_embedding(&passage.embedding);
+                        passage_stmt.execute(named_params! {
+                            ":id": item_id,
+                            ":model_id": model_id,
+                            ":model_version": model_version,
+                            ":passage_index": passage_index,
+                            ":start_offset": passage.range.start,
+                            ":end_offset": passage.range.end,
+                            ":version": index_version,
+                            ":embedding": &bytes_vec,
+                        })?;
+                    }
+                } else if !matches!(item.state, ScanItemState::Unchanged) {
+                    // The item was skipped or has nothing left to encode, so it shouldn't keep
+                    // matching searches with its old passages.
+                    clear_passages_stmt.execute(params![item_id, model_id, model_version])?;
                 }
             }
         }
@@ -0,0 +1,513 @@
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::atomic::Ordering,
+    time::Duration,
+};
+
+use ahash::{HashMap, HashSet};
+use eyre::{eyre, Context};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use notify::{EventKind, RecursiveMode, Watcher};
+use rusqlite::params;
+
+use super::{
+    calculate_embeddings::calculate_embeddings, log_thread_error,
+    match_existing_items::match_to_existing_items, read_items::read_items, update_db::update_db,
+    CountingVecSender, ScanEvent, ScanStats,
+};
+use crate::{
+    cancel::CancellationToken,
+    db::Database,
+    model::Model,
+    search::Searcher,
+    sources::{
+        fs::{file_item, FileScanner},
+        item_errors::{record_item_error, ItemErrorKind},
+        Source, SourceConfig,
+    },
+    Item,
+};
+
+/// How long to wait for the filesystem to be quiet before processing a set of changes.
+const DEBOUNCE_TIME: Duration = Duration::from_millis(500);
+
+/// Watch a filesystem source for changes, and update the index and the searcher as
+/// files are created, modified, renamed, and deleted. This runs until `cancel` is triggered or
+/// the watcher shuts down.
+///
+/// `get_searcher` is called for each set of changes, so that the caller can swap in a rebuilt
+/// searcher while the watch runs. When it returns `None`, only the database is updated.
+///
+/// Failures to read or index the changed files are recorded against those files, and the watch
+/// continues. Only a failure of the watcher itself ends the watch with an error.
+#[allow(clippy::too_many_arguments)]
+pub fn watch_source<S: Deref<Target = Searcher>>(
+    stats: &ScanStats,
+    database: &Database,
+    model: &Model,
+    model_id: u32,
+    model_version: u32,
+    source: &Source,
+    get_searcher: impl Fn() -> Option<S>,
+    cancel: &CancellationToken,
+) -> Result<(), eyre::Report> {
+    let SourceConfig::Fs(config) = &source.config else {
+        return Err(eyre!("Only filesystem sources can be watched"));
+    };
+
+    let scanner = FileScanner {
+        source_id: source.id,
+        location: source.location.clone(),
+        config: config.clone(),
+    };
+    let glob = scanner.build_globset()?;
+
+    // The events use the canonical path of each file, so this is needed to map them back to paths
+    // under the source's location, which is how the scan identifies the files.
+    let root = std::fs::canonicalize(&source.location)
+        .wrap_err_with(|| eyre!("Reading {}", source.location))?;
+
+    let (event_tx, event_rx) = flume::unbounded();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        event_tx.send(event).ok();
+    })?;
+    watcher.watch(&root, RecursiveMode::Recursive)?;
+
+    let location = Path::new(&source.location);
+    let mut pending = HashSet::default();
+    while !cancel.is_cancelled() {
+        match event_rx.recv_timeout(DEBOUNCE_TIME) {
+            Ok(Ok(event)) => {
+                if !matches!(event.kind, EventKind::Access(_)) {
+                    pending.extend(event.paths);
+                }
+            }
+            Ok(Err(e)) => {
+                // An error that isn't about any particular file means that the watcher itself
+                // has failed.
+                if e.paths.is_empty() {
+                    return Err(e).wrap_err_with(|| eyre!("Watching {}", source.location));
+                }
+
+                let kind = match e.kind {
+                    notify::ErrorKind::Io(_) | notify::ErrorKind::PathNotFound => ItemErrorKind::Io,
+                    _ => ItemErrorKind::Other,
+                };
+                let message = e.to_string();
+                for path in &e.paths {
+                    if let Some(item_path) = to_item_path(&root, location, path) {
+                        record_path_error(stats, database, source.id, &item_path, kind, &message)?;
+                    }
+                }
+            }
+            Err(flume::RecvTimeoutError::Timeout) => {
+                if pending.is_empty() {
+                    continue;
+                }
+
+                let paths = pending.drain().collect::<Vec<_>>();
+                let searcher = get_searcher();
+                let result = process_changes(
+                    stats,
+                    database,
+                    model,
+                    model_id,
+                    model_version,
+                    source,
+                    searcher.as_deref(),
+                    &scanner,
+                    &glob,
+                    &root,
+                    cancel,
+                    &paths,
+                );
+
+                if let Err(e) = result {
+                    // Attribute the failure to every file in the batch, so that it shows up in the
+                    // source's errors instead of stopping the watch.
+                    let kind = ItemErrorKind::from_error(&e);
+                    let message = format!("{e:#}");
+                    for path in &paths {
+                        if let Some(item_path) = to_item_path(&root, location, path) {
+                            record_path_error(
+                                stats, database, source.id, &item_path, kind, &message,
+                            )?;
+                        }
+                    }
+                }
+            }
+            Err(flume::RecvTimeoutError::Disconnected) => {
+                return Err(eyre!("Watcher for {} shut down", source.location))
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Map a path from a watch event, which is under the canonical root, to the path that the scan
+/// uses for the file.
+fn to_item_path(root: &Path, location: &Path, path: &Path) -> Option<PathBuf> {
+    path.strip_prefix(root)
+        .ok()
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .map(|relative| location.join(relative))
+}
+
+/// Record a failure for a file in the same way that the pipeline does for items that fail to read.
+fn record_path_error(
+    stats: &ScanStats,
+    database: &Database,
+    source_id: i64,
+    item_path: &Path,
+    kind: ItemErrorKind,
+    message: &str,
+) -> Result<(), eyre::Report> {
+    let external_id = item_path.to_string_lossy().to_string();
+    record_item_error(database, source_id, &external_id, kind, message)?;
+    stats.errored.fetch_add(1, Ordering::Relaxed);
+    stats.events.emit(|| ScanEvent::Errored {
+        source_id,
+        external_id,
+        kind,
+        message: message.to_string(),
+    });
+    Ok(())
+}
+
+/// The ignore files in a directory.
+struct DirRules {
+    ignore: Option<Gitignore>,
+    gitignore: Option<Gitignore>,
+    git_exclude: Option<Gitignore>,
+    is_repo: bool,
+}
+
+impl DirRules {
+    fn load(dir: &Path) -> DirRules {
+        let matcher = |file: PathBuf| {
+            if !file.is_file() {
+                return None;
+            }
+
+            let mut builder = GitignoreBuilder::new(dir);
+            // Like the directory walk, use whatever lines were valid if some of them weren't.
+            builder.add(&file);
+            builder.build().ok()
+        };
+
+        DirRules {
+            ignore: matcher(dir.join(".ignore")),
+            gitignore: matcher(dir.join(".gitignore")),
+            git_exclude: matcher(dir.join(".git/info/exclude")),
+            is_repo: dir.join(".git").exists(),
+        }
+    }
+}
+
+/// Decides which changed paths to skip, using the same rules as the directory walk in a full
+/// scan: hidden files are skipped, along with anything matched by `.ignore` files, and by
+/// `.gitignore` files inside a git repository.
+struct IgnoreRules<'a> {
+    root: &'a Path,
+    dirs: HashMap<PathBuf, DirRules>,
+    global: Gitignore,
+}
+
+impl<'a> IgnoreRules<'a> {
+    fn new(root: &'a Path) -> Self {
+        IgnoreRules {
+            root,
+            dirs: HashMap::default(),
+            global: Gitignore::global().0,
+        }
+    }
+
+    /// Returns true if the path, or any directory between it and the source's root, is ignored.
+    fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        let Ok(relative) = path.strip_prefix(self.root) else {
+            return false;
+        };
+
+        let components = relative.components().collect::<Vec<_>>();
+        let mut current = self.root.to_path_buf();
+        for (i, component) in components.iter().enumerate() {
+            current.push(component);
+            let current_is_dir = is_dir || i + 1 < components.len();
+            if component.as_os_str().to_string_lossy().starts_with('.')
+                || self.matches_ignore_file(&current, current_is_dir)
+            {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn matches_ignore_file(&mut self, path: &Path, is_dir: bool) -> bool {
+        let dirs = path.ancestors().skip(1).collect::<Vec<_>>();
+        for dir in &dirs {
+            if !self.dirs.contains_key(*dir) {
+                self.dirs.insert(dir.to_path_buf(), DirRules::load(dir));
+            }
+        }
+
+        let dir_rules = dirs.iter().map(|dir| &self.dirs[*dir]).collect::<Vec<_>>();
+        let in_repo = dir_rules.iter().any(|rules| rules.is_repo);
+
+        // `.ignore` files take precedence over git's ignore files, and within each kind a file
+        // closer to the path overrides the ones above it.
+        let kinds: [fn(&DirRules) -> Option<&Gitignore>; 3] = [
+            |rules| rules.ignore.as_ref(),
+            |rules| rules.gitignore.as_ref(),
+            |rules| rules.git_exclude.as_ref(),
+        ];
+        let checked_kinds = if in_repo { 3 } else { 1 };
+        for kind in &kinds[..checked_kinds] {
+            for matcher in dir_rules.iter().filter_map(|rules| kind(rules)) {
+                let matched = matcher.matched(path, is_dir);
+                if matched.is_ignore() {
+                    return true;
+                } else if matched.is_whitelist() {
+                    return false;
+                }
+            }
+        }
+
+        in_repo && self.global.matched(path, is_dir).is_ignore()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn process_changes(
+    stats: &ScanStats,
+    database: &Database,
+    model: &Model,
+    model_id: u32,
+    model_version: u32,
+    source: &Source,
+    searcher: Option<&Searcher>,
+    scanner: &FileScanner,
+    glob: &globset::GlobSet,
+    root: &Path,
+    cancel: &CancellationToken,
+    paths: &[PathBuf],
+) -> Result<(), eyre::Report> {
+    let location = Path::new(&source.location);
+
+    let mut rules = IgnoreRules::new(root);
+    let mut changed = Vec::new();
+    let mut removed = Vec::new();
+
+    for path in paths {
+        let Some(item_path) = to_item_path(root, location, path) else {
+            continue;
+        };
+
+        match std::fs::metadata(path) {
+            Ok(meta) if meta.is_file() => {
+                if glob.is_match(&item_path) && !rules.is_ignored(path, false) {
+                    changed.push(file_item(source.id, &item_path, &meta));
+                }
+            }
+            Ok(meta) if meta.is_dir() => {
+                if rules.is_ignored(path, true) {
+                    continue;
+                }
+
+                // A directory that was moved into the source only generates an event for the
+                // directory itself, so look for files inside it.
+                for entry in ignore::Walk::new(path).flatten() {
+                    let Ok(meta) = entry.metadata() else {
+                        continue;
+                    };
+
+                    let Some(entry_path) = to_item_path(root, location, entry.path()) else {
+                        continue;
+                    };
+
+                    if meta.is_file() && glob.is_match(&entry_path) {
+                        changed.push(file_item(source.id, &entry_path, &meta));
+                    }
+                }
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => removed.push(item_path),
+            Err(e) => {
+                let message = format!("Reading {}: {e}", path.display());
+                record_path_error(
+                    stats,
+                    database,
+                    source.id,
+                    &item_path,
+                    ItemErrorKind::Io,
+                    &message,
+                )?;
+            }
+        }
+    }
+
+    if !removed.is_empty() {
+        let removed_ids = remove_paths(database, source.id, &removed)?;
+        stats
+            .removed
+            .fetch_add(removed_ids.len() as u64, Ordering::Relaxed);
+        if let Some(searcher) = searcher {
+            searcher.remove_items(source.id, &removed_ids);
+        }
+    }
+
+    if !changed.is_empty() {
+        let external_ids = changed
+            .iter()
+            .map(|item| rusqlite::types::Value::from(item.external_id.clone()))
+            .collect::<Vec<_>>();
+
+        index_items(
+            stats,
+            database,
+            model,
+            model_id,
+            model_version,
+            source,
+            scanner,
+            cancel,
+            changed,
+        )?;
+
+        if let Some(searcher) = searcher {
+            let conn = database.read_pool.get()?;
+            let mut stmt = conn.prepare_cached(
+                "SELECT id FROM items WHERE source_id = ? AND external_id IN rarray(?)",
+            )?;
+            let ids = stmt
+                .query_map(params![source.id, Rc::new(external_ids)], |row| row.get(0))?
+                .collect::<Result<Vec<i64>, _>>()?;
+
+            searcher.update_items(database, source.id, model_id, model_version, &ids)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Delete the items for removed files, or for files inside removed directories.
+fn remove_paths(
+    database: &Database,
+    source_id: i64,
+    paths: &[PathBuf],
+) -> Result<Vec<i64>, eyre::Report> {
+    let mut conn = database.write_conn.lock();
+    let tx = conn.transaction()?;
+    let mut ids = Vec::new();
+
+    {
+        // This compares the prefix directly instead of using LIKE, which would treat `_` and `%`
+        // in the path as wildcards and ignore case.
+        let mut stmt = tx.prepare_cached(
+            r##"DELETE FROM items
+            WHERE source_id = ?1
+                AND (external_id = ?2 OR substr(external_id, 1, length(?2) + 1) = ?2 || '/')
+            RETURNING id"##,
+        )?;
+
+        for path in paths {
+            let path = path.to_string_lossy();
+            let deleted = stmt
+                .query_map(params![source_id, path], |row| row.get::<_, i64>(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+            ids.extend(deleted);
+        }
+    }
+
+    tx.commit()?;
+    Ok(ids)
+}
+
+/// Run a set of changed files through the pipeline, one item at a time.
+#[allow(clippy::too_many_arguments)]
+fn index_items(
+    stats: &ScanStats,
+    database: &Database,
+    model: &Model,
+    model_id: u32,
+    model_version: u32,
+    source: &Source,
+    scanner: &FileScanner,
+    cancel: &CancellationToken,
+    items: Vec<Item>,
+) -> Result<(), eyre::Report> {
+    let compare_strategy = source.compare_strategy;
+
+    std::thread::scope(|scope| {
+        let (item_tx, item_rx) = flume::unbounded();
+        let (matched_tx, matched_rx) = flume::unbounded();
+        let (with_content_tx, with_content_rx) = flume::unbounded();
+        let (with_embeddings_tx, with_embeddings_rx) = flume::unbounded();
+
+        let item_tx = CountingVecSender {
+            tx: item_tx,
+            count: &stats.scanned,
+        };
+
+        for item in items {
+            item_tx.send(vec![item]).ok();
+        }
+        drop(item_tx);
+
+        let db_lookup_task = scope.spawn(|| {
+            match_to_existing_items(
+                stats,
+                database,
+                model_id,
+                model_version,
+                source.id,
+                compare_strategy,
+                None,
+                item_rx,
+                matched_tx,
+            )
+        });
+
+        let read_task = scope.spawn(|| {
+            read_items(
+                stats,
+                database,
+                compare_strategy,
+                scanner,
+                cancel,
+                matched_rx,
+                with_content_tx,
+            )
+        });
+
+        let embed_task = scope.spawn(|| {
+            calculate_embeddings(stats, model, cancel, with_content_rx, with_embeddings_tx)
+        });
+
+        let write_db_task = scope.spawn(|| {
+            update_db(
+                model_id,
+                model_version,
+                stats,
+                database,
+                source.index_version,
+                with_embeddings_rx,
+            )
+        });
+
+        let mut errored = false;
+        errored |= log_thread_error("db_writer", write_db_task.join());
+        errored |= log_thread_error("encoding task", embed_task.join());
+        errored |= log_thread_error("read_items", read_task.join());
+        errored |= log_thread_error("db_lookup", db_lookup_task.join());
+
+        if errored {
+            Err(eyre!("Failed to index changed files"))
+        } else {
+            Ok(())
+        }
+    })
+}
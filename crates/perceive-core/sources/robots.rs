@@ -0,0 +1,196 @@
+/// The rules from a robots.txt file that apply to a particular user agent.
+#[derive(Debug, Default)]
+pub struct RobotsTxt {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+impl RobotsTxt {
+    /// A robots.txt that allows everything, used when a site doesn't have one.
+    pub fn allow_all() -> RobotsTxt {
+        RobotsTxt::default()
+    }
+
+    /// Parse a robots.txt file, keeping the rules for the group that best matches `user_agent`.
+    /// A group that names the user agent is used instead of the `*` group if there is one.
+    pub fn parse(content: &str, user_agent: &str) -> RobotsTxt {
+        let user_agent = user_agent.to_lowercase();
+
+        let mut specific_rules = Vec::new();
+        let mut wildcard_rules = Vec::new();
+        // Set when a group names the user agent, even if that group allows everything.
+        let mut has_specific_group = false;
+
+        // The user agents for the current group.
+        let mut group_agents: Vec<String> = Vec::new();
+        let mut in_rules = false;
+
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+
+            let key = key.trim().to_lowercase();
+            let value = value.trim();
+
+            match key.as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        // A user-agent line after some rules starts a new group.
+                        group_agents.clear();
+                        in_rules = false;
+                    }
+                    group_agents.push(value.to_lowercase());
+                }
+                "allow" | "disallow" => {
+                    in_rules = true;
+                    let is_specific = group_agents
+                        .iter()
+                        .any(|a| a != "*" && user_agent.contains(a.as_str()));
+                    has_specific_group |= is_specific;
+
+                    if value.is_empty() {
+                        // An empty disallow means that everything is allowed.
+                        continue;
+                    }
+
+                    let rule = || Rule {
+                        allow: key == "allow",
+                        pattern: value.to_string(),
+                    };
+
+                    if is_specific {
+                        specific_rules.push(rule());
+                    } else if group_agents.iter().any(|a| a == "*") {
+                        wildcard_rules.push(rule());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        RobotsTxt {
+            rules: if has_specific_group {
+                specific_rules
+            } else {
+                wildcard_rules
+            },
+        }
+    }
+
+    /// Check if a path (including the query string) may be fetched. The longest matching rule
+    /// wins, and an allow rule wins over a disallow rule of the same length.
+    pub fn is_allowed(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|rule| pattern_matches(&rule.pattern, path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .map(|rule| rule.allow)
+            .unwrap_or(true)
+    }
+}
+
+/// Match a robots.txt path pattern, which may contain `*` wildcards and end with a `$` anchor.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(p) => (p, true),
+        None => (pattern, false),
+    };
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = path.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts = parts.collect::<Vec<_>>();
+    for (i, part) in parts.iter().enumerate() {
+        let is_last = i == parts.len() - 1;
+        if is_last && anchored {
+            return rest.ends_with(part);
+        }
+
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+
+    !anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS: &str = r##"
+# Comments are ignored
+User-agent: *
+Disallow: /private/
+Allow: /private/public.html
+Disallow: /*.pdf$
+
+User-agent: BadBot
+User-agent: perceive-search
+Disallow: /search
+Disallow:
+"##;
+
+    #[test]
+    fn wildcard_group() {
+        let robots = RobotsTxt::parse(ROBOTS, "other-agent");
+        assert!(robots.is_allowed("/"));
+        assert!(robots.is_allowed("/search"));
+        assert!(!robots.is_allowed("/private/secret.html"));
+        assert!(robots.is_allowed("/private/public.html"));
+        assert!(!robots.is_allowed("/files/doc.pdf"));
+        assert!(robots.is_allowed("/files/doc.pdf?download=1"));
+    }
+
+    #[test]
+    fn specific_group() {
+        let robots = RobotsTxt::parse(ROBOTS, "perceive-search");
+        assert!(!robots.is_allowed("/search?q=abc"));
+        // The specific group replaces the wildcard group.
+        assert!(robots.is_allowed("/private/secret.html"));
+    }
+
+    #[test]
+    fn specific_group_allows_everything() {
+        let content = r##"
+User-agent: *
+Disallow: /
+
+User-agent: perceive-search
+Disallow:
+"##;
+
+        // The wildcard group doesn't apply, since there is a group for this user agent.
+        let robots = RobotsTxt::parse(content, "perceive-search");
+        assert!(robots.is_allowed("/anything"));
+
+        let robots = RobotsTxt::parse(content, "other-agent");
+        assert!(!robots.is_allowed("/anything"));
+    }
+
+    #[test]
+    fn empty_file() {
+        let robots = RobotsTxt::parse("", "perceive-search");
+        assert!(robots.is_allowed("/anything"));
+    }
+
+    #[test]
+    fn wildcard_patterns() {
+        assert!(pattern_matches("/a*/c", "/abc/c/d"));
+        assert!(!pattern_matches("/a*/c$", "/abc/c/d"));
+        assert!(pattern_matches("/a*/c$", "/abc/c"));
+        assert!(pattern_matches("*", "/anything"));
+        assert!(!pattern_matches("/b", "/a/b"));
+    }
+}
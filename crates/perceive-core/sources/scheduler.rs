@@ -0,0 +1,130 @@
+use std::sync::atomic::Ordering;
+
+use time::OffsetDateTime;
+
+use super::{
+    db::update_source,
+    pipeline::{scan_source, ScanEvents, ScanResult, ScanStats},
+    ItemCompareStrategy, Source, SourceStatus,
+};
+use crate::{cancel::CancellationToken, db::Database, model::Model};
+
+/// Return the indexes of the sources that are due to be indexed, most overdue first.
+pub fn due_sources(sources: &[Source], now: OffsetDateTime) -> Vec<usize> {
+    let mut due = sources
+        .iter()
+        .enumerate()
+        .filter_map(|(index, source)| {
+            source
+                .next_index_time()
+                .filter(|&next| next <= now)
+                .map(|next| (index, next))
+        })
+        .collect::<Vec<_>>();
+
+    due.sort_by_key(|(_, next)| *next);
+    due.into_iter().map(|(index, _)| index).collect()
+}
+
+/// The next time that any of the sources will be due for indexing.
+pub fn next_due_time(sources: &[Source]) -> Option<OffsetDateTime> {
+    sources.iter().filter_map(|s| s.next_index_time()).min()
+}
+
+/// Scan a source, and record the outcome of the scan in the source's status. If the previous scan
+/// of the source was interrupted, this resumes it instead of starting over.
+#[allow(clippy::too_many_arguments)]
+pub fn index_source(
+    stats: &ScanStats,
+    database: &Database,
+    model: &Model,
+    model_id: u32,
+    model_version: u32,
+    source: &mut Source,
+    override_compare_strategy: Option<ItemCompareStrategy>,
+    cancel: &CancellationToken,
+) -> Result<ScanResult, eyre::Report> {
+    let started_at = match source.status {
+        // The last scan didn't finish, either because it was cancelled or because the process
+        // exited partway through. Keep the same index version so that the items it already
+        // handled are skipped.
+        SourceStatus::Interrupted { started_at, .. } | SourceStatus::Indexing { started_at } => {
+            OffsetDateTime::from_unix_timestamp(started_at)
+                .unwrap_or_else(|_| OffsetDateTime::now_utc())
+        }
+        _ => {
+            source.index_version += 1;
+            OffsetDateTime::now_utc()
+        }
+    };
+
+    source.last_indexed = started_at;
+    source.status = SourceStatus::Indexing {
+        started_at: started_at.unix_timestamp(),
+    };
+    update_source(database, source)?;
+
+    let result = scan_source(
+        stats,
+        database,
+        model,
+        model_id,
+        model_version,
+        source,
+        override_compare_strategy,
+        cancel,
+    );
+
+    source.status = match &result {
+        Ok(result) if result.completed => SourceStatus::Ready {
+            scanned: stats.scanned.load(Ordering::Relaxed) as u32,
+            duration: (OffsetDateTime::now_utc() - started_at).whole_seconds() as u32,
+        },
+        Ok(result) if result.cancelled => SourceStatus::Interrupted {
+            started_at: started_at.unix_timestamp(),
+            scanned: stats.scanned.load(Ordering::Relaxed) as u32,
+        },
+        Ok(_) => SourceStatus::Error {
+            error: "Scan did not complete".to_string(),
+        },
+        Err(e) => SourceStatus::Error {
+            error: e.to_string(),
+        },
+    };
+    update_source(database, source)?;
+
+    result
+}
+
+/// Index every source that is due, one after another. Returns the index of each source that was
+/// indexed, along with the result of its scan. Sources that haven't started yet are skipped once
+/// `cancel` is triggered. The events from every scan are published to `events`.
+pub fn run_due_sources(
+    database: &Database,
+    model: &Model,
+    model_id: u32,
+    model_version: u32,
+    sources: &mut [Source],
+    events: &ScanEvents,
+    cancel: &CancellationToken,
+) -> Vec<(usize, Result<ScanResult, eyre::Report>)> {
+    due_sources(sources, OffsetDateTime::now_utc())
+        .into_iter()
+        .take_while(|_| !cancel.is_cancelled())
+        .map(|index| {
+            let stats = ScanStats::with_events(events.clone());
+            let result = index_source(
+                &stats,
+                database,
+                model,
+                model_id,
+                model_version,
+                &mut sources[index],
+                None,
+                cancel,
+            );
+
+            (index, result)
+        })
+        .collect()
+}
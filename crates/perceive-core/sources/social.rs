@@ -0,0 +1,406 @@
+mod mastodon;
+mod twitter;
+
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use ahash::{HashMap, HashSet};
+use eyre::{eyre, Context, Result};
+use itertools::Itertools;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use time::OffsetDateTime;
+use zip::{result::ZipError, ZipArchive};
+
+use self::{mastodon::parse_mastodon_archive, twitter::parse_twitter_archive};
+use super::{
+    parse_html::{read_web_page, reprocess_html_article, should_skip, HTML_PROCESS_VERSION},
+    pipeline::{
+        prefilled_read, CountingVecSender, FoundItem, SourceScanner, SourceScannerReadResult,
+        SCAN_BATCH_SIZE,
+    },
+    web_fetcher::{FetchConfig, WebFetcher},
+    ItemCompareStrategy,
+};
+use crate::{batch_sender::BatchSender, cancel::CancellationToken, Item, ItemMetadata};
+
+#[derive(Debug, Display, EnumString, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum SocialPlatform {
+    /// A Mastodon account archive
+    Mastodon,
+    /// A Twitter/X archive
+    Twitter,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SocialArchiveConfig {
+    pub platform: SocialPlatform,
+    /// Fetch the pages linked from posts, along with bookmarked posts whose text isn't in the
+    /// archive.
+    #[serde(default)]
+    pub fetch_links: bool,
+    /// Domains that we should never fetch
+    #[serde(default)]
+    pub skip: Vec<String>,
+    #[serde(default)]
+    pub fetch: FetchConfig,
+}
+
+/// A post read from an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Post {
+    /// The ID that replies use to refer to the post
+    pub id: String,
+    /// The canonical URL of the post
+    pub url: String,
+    pub author: Option<String>,
+    pub time: Option<OffsetDateTime>,
+    pub text: String,
+    /// The ID of the post that this one replies to
+    pub in_reply_to: Option<String>,
+    /// Links to other pages in the post
+    pub links: Vec<String>,
+}
+
+/// The posts read from an archive.
+#[derive(Debug, Default)]
+pub struct SocialArchive {
+    /// The account's own posts
+    pub posts: Vec<Post>,
+    /// Bookmarked or liked posts. Mastodon archives only list the URLs of bookmarks, so these
+    /// may have no text.
+    pub saved: Vec<Post>,
+}
+
+/// The files in an archive, either still zipped or extracted to a directory.
+enum ArchiveFiles {
+    Zip(ZipArchive<File>),
+    Dir(PathBuf),
+}
+
+impl ArchiveFiles {
+    fn open(location: &str) -> Result<Self> {
+        let path = Path::new(location);
+        if path.is_dir() {
+            return Ok(Self::Dir(path.to_path_buf()));
+        }
+
+        let file = File::open(path).wrap_err_with(|| eyre!("Opening {location}"))?;
+        let zip = ZipArchive::new(file).wrap_err_with(|| eyre!("Reading {location}"))?;
+        Ok(Self::Zip(zip))
+    }
+
+    /// Read a file from the archive, or return None if it doesn't exist.
+    fn read(&mut self, name: &str) -> Result<Option<Vec<u8>>> {
+        let mut content = Vec::new();
+        match self {
+            Self::Zip(zip) => match zip.by_name(name) {
+                Ok(mut file) => {
+                    file.read_to_end(&mut content)?;
+                }
+                Err(ZipError::FileNotFound) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            },
+            Self::Dir(dir) => {
+                let path = dir.join(name);
+                if !path.is_file() {
+                    return Ok(None);
+                }
+                content = std::fs::read(&path).wrap_err_with(|| eyre!("Reading {name}"))?;
+            }
+        }
+
+        Ok(Some(content))
+    }
+}
+
+/// Group posts into threads, following replies back to the first post in the archive that
+/// they reply to. Replies to other people's posts start a new thread.
+pub fn group_threads(posts: Vec<Post>) -> Vec<Vec<Post>> {
+    let parents = posts
+        .iter()
+        .filter_map(|p| Some((p.id.clone(), p.in_reply_to.clone()?)))
+        .collect::<HashMap<_, _>>();
+    let ids = posts.iter().map(|p| p.id.clone()).collect::<HashSet<_>>();
+
+    let root = |id: &str| {
+        let mut current = id;
+        // The limit guards against loops in a malformed archive.
+        for _ in 0..parents.len() {
+            match parents.get(current) {
+                Some(parent) if ids.contains(parent) => current = parent,
+                _ => break,
+            }
+        }
+        current.to_string()
+    };
+
+    let mut threads: HashMap<String, Vec<Post>> = HashMap::default();
+    for post in posts {
+        threads.entry(root(&post.id)).or_default().push(post);
+    }
+
+    let mut threads = threads.into_values().collect::<Vec<_>>();
+    for thread in threads.iter_mut() {
+        thread.sort_by_key(|p| p.time);
+    }
+    threads.sort_by_key(|t| t.first().and_then(|p| p.time));
+    threads
+}
+
+/// Use the start of a post as its title.
+fn post_title(text: &str) -> Option<String> {
+    const MAX_TITLE_LENGTH: usize = 80;
+
+    let line = text.lines().map(str::trim).find(|l| !l.is_empty())?;
+    if line.chars().count() <= MAX_TITLE_LENGTH {
+        return Some(line.to_string());
+    }
+
+    let title = line.chars().take(MAX_TITLE_LENGTH).collect::<String>();
+    Some(format!("{}...", title.trim_end()))
+}
+
+fn thread_item(source_id: i64, thread: &[Post]) -> Option<Item> {
+    let first = thread.first()?;
+    let content = thread
+        .iter()
+        .map(|p| p.text.trim())
+        .filter(|t| !t.is_empty())
+        .join("\n\n");
+    if content.is_empty() {
+        return None;
+    }
+
+    Some(Item {
+        id: -1,
+        source_id,
+        external_id: first.url.clone(),
+        hash: None,
+        content: Some(content),
+        raw_content: None,
+        skipped: None,
+        process_version: 0,
+        metadata: ItemMetadata {
+            name: post_title(&first.text),
+            author: first.author.clone(),
+            mtime: thread.iter().filter_map(|p| p.time).max(),
+            ..Default::default()
+        },
+    })
+}
+
+/// Reads the posts from a Mastodon or Twitter archive. Threads of replies become a single item,
+/// and bookmarked or liked posts are indexed as well. Links in the posts can optionally be
+/// fetched, to make the pages that they point to searchable.
+pub struct SocialArchiveScanner {
+    pub source_id: i64,
+    pub location: String,
+    pub config: SocialArchiveConfig,
+    /// Set when linked pages should be fetched.
+    pub fetcher: Option<WebFetcher>,
+}
+
+impl SocialArchiveScanner {
+    pub fn new(source_id: i64, location: String, config: SocialArchiveConfig) -> Result<Self> {
+        let fetcher = if config.fetch_links {
+            Some(WebFetcher::new(config.fetch.clone(), true)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            source_id,
+            location,
+            config,
+            fetcher,
+        })
+    }
+
+    fn read_archive(&self) -> Result<SocialArchive> {
+        let mut files = ArchiveFiles::open(&self.location)?;
+        match self.config.platform {
+            SocialPlatform::Mastodon => parse_mastodon_archive(&mut files),
+            SocialPlatform::Twitter => parse_twitter_archive(&mut files),
+        }
+    }
+
+    /// Create an item for a linked page or a saved post that needs to be fetched. Pages are only
+    /// refetched when a newer post links to them.
+    fn fetch_item(&self, url: String, time: Option<OffsetDateTime>) -> Item {
+        Item {
+            id: -1,
+            source_id: self.source_id,
+            external_id: url,
+            hash: None,
+            content: None,
+            raw_content: None,
+            skipped: None,
+            process_version: HTML_PROCESS_VERSION,
+            metadata: ItemMetadata {
+                mtime: time,
+                atime: time,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Normalize a link to a page, or return None if it shouldn't be fetched.
+    fn link_url(&self, link: &str) -> Option<String> {
+        let mut url = Url::parse(link).ok()?;
+        if !matches!(url.scheme(), "http" | "https") || should_skip(&self.config.skip, &url) {
+            return None;
+        }
+
+        url.set_fragment(None);
+        Some(url.to_string())
+    }
+}
+
+impl SourceScanner for SocialArchiveScanner {
+    fn scan(&self, output: CountingVecSender<Item>, cancel: &CancellationToken) -> Result<()> {
+        let archive = self.read_archive()?;
+        let sender = BatchSender::new(SCAN_BATCH_SIZE, output);
+        let mut seen = HashSet::default();
+
+        // The latest time that each page was linked.
+        let mut links: HashMap<String, Option<OffsetDateTime>> = HashMap::default();
+        for thread in group_threads(archive.posts) {
+            if cancel.is_cancelled() {
+                return Ok(());
+            }
+
+            if self.fetcher.is_some() {
+                for post in &thread {
+                    for link in post.links.iter().filter_map(|l| self.link_url(l)) {
+                        let time = links.entry(link).or_default();
+                        *time = (*time).max(post.time);
+                    }
+                }
+            }
+
+            if let Some(item) = thread_item(self.source_id, &thread) {
+                if seen.insert(item.external_id.clone()) {
+                    sender.add(item)?;
+                }
+            }
+        }
+
+        for post in archive.saved {
+            if !seen.insert(post.url.clone()) {
+                continue;
+            }
+
+            if !post.text.trim().is_empty() {
+                if let Some(item) = thread_item(self.source_id, &[post]) {
+                    sender.add(item)?;
+                }
+            } else if self.fetcher.is_some() {
+                sender.add(self.fetch_item(post.url, post.time))?;
+            }
+        }
+
+        for (url, time) in links {
+            if seen.insert(url.clone()) {
+                sender.add(self.fetch_item(url, time))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read(
+        &self,
+        existing: Option<&FoundItem>,
+        compare_strategy: ItemCompareStrategy,
+        item: &mut Item,
+    ) -> Result<SourceScannerReadResult> {
+        // Posts were read from the archive while scanning.
+        if item.content.is_some() {
+            return Ok(prefilled_read(existing, compare_strategy, item));
+        }
+
+        let Some(fetcher) = self.fetcher.as_ref() else {
+            return Ok(SourceScannerReadResult::Omit);
+        };
+
+        // Keep the time of the post that linked to the page, like the feeds source does.
+        let (mtime, atime) = (item.metadata.mtime, item.metadata.atime);
+        let result = read_web_page(fetcher, existing, compare_strategy, item)?;
+        item.metadata.mtime = mtime.or(item.metadata.mtime);
+        item.metadata.atime = atime;
+        Ok(result)
+    }
+
+    fn latest_process_version(&self) -> i32 {
+        HTML_PROCESS_VERSION
+    }
+
+    fn reprocess(&self, item: &mut Item) -> Result<SourceScannerReadResult> {
+        reprocess_html_article(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::timeline::test_time;
+
+    fn post(id: &str, minute: i64, in_reply_to: Option<&str>) -> Post {
+        Post {
+            id: id.to_string(),
+            url: format!("https://example.com/{id}"),
+            author: None,
+            time: Some(test_time(minute)),
+            text: id.to_string(),
+            in_reply_to: in_reply_to.map(String::from),
+            links: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn threads() {
+        let posts = vec![
+            post("reply-2", 10, Some("reply-1")),
+            post("standalone", 5, None),
+            post("root", 0, None),
+            post("reply-1", 2, Some("root")),
+            // Replies to posts from other accounts start their own thread.
+            post("other-reply", 20, Some("someone-else")),
+        ];
+
+        let threads = group_threads(posts)
+            .into_iter()
+            .map(|t| t.into_iter().map(|p| p.id).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            threads,
+            vec![
+                vec!["root", "reply-1", "reply-2"],
+                vec!["standalone"],
+                vec!["other-reply"],
+            ]
+        );
+    }
+
+    #[test]
+    fn titles() {
+        assert_eq!(
+            post_title("\n  First line \nSecond"),
+            Some("First line".to_string())
+        );
+        assert_eq!(
+            post_title(&"word ".repeat(30)),
+            Some(format!("{}...", "word ".repeat(16).trim_end()))
+        );
+        assert_eq!(post_title("   "), None);
+    }
+}
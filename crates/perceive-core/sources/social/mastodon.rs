@@ -0,0 +1,277 @@
+use eyre::{eyre, Context, Result};
+use reqwest::Url;
+use serde::Deserialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use super::{ArchiveFiles, Post, SocialArchive};
+use crate::sources::parse_html::html_to_text;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Actor {
+    preferred_username: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+}
+
+impl Actor {
+    /// The display name along with the full handle, like `Alice (@alice@example.social)`.
+    fn author(&self) -> String {
+        let host = self
+            .url
+            .as_deref()
+            .and_then(|url| Url::parse(url).ok())
+            .and_then(|url| url.host_str().map(String::from));
+        let handle = match host {
+            Some(host) => format!("@{}@{host}", self.preferred_username),
+            None => format!("@{}", self.preferred_username),
+        };
+
+        match self.name.as_deref().filter(|n| !n.is_empty()) {
+            Some(name) => format!("{name} ({handle})"),
+            None => handle,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Collection<T> {
+    #[serde(default = "Vec::new")]
+    ordered_items: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Activity {
+    #[serde(rename = "type")]
+    kind: String,
+    object: ActivityObject,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ActivityObject {
+    Note(Box<Note>),
+    /// Boosts only refer to the boosted post by its URL.
+    Link(String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Note {
+    id: String,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    published: Option<String>,
+    /// The content warning, if there is one
+    #[serde(default)]
+    summary: Option<String>,
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    in_reply_to: Option<String>,
+    #[serde(default)]
+    attachment: Vec<Attachment>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Attachment {
+    /// The alt text
+    #[serde(default)]
+    name: Option<String>,
+}
+
+/// Read the posts and bookmarks from a Mastodon account archive.
+pub(super) fn parse_mastodon_archive(files: &mut ArchiveFiles) -> Result<SocialArchive> {
+    let author = match files.read("actor.json")? {
+        Some(actor) => {
+            let actor: Actor = serde_json::from_slice(&actor).wrap_err("Reading actor.json")?;
+            Some(actor.author())
+        }
+        None => None,
+    };
+
+    let outbox = files
+        .read("outbox.json")?
+        .ok_or_else(|| eyre!("The archive does not contain outbox.json"))?;
+    let posts = parse_outbox(&outbox, author.as_deref()).wrap_err("Reading outbox.json")?;
+
+    let saved = match files.read("bookmarks.json")? {
+        Some(bookmarks) => parse_bookmarks(&bookmarks).wrap_err("Reading bookmarks.json")?,
+        None => Vec::new(),
+    };
+
+    Ok(SocialArchive { posts, saved })
+}
+
+fn parse_outbox(content: &[u8], author: Option<&str>) -> Result<Vec<Post>> {
+    let outbox: Collection<Activity> = serde_json::from_slice(content)?;
+    let posts = outbox
+        .ordered_items
+        .into_iter()
+        .filter(|activity| activity.kind == "Create")
+        .filter_map(|activity| match activity.object {
+            ActivityObject::Note(note) => Some(note_post(*note, author)),
+            ActivityObject::Link(_) => None,
+        })
+        .collect();
+
+    Ok(posts)
+}
+
+fn note_post(note: Note, author: Option<&str>) -> Post {
+    let content = note.content.unwrap_or_default();
+    let mut text = String::new();
+    if let Some(summary) = note.summary.as_deref().filter(|s| !s.is_empty()) {
+        text.push_str(&html_to_text(summary));
+        text.push_str("\n\n");
+    }
+    text.push_str(&html_to_text(&content));
+
+    for alt in note.attachment.iter().filter_map(|a| a.name.as_deref()) {
+        text.push_str(&format!("\n[image: {alt}]"));
+    }
+
+    Post {
+        url: note.url.unwrap_or_else(|| note.id.clone()),
+        id: note.id,
+        author: author.map(String::from),
+        time: note
+            .published
+            .and_then(|p| OffsetDateTime::parse(&p, &Rfc3339).ok()),
+        text,
+        in_reply_to: note.in_reply_to,
+        links: post_links(&content),
+    }
+}
+
+/// The bookmarks file only lists the URLs of the bookmarked posts.
+fn parse_bookmarks(content: &[u8]) -> Result<Vec<Post>> {
+    let bookmarks: Collection<String> = serde_json::from_slice(content)?;
+    let posts = bookmarks
+        .ordered_items
+        .into_iter()
+        .map(|url| Post {
+            id: url.clone(),
+            url,
+            author: None,
+            time: None,
+            text: String::new(),
+            in_reply_to: None,
+            links: Vec::new(),
+        })
+        .collect();
+
+    Ok(posts)
+}
+
+/// Get the links from a post's HTML, leaving out mentions and hashtags.
+fn post_links(html: &str) -> Vec<String> {
+    let mut links = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find("<a ") {
+        rest = &rest[start..];
+        let end = rest.find('>').unwrap_or(rest.len());
+        let tag = &rest[..end];
+        rest = &rest[end..];
+
+        let class = attribute(tag, "class").unwrap_or_default();
+        if class
+            .split_whitespace()
+            .any(|c| c == "mention" || c == "hashtag")
+        {
+            continue;
+        }
+
+        if let Some(href) = attribute(tag, "href") {
+            links.push(href.replace("&amp;", "&"));
+        }
+    }
+
+    links
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let pattern = format!(" {name}=\"");
+    let start = tag.find(&pattern)? + pattern.len();
+    let len = tag[start..].find('"')?;
+    Some(&tag[start..start + len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outbox() {
+        let outbox = r##"{
+            "@context": "https://www.w3.org/ns/activitystreams",
+            "type": "OrderedCollection",
+            "totalItems": 3,
+            "orderedItems": [
+                {
+                    "type": "Create",
+                    "object": {
+                        "id": "https://example.social/users/alice/statuses/1",
+                        "type": "Note",
+                        "url": "https://example.social/@alice/1",
+                        "published": "2023-01-10T12:00:00Z",
+                        "summary": null,
+                        "inReplyTo": null,
+                        "content": "<p>Reading <a href=\"https://example.com/post?a=1&amp;b=2\" rel=\"nofollow noopener\"><span>example.com/post</span></a> with <span class=\"h-card\"><a href=\"https://example.social/@bob\" class=\"u-url mention\">@<span>bob</span></a></span> <a href=\"https://example.social/tags/rust\" class=\"mention hashtag\" rel=\"tag\">#<span>rust</span></a></p>",
+                        "attachment": [{"type": "Document", "mediaType": "image/png", "name": "A crab"}]
+                    }
+                },
+                {"type": "Announce", "object": "https://other.social/users/carol/statuses/5"},
+                {
+                    "type": "Create",
+                    "object": {
+                        "id": "https://example.social/users/alice/statuses/2",
+                        "type": "Note",
+                        "url": "https://example.social/@alice/2",
+                        "published": "2023-01-10T12:05:00Z",
+                        "summary": "Spoilers",
+                        "inReplyTo": "https://example.social/users/alice/statuses/1",
+                        "content": "<p>More</p>",
+                        "attachment": []
+                    }
+                }
+            ]
+        }"##;
+
+        let posts = parse_outbox(outbox.as_bytes(), Some("Alice (@alice@example.social)")).unwrap();
+        assert_eq!(posts.len(), 2);
+
+        assert_eq!(posts[0].url, "https://example.social/@alice/1");
+        assert_eq!(
+            posts[0].time,
+            Some(OffsetDateTime::from_unix_timestamp(1673352000).unwrap())
+        );
+        assert_eq!(posts[0].links, vec!["https://example.com/post?a=1&b=2"]);
+        assert!(posts[0].text.starts_with("Reading"));
+        assert!(posts[0].text.ends_with("\n[image: A crab]"));
+
+        assert_eq!(posts[1].text, "Spoilers\n\nMore");
+        assert_eq!(
+            posts[1].in_reply_to.as_deref(),
+            Some("https://example.social/users/alice/statuses/1")
+        );
+        assert_eq!(
+            posts[1].author.as_deref(),
+            Some("Alice (@alice@example.social)")
+        );
+    }
+
+    #[test]
+    fn bookmarks() {
+        let bookmarks =
+            r#"{"type": "OrderedCollection", "orderedItems": ["https://other.social/@carol/5"]}"#;
+        let saved = parse_bookmarks(bookmarks.as_bytes()).unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].url, "https://other.social/@carol/5");
+        assert!(saved[0].text.is_empty());
+    }
+}
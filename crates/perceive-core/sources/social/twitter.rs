@@ -0,0 +1,279 @@
+use eyre::{eyre, Context, Result};
+use reqwest::Url;
+use serde::{de::DeserializeOwned, Deserialize};
+use time::OffsetDateTime;
+
+use super::{ArchiveFiles, Post, SocialArchive};
+
+#[derive(Debug, Deserialize)]
+struct AccountEntry {
+    account: Account,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Account {
+    username: String,
+    #[serde(default)]
+    account_display_name: Option<String>,
+}
+
+/// Newer archives wrap each tweet in an object, and older ones don't.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TweetEntry {
+    Wrapped { tweet: Tweet },
+    Bare(Tweet),
+}
+
+#[derive(Debug, Deserialize)]
+struct Tweet {
+    id_str: String,
+    full_text: String,
+    created_at: String,
+    #[serde(default)]
+    in_reply_to_status_id_str: Option<String>,
+    #[serde(default)]
+    entities: Entities,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Entities {
+    #[serde(default)]
+    urls: Vec<UrlEntity>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UrlEntity {
+    /// The shortened t.co URL that appears in the text
+    url: String,
+    expanded_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LikeEntry {
+    like: Like,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Like {
+    tweet_id: String,
+    #[serde(default)]
+    full_text: Option<String>,
+}
+
+/// Read the tweets and likes from a Twitter/X archive.
+pub(super) fn parse_twitter_archive(files: &mut ArchiveFiles) -> Result<SocialArchive> {
+    let account = read_parts::<AccountEntry>(files, &["account"])?
+        .into_iter()
+        .next()
+        .map(|entry| entry.account)
+        .ok_or_else(|| eyre!("The archive does not contain data/account.js"))?;
+
+    // The file was renamed at some point, and large archives split it into parts.
+    let tweets = read_parts::<TweetEntry>(files, &["tweets", "tweet"])?;
+
+    let posts = tweets
+        .into_iter()
+        .filter_map(|entry| {
+            let tweet = match entry {
+                TweetEntry::Wrapped { tweet } => tweet,
+                TweetEntry::Bare(tweet) => tweet,
+            };
+            tweet_post(tweet, &account)
+        })
+        .collect();
+
+    let saved = read_parts::<LikeEntry>(files, &["like"])?
+        .into_iter()
+        .filter_map(|entry| {
+            let like = entry.like;
+            Some(Post {
+                url: format!("https://twitter.com/i/web/status/{}", like.tweet_id),
+                id: like.tweet_id,
+                author: None,
+                time: None,
+                text: unescape(&like.full_text?),
+                in_reply_to: None,
+                links: Vec::new(),
+            })
+        })
+        .collect();
+
+    Ok(SocialArchive { posts, saved })
+}
+
+/// Read the entries from a data file and any additional parts, like `data/tweets.js` followed by
+/// `data/tweets-part1.js`.
+fn read_parts<T: DeserializeOwned>(files: &mut ArchiveFiles, names: &[&str]) -> Result<Vec<T>> {
+    let mut entries = Vec::new();
+    for name in names {
+        for part in 0.. {
+            let path = if part == 0 {
+                format!("data/{name}.js")
+            } else {
+                format!("data/{name}-part{part}.js")
+            };
+
+            let Some(content) = files.read(&path)? else {
+                break;
+            };
+
+            let parsed = parse_js_data::<T>(&content).wrap_err_with(|| eyre!("Reading {path}"))?;
+            entries.extend(parsed);
+        }
+    }
+
+    Ok(entries)
+}
+
+/// The data files are Javascript that assign a JSON array to a variable, like
+/// `window.YTD.tweets.part0 = [...]`.
+fn parse_js_data<T: DeserializeOwned>(content: &[u8]) -> Result<Vec<T>> {
+    let start = content
+        .iter()
+        .position(|&b| b == b'=')
+        .map(|pos| pos + 1)
+        .unwrap_or(0);
+    Ok(serde_json::from_slice(&content[start..])?)
+}
+
+fn tweet_post(tweet: Tweet, account: &Account) -> Option<Post> {
+    // Retweets are someone else's post.
+    if tweet.full_text.starts_with("RT @") {
+        return None;
+    }
+
+    let mut text = tweet.full_text;
+    for url in &tweet.entities.urls {
+        text = text.replace(&url.url, &url.expanded_url);
+    }
+
+    // Links to other tweets can't be read without logging in.
+    let links = tweet
+        .entities
+        .urls
+        .into_iter()
+        .map(|url| url.expanded_url)
+        .filter(|url| {
+            let host = Url::parse(url)
+                .ok()
+                .and_then(|u| u.host_str().map(String::from));
+            !matches!(
+                host.as_deref(),
+                Some("twitter.com" | "www.twitter.com" | "x.com" | "t.co")
+            )
+        })
+        .collect();
+
+    let author = match account.account_display_name.as_deref() {
+        Some(name) if !name.is_empty() => format!("{name} (@{})", account.username),
+        _ => format!("@{}", account.username),
+    };
+
+    Some(Post {
+        url: format!(
+            "https://twitter.com/{}/status/{}",
+            account.username, tweet.id_str
+        ),
+        id: tweet.id_str,
+        author: Some(author),
+        time: parse_created_at(&tweet.created_at),
+        text: unescape(&text),
+        in_reply_to: tweet.in_reply_to_status_id_str,
+        links,
+    })
+}
+
+/// Parse a date like `Tue Jan 10 12:00:00 +0000 2023`.
+fn parse_created_at(date: &str) -> Option<OffsetDateTime> {
+    let format = time::format_description::parse(
+        "[weekday repr:short] [month repr:short] [day] [hour]:[minute]:[second] \
+         [offset_hour sign:mandatory][offset_minute] [year]",
+    )
+    .ok()?;
+    OffsetDateTime::parse(date, &format).ok()
+}
+
+/// Tweet text has `<`, `>`, and `&` escaped as HTML entities.
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tweets() {
+        let tweets = r#"window.YTD.tweets.part0 = [
+  {
+    "tweet" : {
+      "id_str" : "2",
+      "in_reply_to_status_id_str" : "1",
+      "created_at" : "Tue Jan 10 12:05:00 +0000 2023",
+      "full_text" : "See https://t.co/abc &amp; https://t.co/def",
+      "entities" : {
+        "urls" : [
+          { "url" : "https://t.co/abc", "expanded_url" : "https://example.com/post" },
+          { "url" : "https://t.co/def", "expanded_url" : "https://twitter.com/bob/status/9" }
+        ]
+      }
+    }
+  },
+  {
+    "tweet" : {
+      "id_str" : "3",
+      "created_at" : "Tue Jan 10 12:10:00 +0000 2023",
+      "full_text" : "RT @bob: something",
+      "entities" : { "urls" : [] }
+    }
+  }
+]"#;
+
+        let account = Account {
+            username: "alice".to_string(),
+            account_display_name: Some("Alice".to_string()),
+        };
+
+        let posts = parse_js_data::<TweetEntry>(tweets.as_bytes())
+            .unwrap()
+            .into_iter()
+            .filter_map(|entry| match entry {
+                TweetEntry::Wrapped { tweet } => tweet_post(tweet, &account),
+                TweetEntry::Bare(tweet) => tweet_post(tweet, &account),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            posts,
+            vec![Post {
+                id: "2".to_string(),
+                url: "https://twitter.com/alice/status/2".to_string(),
+                author: Some("Alice (@alice)".to_string()),
+                time: Some(OffsetDateTime::from_unix_timestamp(1673352300).unwrap()),
+                text: "See https://example.com/post & https://twitter.com/bob/status/9".to_string(),
+                in_reply_to: Some("1".to_string()),
+                links: vec!["https://example.com/post".to_string()],
+            }]
+        );
+    }
+
+    #[test]
+    fn likes() {
+        let likes = r#"window.YTD.like.part0 = [
+  { "like" : { "tweetId" : "5", "fullText" : "A &lt;good&gt; post", "expandedUrl" : "https://twitter.com/i/web/status/5" } }
+]"#;
+
+        let likes = parse_js_data::<LikeEntry>(likes.as_bytes()).unwrap();
+        assert_eq!(likes.len(), 1);
+        assert_eq!(likes[0].like.tweet_id, "5");
+        assert_eq!(
+            unescape(likes[0].like.full_text.as_deref().unwrap()),
+            "A <good> post"
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use time::{Duration, OffsetDateTime};
+
+/// Sort items by time and split them wherever one is more than `gap` after the previous one.
+/// Items without a time are sorted first and never start a new group.
+pub fn split_by_gap<T>(
+    mut items: Vec<T>,
+    gap: Duration,
+    time: impl Fn(&T) -> Option<OffsetDateTime>,
+) -> Vec<Vec<T>> {
+    items.sort_by_key(&time);
+
+    let mut groups = Vec::new();
+    let mut current: Vec<T> = Vec::new();
+    for item in items {
+        let split = current
+            .last()
+            .and_then(&time)
+            .zip(time(&item))
+            .map_or(false, |(last, time)| time - last > gap);
+        if split {
+            groups.push(std::mem::take(&mut current));
+        }
+        current.push(item);
+    }
+
+    if !current.is_empty() {
+        groups.push(current);
+    }
+
+    groups
+}
+
+/// Format a time like `2023-01-10 12:00:00`, for the text of items built from timestamped
+/// entries.
+pub fn format_time(time: OffsetDateTime) -> String {
+    format!(
+        "{} {:02}:{:02}:{:02}",
+        time.date(),
+        time.hour(),
+        time.minute(),
+        time.second()
+    )
+}
+
+/// The time `minute` minutes after 2023-01-10 12:00 UTC, for test fixtures.
+#[cfg(test)]
+pub fn test_time(minute: i64) -> OffsetDateTime {
+    OffsetDateTime::from_unix_timestamp(1673352000 + minute * 60).unwrap()
+}
+
+/// Run a scanner to completion and collect the items that it sends, in the order they were sent.
+#[cfg(test)]
+pub fn test_scan(scanner: &dyn super::pipeline::SourceScanner) -> Vec<crate::Item> {
+    let (tx, rx) = flume::unbounded();
+    let count = std::sync::atomic::AtomicU64::new(0);
+    scanner
+        .scan(
+            super::pipeline::CountingVecSender::new(&count, tx),
+            &crate::cancel::CancellationToken::new(),
+        )
+        .unwrap();
+
+    rx.into_iter().flatten().collect()
+}
+
+/// The database's view of an item that was indexed with this hash and content, and hasn't
+/// failed to read since.
+#[cfg(test)]
+pub fn test_found_item(hash: &str, content: &str) -> super::pipeline::FoundItem {
+    super::pipeline::FoundItem {
+        hash: hash.to_string(),
+        content: content.to_string(),
+        modified: None,
+        last_accessed: None,
+        skipped: None,
+        has_embedding: true,
+        error_attempts: 0,
+        next_retry_at: None,
+        etag: None,
+        last_modified: None,
+        expires_at: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gaps() {
+        let at = |minute| Some(test_time(minute));
+        let groups = split_by_gap(
+            vec![(40, at(40)), (0, at(0)), (10, at(10)), (5, None)],
+            Duration::minutes(15),
+            |(_, time)| *time,
+        );
+        let groups = groups
+            .iter()
+            .map(|g| g.iter().map(|(n, _)| *n).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(groups, vec![vec![5, 0, 10], vec![40]]);
+    }
+
+    #[test]
+    fn format() {
+        let time = OffsetDateTime::from_unix_timestamp(1673352005).unwrap();
+        assert_eq!(format_time(time), "2023-01-10 12:00:05");
+    }
+}
@@ -0,0 +1,470 @@
+use std::path::{Path, PathBuf};
+
+use ahash::HashMap;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use super::{
+    pipeline::{
+        prefilled_read, CountingVecSender, FoundItem, SourceScanner, SourceScannerReadResult,
+        SCAN_BATCH_SIZE,
+    },
+    ItemCompareStrategy,
+};
+use crate::{cancel::CancellationToken, Item, ItemMetadata};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct VaultConfig {}
+
+/// Reads the notes from an Obsidian or Logseq vault, along with the links and tags between them.
+pub struct VaultScanner {
+    pub source_id: i64,
+    pub location: String,
+    pub config: VaultConfig,
+}
+
+/// A note parsed from a vault.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Note {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    pub aliases: Vec<String>,
+    pub tags: Vec<String>,
+    /// Link targets as written, without the heading, block reference, or display text.
+    pub links: Vec<String>,
+    /// The text of the note, with wikilinks replaced by their display text.
+    pub text: String,
+}
+
+impl Note {
+    fn add_tag(&mut self, tag: &str) {
+        let tag = tag.trim().trim_start_matches('#');
+        if !tag.is_empty() && !self.tags.iter().any(|t| t == tag) {
+            self.tags.push(tag.to_string());
+        }
+    }
+
+    fn add_alias(&mut self, alias: &str) {
+        let alias = alias.trim().trim_start_matches("[[").trim_end_matches("]]");
+        if !alias.is_empty() && !self.aliases.iter().any(|a| a == alias) {
+            self.aliases.push(alias.to_string());
+        }
+    }
+
+    fn add_link(&mut self, target: &str) {
+        let target = target.trim();
+        if !target.is_empty() && !self.links.iter().any(|l| l == target) {
+            self.links.push(target.to_string());
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    /// Get the values, splitting a single string on any of the given separators.
+    fn values(self, separators: &[char]) -> Vec<String> {
+        match self {
+            OneOrMany::One(s) => s.split(separators).map(String::from).collect(),
+            OneOrMany::Many(v) => v,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct NoteAttributes {
+    title: Option<String>,
+    author: Option<String>,
+    description: Option<String>,
+    summary: Option<String>,
+    #[serde(alias = "alias")]
+    aliases: Option<OneOrMany>,
+    #[serde(alias = "tag")]
+    tags: Option<OneOrMany>,
+}
+
+/// Parse a note, including its frontmatter.
+pub fn parse_note(content: &str) -> Note {
+    let parser = gray_matter::Matter::<gray_matter::engine::YAML>::new();
+    let parsed = parser.parse(content);
+    let attributes = parsed
+        .data
+        .and_then(|data| data.deserialize::<NoteAttributes>().ok())
+        .unwrap_or_default();
+
+    let mut note = Note {
+        title: attributes.title,
+        author: attributes.author,
+        description: attributes.description.or(attributes.summary),
+        ..Default::default()
+    };
+
+    for alias in attributes
+        .aliases
+        .map(|a| a.values(&[',']))
+        .unwrap_or_default()
+    {
+        note.add_alias(&alias);
+    }
+    for tag in attributes
+        .tags
+        .map(|t| t.values(&[',', ' ']))
+        .unwrap_or_default()
+    {
+        note.add_tag(&tag);
+    }
+
+    parse_body(&parsed.content, &mut note);
+    note
+}
+
+/// Parse the text of a note, after the frontmatter.
+pub fn parse_body(body: &str, note: &mut Note) {
+    let body = parse_page_properties(body, note);
+
+    let mut text = String::with_capacity(body.len());
+    let mut in_fence = false;
+    for line in body.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let is_fence = trimmed.starts_with("```") || trimmed.starts_with("~~~");
+        if is_fence {
+            in_fence = !in_fence;
+        }
+
+        if in_fence || is_fence {
+            // Don't look for links or tags in code.
+            text.push_str(line);
+        } else {
+            parse_line(line, note, &mut text);
+        }
+    }
+
+    note.text = text;
+}
+
+/// Read Logseq's `key:: value` page properties from the top of the note, and return the rest.
+fn parse_page_properties<'a>(body: &'a str, note: &mut Note) -> &'a str {
+    let mut rest = body;
+    while let Some(line) = rest.lines().next() {
+        let property = line
+            .trim_start_matches(['-', ' '].as_slice())
+            .split_once(":: ")
+            .filter(|(key, _)| {
+                !key.is_empty()
+                    && key
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+            });
+        let Some((key, value)) = property else {
+            break;
+        };
+
+        match key {
+            "title" => note.title = Some(value.trim().to_string()),
+            "alias" | "aliases" => value.split(',').for_each(|a| note.add_alias(a)),
+            "tags" => value.split(',').for_each(|t| {
+                note.add_tag(t.trim().trim_start_matches("[[").trim_end_matches("]]"))
+            }),
+            "description" => note.description = Some(value.trim().to_string()),
+            _ => {}
+        }
+
+        rest = rest[line.len()..].trim_start_matches(['\r', '\n'].as_slice());
+    }
+
+    rest
+}
+
+fn parse_line(line: &str, note: &mut Note, text: &mut String) {
+    let mut rest = line;
+    let mut prev = None;
+    while let Some(c) = rest.chars().next() {
+        if c == '`' {
+            // Inline code, which runs to the next backtick.
+            let end = rest[1..].find('`').map(|i| i + 2).unwrap_or(rest.len());
+            text.push_str(&rest[..end]);
+            rest = &rest[end..];
+            prev = Some('`');
+            continue;
+        }
+
+        let embed = rest.starts_with("![[");
+        if embed || rest.starts_with("[[") {
+            let start = if embed { 3 } else { 2 };
+            if let Some(len) = rest[start..].find("]]") {
+                let inner = &rest[start..start + len];
+                let (target, display) = match inner.split_once('|') {
+                    Some((target, display)) => (target, display),
+                    None => (inner, inner),
+                };
+
+                // Links can point to a heading or block within the note.
+                note.add_link(target.split('#').next().unwrap_or_default());
+                if !embed {
+                    text.push_str(display.trim());
+                }
+
+                rest = &rest[start + len + 2..];
+                prev = Some(']');
+                continue;
+            }
+        }
+
+        let tag_allowed = prev.map_or(true, |p| p.is_whitespace() || p == '(');
+        if c == '#' && tag_allowed {
+            if let Some((tag, len)) = parse_tag(&rest[1..]) {
+                note.add_tag(tag);
+                text.push_str(&rest[..len + 1]);
+                rest = &rest[len + 1..];
+                prev = Some('#');
+                continue;
+            }
+        }
+
+        text.push(c);
+        rest = &rest[c.len_utf8()..];
+        prev = Some(c);
+    }
+}
+
+/// Parse a tag from just after the `#`, returning the tag and its length.
+fn parse_tag(input: &str) -> Option<(&str, usize)> {
+    if let Some(inner) = input.strip_prefix("[[") {
+        // Logseq allows tags with spaces in them, like `#[[two words]]`.
+        let len = inner.find("]]")?;
+        let tag = inner[..len].trim();
+        return (!tag.is_empty()).then_some((tag, len + 4));
+    }
+
+    let len = input
+        .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '/')))
+        .unwrap_or(input.len());
+    let tag = &input[..len];
+
+    // Things like issue numbers aren't tags.
+    tag.chars()
+        .any(|c| !c.is_ascii_digit())
+        .then_some((tag, len))
+}
+
+/// Normalize a link target or note name, so that they can be matched up with each other.
+fn link_key(target: &str) -> String {
+    let target = target.trim().replace('\\', "/").to_lowercase();
+    match target.strip_suffix(".md") {
+        Some(target) => target.to_string(),
+        None => target,
+    }
+}
+
+struct ScannedNote {
+    path: PathBuf,
+    meta: std::fs::Metadata,
+    note: Note,
+}
+
+impl VaultScanner {
+    fn read_notes(&self, cancel: &CancellationToken) -> Vec<ScannedNote> {
+        let root = Path::new(&self.location);
+        // Hidden directories like `.obsidian` and `.trash` are skipped by default.
+        let walker = ignore::WalkBuilder::new(root)
+            .filter_entry(|entry| {
+                // Logseq keeps its config and backups of the pages here.
+                !(entry.depth() == 1 && entry.file_name() == "logseq")
+            })
+            .build();
+
+        let mut notes = Vec::new();
+        for entry in walker {
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            let Ok(entry) = entry else {
+                continue;
+            };
+
+            let path = entry.path();
+            let is_markdown = path
+                .extension()
+                .map_or(false, |ext| ext.eq_ignore_ascii_case("md"));
+            if !is_markdown {
+                continue;
+            }
+
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            let Ok(content) = std::fs::read_to_string(path) else {
+                continue;
+            };
+
+            notes.push(ScannedNote {
+                path: path.to_path_buf(),
+                meta,
+                note: parse_note(&content),
+            });
+        }
+
+        notes
+    }
+}
+
+impl SourceScanner for VaultScanner {
+    fn scan(
+        &self,
+        output: CountingVecSender<Item>,
+        cancel: &CancellationToken,
+    ) -> Result<(), eyre::Report> {
+        let root = Path::new(&self.location);
+        let notes = self.read_notes(cancel);
+
+        // Links can use the note's name, its path within the vault, or one of its aliases.
+        // Names are added last so that they win over aliases.
+        let mut targets = HashMap::default();
+        for scanned in &notes {
+            for alias in &scanned.note.aliases {
+                targets.insert(link_key(alias), &scanned.path);
+            }
+        }
+        for scanned in &notes {
+            if let Ok(relative) = scanned.path.strip_prefix(root) {
+                targets.insert(link_key(&relative.to_string_lossy()), &scanned.path);
+            }
+            if let Some(stem) = scanned.path.file_stem() {
+                targets
+                    .entry(link_key(&stem.to_string_lossy()))
+                    .or_insert(&scanned.path);
+            }
+        }
+
+        let items = notes.iter().map(|scanned| {
+            let links = scanned
+                .note
+                .links
+                .iter()
+                .filter_map(|target| {
+                    let key = link_key(target);
+                    targets.get(&key).or_else(|| {
+                        // A path that doesn't match may still end with the note's name.
+                        let name = key.rsplit('/').next()?;
+                        targets.get(name)
+                    })
+                })
+                .copied()
+                .filter(|path| *path != &scanned.path)
+                .map(|path| path.to_string_lossy().to_string())
+                .unique()
+                .collect();
+
+            let title = scanned.note.title.clone().or_else(|| {
+                scanned
+                    .path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+            });
+
+            Item {
+                id: -1,
+                source_id: self.source_id,
+                external_id: scanned.path.to_string_lossy().to_string(),
+                hash: None,
+                content: Some(scanned.note.text.clone()).filter(|t| !t.trim().is_empty()),
+                raw_content: None,
+                skipped: None,
+                process_version: 0,
+                metadata: ItemMetadata {
+                    name: title,
+                    author: scanned.note.author.clone(),
+                    description: scanned.note.description.clone(),
+                    mtime: scanned.meta.modified().ok().map(OffsetDateTime::from),
+                    atime: scanned.meta.accessed().ok().map(OffsetDateTime::from),
+                    created: None,
+                    http_cache: None,
+                    tags: Some(scanned.note.tags.clone()),
+                    links: Some(links),
+                },
+            }
+        });
+
+        for batch in &items.chunks(SCAN_BATCH_SIZE) {
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            output.send(batch.collect())?;
+        }
+
+        Ok(())
+    }
+
+    fn read(
+        &self,
+        existing: Option<&FoundItem>,
+        compare_strategy: ItemCompareStrategy,
+        item: &mut Item,
+    ) -> Result<SourceScannerReadResult, eyre::Report> {
+        // The note was already read during the scan, since the links can't be resolved without
+        // seeing every note.
+        Ok(prefilled_read(existing, compare_strategy, item))
+    }
+
+    fn latest_process_version(&self) -> i32 {
+        0
+    }
+
+    fn reprocess(&self, _item: &mut Item) -> Result<SourceScannerReadResult, eyre::Report> {
+        Ok(SourceScannerReadResult::Unchanged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(body: &str) -> Note {
+        let mut note = Note::default();
+        parse_body(body, &mut note);
+        note
+    }
+
+    #[test]
+    fn wikilinks() {
+        let note =
+            parse("See [[Other Note]] and [[folder/Third#Heading|the third]].\n![[image.png]]\n");
+        assert_eq!(note.links, vec!["Other Note", "folder/Third", "image.png"]);
+        assert_eq!(note.text, "See Other Note and the third.\n\n");
+    }
+
+    #[test]
+    fn inline_tags() {
+        let note = parse(
+            "# Heading\n#project/perceive and #idea, not issue #123 or http://x.com/#anchor #[[two words]]\n",
+        );
+        assert_eq!(note.tags, vec!["project/perceive", "idea", "two words"]);
+    }
+
+    #[test]
+    fn ignores_code() {
+        let note = parse("`[[not a link]]` #tag\n```\n#not-a-tag [[nope]]\n```\n");
+        assert_eq!(note.tags, vec!["tag"]);
+        assert!(note.links.is_empty());
+    }
+
+    #[test]
+    fn logseq_properties() {
+        let note =
+            parse("title:: My Page\nalias:: First, [[Second]]\ntags:: [[a]], b\n\n- Content\n");
+        assert_eq!(note.title.as_deref(), Some("My Page"));
+        assert_eq!(note.aliases, vec!["First", "Second"]);
+        assert_eq!(note.tags, vec!["a", "b"]);
+        assert_eq!(note.text, "- Content\n");
+    }
+}
@@ -0,0 +1,764 @@
+use std::{
+    io::{BufRead, BufReader, Read},
+    path::Path,
+};
+
+use ahash::HashMap;
+use eyre::{eyre, Context, Result};
+use flate2::read::{GzDecoder, MultiGzDecoder, ZlibDecoder};
+use mail_parser::{MessageParser, MimeHeaders, PartType};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use super::{
+    parse_html::{reprocess_html_article, should_skip, HTML_PROCESS_VERSION},
+    pipeline::{
+        content_hash, CountingVecSender, FoundItem, SourceScanner, SourceScannerReadResult,
+        SCAN_BATCH_SIZE,
+    },
+    ItemCompareStrategy,
+};
+use crate::{batch_sender::BatchSender, cancel::CancellationToken, Item, ItemMetadata};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct WebArchiveConfig {
+    /// Domains that we should never index
+    #[serde(default)]
+    pub skip: Vec<String>,
+}
+
+/// Reads the pages saved in web archives: WARC files, such as those written by
+/// `wget --warc-file`, pages saved with SingleFile, and MHTML files saved from a browser.
+/// The location can be a single archive or a directory of them. Nothing is fetched from the
+/// network, so pages that have since disappeared from the web stay searchable.
+pub struct WebArchiveScanner {
+    pub source_id: i64,
+    pub location: String,
+    pub config: WebArchiveConfig,
+}
+
+/// A page captured in an archive.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Capture {
+    pub url: String,
+    pub captured: Option<OffsetDateTime>,
+    pub html: Vec<u8>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ArchiveKind {
+    Warc,
+    CompressedWarc,
+    Mhtml,
+    Html,
+}
+
+impl ArchiveKind {
+    fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        if name.ends_with(".warc") {
+            Some(Self::Warc)
+        } else if name.ends_with(".warc.gz") {
+            Some(Self::CompressedWarc)
+        } else if name.ends_with(".mhtml") || name.ends_with(".mht") {
+            Some(Self::Mhtml)
+        } else if name.ends_with(".html") || name.ends_with(".htm") {
+            Some(Self::Html)
+        } else {
+            None
+        }
+    }
+}
+
+impl WebArchiveScanner {
+    /// The URL that identifies a captured page, or None if the page shouldn't be indexed.
+    fn page_url(&self, captured_url: &str) -> Option<String> {
+        let mut url = Url::parse(captured_url).ok()?;
+        if !matches!(url.scheme(), "http" | "https") || should_skip(&self.config.skip, &url) {
+            return None;
+        }
+
+        url.set_fragment(None);
+        Some(url.to_string())
+    }
+
+    fn archive_files(&self) -> Vec<(std::path::PathBuf, ArchiveKind)> {
+        let root = Path::new(&self.location);
+        if root.is_file() {
+            return ArchiveKind::from_path(root)
+                .map(|kind| vec![(root.to_path_buf(), kind)])
+                .unwrap_or_default();
+        }
+
+        ignore::WalkBuilder::new(root)
+            .build()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().map_or(false, |t| t.is_file()))
+            .filter_map(|entry| {
+                let kind = ArchiveKind::from_path(entry.path())?;
+                Some((entry.into_path(), kind))
+            })
+            .collect()
+    }
+
+    fn read_archive(
+        &self,
+        path: &Path,
+        kind: ArchiveKind,
+        cancel: &CancellationToken,
+        mut add: impl FnMut(Capture) -> Result<()>,
+    ) -> Result<()> {
+        let file =
+            std::fs::File::open(path).wrap_err_with(|| eyre!("Opening {}", path.display()))?;
+        let file_mtime = file
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .map(OffsetDateTime::from);
+
+        match kind {
+            ArchiveKind::Warc | ArchiveKind::CompressedWarc => {
+                let reader: Box<dyn BufRead> = if kind == ArchiveKind::CompressedWarc {
+                    // Each record is compressed separately, so the file is a series of gzip
+                    // members.
+                    Box::new(BufReader::new(MultiGzDecoder::new(BufReader::new(file))))
+                } else {
+                    Box::new(BufReader::new(file))
+                };
+
+                let mut warc = WarcReader::new(reader);
+                while let Some(capture) = warc
+                    .next_capture()
+                    .wrap_err_with(|| eyre!("Reading {}", path.display()))?
+                {
+                    if cancel.is_cancelled() {
+                        break;
+                    }
+                    add(capture)?;
+                }
+            }
+            ArchiveKind::Mhtml => {
+                let mut contents = Vec::new();
+                BufReader::new(file).read_to_end(&mut contents)?;
+                if let Some(mut capture) = parse_mhtml(&contents) {
+                    capture.captured = capture.captured.or(file_mtime);
+                    add(capture)?;
+                }
+            }
+            ArchiveKind::Html => {
+                let mut contents = Vec::new();
+                BufReader::new(file).read_to_end(&mut contents)?;
+                // Other HTML files don't record where they came from.
+                if let Some(mut capture) = parse_singlefile(contents) {
+                    capture.captured = capture.captured.or(file_mtime);
+                    add(capture)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SourceScanner for WebArchiveScanner {
+    fn scan(&self, output: CountingVecSender<Item>, cancel: &CancellationToken) -> Result<()> {
+        // The same page is often captured more than once, so only the latest capture is kept.
+        // The archives are read twice, first to find the time of each page's latest capture and
+        // then to send those captures, so that the pages don't all have to be held in memory.
+        let archives = self.archive_files();
+        let mut latest: HashMap<String, Option<OffsetDateTime>> = HashMap::default();
+
+        for (path, kind) in &archives {
+            if cancel.is_cancelled() {
+                return Ok(());
+            }
+
+            let result = self.read_archive(path, *kind, cancel, |capture| {
+                if let Some(url) = self.page_url(&capture.url) {
+                    let captured = latest.entry(url).or_insert(capture.captured);
+                    *captured = (*captured).max(capture.captured);
+                }
+                Ok(())
+            });
+
+            // A damaged archive shouldn't stop the others from being indexed. The pages read
+            // before the damage are still used.
+            if let Err(e) = result {
+                eprintln!("Skipping the rest of {}: {e:#}", path.display());
+            }
+        }
+
+        let sender = BatchSender::new(SCAN_BATCH_SIZE, output);
+        let mut send_error = None;
+        for (path, kind) in &archives {
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            // Errors reading the archive were already reported in the first pass.
+            self.read_archive(path, *kind, cancel, |capture| {
+                let Some(url) = self.page_url(&capture.url) else {
+                    return Ok(());
+                };
+
+                // Only send the latest capture, and only once if there are several from that time.
+                if latest.get(&url) != Some(&capture.captured) {
+                    return Ok(());
+                }
+                latest.remove(&url);
+
+                let raw_content = zstd::encode_all(capture.html.as_slice(), 3)?;
+                let hash = content_hash(&[&String::from_utf8_lossy(&capture.html)]);
+                let item = Item {
+                    id: -1,
+                    source_id: self.source_id,
+                    external_id: url,
+                    hash: Some(hash),
+                    content: None,
+                    raw_content: Some(raw_content),
+                    skipped: None,
+                    process_version: HTML_PROCESS_VERSION,
+                    metadata: ItemMetadata {
+                        mtime: capture.captured,
+                        ..Default::default()
+                    },
+                };
+
+                if let Err(e) = sender.add(item) {
+                    send_error = Some(e);
+                    return Err(eyre!("Scan output closed"));
+                }
+
+                Ok(())
+            })
+            .ok();
+
+            if let Some(e) = send_error.take() {
+                return Err(e.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read(
+        &self,
+        existing: Option<&FoundItem>,
+        compare_strategy: ItemCompareStrategy,
+        item: &mut Item,
+    ) -> Result<SourceScannerReadResult> {
+        // The HTML was pulled out of the archive while scanning, and is extracted the same way
+        // as when reprocessing.
+        if item.raw_content.is_none() {
+            return Ok(SourceScannerReadResult::Omit);
+        }
+
+        // The hash covers the captured HTML, so there's nothing to extract if it's the same
+        // capture as last time.
+        let unchanged = compare_strategy != ItemCompareStrategy::Force
+            && existing.map_or(false, |e| Some(e.hash.as_str()) == item.hash.as_deref());
+        if unchanged {
+            return Ok(SourceScannerReadResult::Unchanged);
+        }
+
+        reprocess_html_article(item)
+    }
+
+    fn latest_process_version(&self) -> i32 {
+        HTML_PROCESS_VERSION
+    }
+
+    fn reprocess(&self, item: &mut Item) -> Result<SourceScannerReadResult> {
+        reprocess_html_article(item)
+    }
+}
+
+/// Reads the HTML pages out of a WARC file.
+pub struct WarcReader<R: BufRead> {
+    reader: R,
+}
+
+impl<R: BufRead> WarcReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+
+    /// Read records until one holds a successfully fetched HTML page. Returns None at the end
+    /// of the file.
+    pub fn next_capture(&mut self) -> Result<Option<Capture>> {
+        loop {
+            let Some(headers) = self.read_headers()? else {
+                return Ok(None);
+            };
+
+            let header = |name: &str| {
+                headers
+                    .iter()
+                    .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                    .map(|(_, v)| v.as_str())
+            };
+
+            let length = header("Content-Length")
+                .and_then(|l| l.parse::<u64>().ok())
+                .ok_or_else(|| eyre!("WARC record is missing its Content-Length"))?;
+            let content_type = header("Content-Type").unwrap_or_default();
+
+            // Responses hold the HTTP response as it was received, and resources hold just the
+            // document.
+            let wanted = match header("WARC-Type") {
+                Some("response") => content_type.starts_with("application/http"),
+                Some("resource") => is_html_type(content_type),
+                _ => false,
+            };
+
+            let url = header("WARC-Target-URI")
+                // WARC 1.0 showed the URI in angle brackets, and some tools followed it.
+                .map(|u| u.trim_start_matches('<').trim_end_matches('>').to_string());
+            let captured =
+                header("WARC-Date").and_then(|d| OffsetDateTime::parse(d, &Rfc3339).ok());
+            let is_response = header("WARC-Type") == Some("response");
+
+            if !wanted || url.is_none() {
+                std::io::copy(&mut (&mut self.reader).take(length), &mut std::io::sink())?;
+                continue;
+            }
+
+            // The buffer grows as the data arrives, rather than trusting the length in the
+            // header for its size.
+            let mut block = Vec::new();
+            (&mut self.reader).take(length).read_to_end(&mut block)?;
+            if block.len() as u64 != length {
+                return Err(eyre!("WARC record was cut off"));
+            }
+
+            let html = if is_response {
+                parse_http_response(&block)
+            } else {
+                Some(block)
+            };
+
+            if let Some(html) = html {
+                return Ok(Some(Capture {
+                    url: url.unwrap_or_default(),
+                    captured,
+                    html,
+                }));
+            }
+        }
+    }
+
+    /// Read the header of the next record, skipping the blank lines that end the previous one.
+    fn read_headers(&mut self) -> Result<Option<Vec<(String, String)>>> {
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                return Ok(None);
+            }
+
+            if !line.iter().all(u8::is_ascii_whitespace) {
+                break;
+            }
+        }
+
+        if !line.starts_with(b"WARC/") {
+            return Err(eyre!("Expected a WARC record"));
+        }
+
+        let mut headers = Vec::new();
+        loop {
+            line.clear();
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                return Err(eyre!("WARC record was cut off"));
+            }
+
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_string(), value.trim().to_string()));
+            }
+        }
+
+        Ok(Some(headers))
+    }
+}
+
+fn is_html_type(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    mime.eq_ignore_ascii_case("text/html") || mime.eq_ignore_ascii_case("application/xhtml+xml")
+}
+
+/// Get the body of a raw HTTP response, if it was a successful response with an HTML page.
+fn parse_http_response(response: &[u8]) -> Option<Vec<u8>> {
+    let (head_len, sep_len) = find_subslice(response, b"\r\n\r\n")
+        .map(|pos| (pos, 4))
+        .or_else(|| find_subslice(response, b"\n\n").map(|pos| (pos, 2)))?;
+    let head = String::from_utf8_lossy(&response[..head_len]);
+    let body = &response[head_len + sep_len..];
+
+    let mut lines = head.lines();
+    let status = lines.next()?.split_whitespace().nth(1)?;
+    if status != "200" {
+        return None;
+    }
+
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim(), value.trim()))
+        .collect::<Vec<_>>();
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| *v)
+    };
+
+    if !is_html_type(header("Content-Type").unwrap_or_default()) {
+        return None;
+    }
+
+    let chunked =
+        header("Transfer-Encoding").map_or(false, |te| te.to_ascii_lowercase().contains("chunked"));
+    let body = if chunked {
+        dechunk(body)?
+    } else {
+        body.to_vec()
+    };
+
+    let mut decoded = Vec::new();
+    match header("Content-Encoding")
+        .map(|e| e.to_ascii_lowercase())
+        .as_deref()
+    {
+        None | Some("identity") => return Some(body),
+        Some("gzip") | Some("x-gzip") => GzDecoder::new(body.as_slice())
+            .read_to_end(&mut decoded)
+            .ok()?,
+        Some("deflate") => ZlibDecoder::new(body.as_slice())
+            .read_to_end(&mut decoded)
+            .ok()?,
+        // Brotli and other encodings aren't supported.
+        Some(_) => return None,
+    };
+
+    Some(decoded)
+}
+
+/// Decode a body sent with `Transfer-Encoding: chunked`.
+fn dechunk(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(body.len());
+    loop {
+        let line_end = find_subslice(body, b"\n")?;
+        let size_line = std::str::from_utf8(&body[..line_end]).ok()?;
+        // Chunk extensions come after a semicolon.
+        let size = size_line.split(';').next()?.trim();
+        let size = usize::from_str_radix(size, 16).ok()?;
+        body = &body[line_end + 1..];
+
+        if size == 0 {
+            return Some(output);
+        }
+
+        output.extend_from_slice(body.get(..size)?);
+        body = &body[size..];
+        body = body
+            .strip_prefix(b"\r\n")
+            .or_else(|| body.strip_prefix(b"\n"))
+            .unwrap_or(body);
+    }
+}
+
+fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Get the page from an MHTML file. Chrome records the page's URL in
+/// `Snapshot-Content-Location`, and other browsers only put it on the HTML part.
+pub fn parse_mhtml(contents: &[u8]) -> Option<Capture> {
+    let message = MessageParser::default().parse(contents)?;
+    let part = message.html_part(0)?;
+    let PartType::Html(html) = &part.body else {
+        return None;
+    };
+
+    let url = message
+        .header("Snapshot-Content-Location")
+        .and_then(|h| h.as_text())
+        .or_else(|| part.content_location())?;
+
+    Some(Capture {
+        url: url.trim().to_string(),
+        captured: message
+            .date()
+            .and_then(|d| OffsetDateTime::from_unix_timestamp(d.to_timestamp()).ok()),
+        html: html.as_bytes().to_vec(),
+    })
+}
+
+/// SingleFile writes a comment like this near the top of the page:
+///
+/// ```text
+/// <!--
+///  Page saved with SingleFile
+///  url: https://example.com/
+///  saved date: Tue Jan 10 2023 14:32:07 GMT+0100 (Central European Standard Time)
+/// -->
+/// ```
+///
+/// Returns None for HTML files that weren't saved by SingleFile.
+pub fn parse_singlefile(html: Vec<u8>) -> Option<Capture> {
+    let head = &html[..html.len().min(4096)];
+    let head = String::from_utf8_lossy(head);
+    let comment_start = head.find("Page saved with SingleFile")?;
+    let comment = &head[comment_start..];
+    let comment = &comment[..comment.find("-->").unwrap_or(comment.len())];
+
+    let field = |name: &str| {
+        comment
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(name))
+            .map(|value| value.trim())
+    };
+
+    let url = field("url:")?.to_string();
+    let captured = field("saved date:").and_then(parse_js_date);
+
+    Some(Capture {
+        url,
+        captured,
+        html,
+    })
+}
+
+/// Parse a date as formatted by Javascript's `Date.toString`, such as
+/// `Tue Jan 10 2023 14:32:07 GMT+0100 (Central European Standard Time)`.
+fn parse_js_date(date: &str) -> Option<OffsetDateTime> {
+    let date = date.split(" (").next()?;
+    let format = time::format_description::parse(
+        "[weekday repr:short] [month repr:short] [day] [year] [hour]:[minute]:[second] \
+         GMT[offset_hour sign:mandatory][offset_minute]",
+    )
+    .ok()?;
+    OffsetDateTime::parse(date, &format).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+    use crate::sources::timeline::{test_found_item, test_scan};
+
+    fn warc_record(headers: &str, block: &[u8]) -> Vec<u8> {
+        let mut record = format!(
+            "WARC/1.1\r\n{headers}Content-Length: {}\r\n\r\n",
+            block.len()
+        )
+        .into_bytes();
+        record.extend_from_slice(block);
+        record.extend_from_slice(b"\r\n\r\n");
+        record
+    }
+
+    #[test]
+    fn warc_records() {
+        let chunked_response = b"HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nTransfer-Encoding: chunked\r\n\r\n6\r\n<p>one\r\n5;ext=1\r\n</p>x\r\n0\r\n\r\n";
+        let mut gzipped = GzEncoder::new(Vec::new(), Compression::default());
+        gzipped.write_all(b"<p>two</p>").unwrap();
+        let mut gzip_response =
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Encoding: gzip\r\n\r\n"
+                .to_vec();
+        gzip_response.extend(gzipped.finish().unwrap());
+
+        let mut warc = Vec::new();
+        warc.extend(warc_record(
+            "WARC-Type: warcinfo\r\nContent-Type: application/warc-fields\r\n",
+            b"software: Wget/1.21\r\n",
+        ));
+        warc.extend(warc_record(
+            "WARC-Type: request\r\nWARC-Target-URI: https://example.com/one\r\nContent-Type: application/http; msgtype=request\r\n",
+            b"GET /one HTTP/1.1\r\n\r\n",
+        ));
+        warc.extend(warc_record(
+            "WARC-Type: response\r\nWARC-Target-URI: <https://example.com/one>\r\nWARC-Date: 2023-01-10T12:00:00Z\r\nContent-Type: application/http; msgtype=response\r\n",
+            chunked_response,
+        ));
+        warc.extend(warc_record(
+            "WARC-Type: response\r\nWARC-Target-URI: https://example.com/missing\r\nContent-Type: application/http; msgtype=response\r\n",
+            b"HTTP/1.1 404 Not Found\r\nContent-Type: text/html\r\n\r\nNot here",
+        ));
+        warc.extend(warc_record(
+            "WARC-Type: response\r\nWARC-Target-URI: https://example.com/logo.png\r\nContent-Type: application/http; msgtype=response\r\n",
+            b"HTTP/1.1 200 OK\r\nContent-Type: image/png\r\n\r\n\x89PNG",
+        ));
+        warc.extend(warc_record(
+            "WARC-Type: response\r\nWARC-Target-URI: https://example.com/two\r\nContent-Type: application/http; msgtype=response\r\n",
+            &gzip_response,
+        ));
+
+        let mut reader = WarcReader::new(warc.as_slice());
+        let one = reader.next_capture().unwrap().unwrap();
+        assert_eq!(one.url, "https://example.com/one");
+        assert_eq!(
+            one.captured,
+            Some(OffsetDateTime::from_unix_timestamp(1673352000).unwrap())
+        );
+        assert_eq!(one.html, b"<p>one</p>x");
+
+        let two = reader.next_capture().unwrap().unwrap();
+        assert_eq!(two.url, "https://example.com/two");
+        assert_eq!(two.captured, None);
+        assert_eq!(two.html, b"<p>two</p>");
+
+        assert_eq!(reader.next_capture().unwrap(), None);
+    }
+
+    #[test]
+    fn mhtml() {
+        let mhtml = "From: <Saved by Blink>\r\n\
+            Snapshot-Content-Location: https://example.com/article\r\n\
+            Subject: An article\r\n\
+            Date: Tue, 10 Jan 2023 12:00:00 -0000\r\n\
+            MIME-Version: 1.0\r\n\
+            Content-Type: multipart/related;\r\n\
+            \ttype=\"text/html\";\r\n\
+            \tboundary=\"----MultipartBoundary--abc----\"\r\n\
+            \r\n\
+            \r\n\
+            ------MultipartBoundary--abc----\r\n\
+            Content-Type: text/html\r\n\
+            Content-ID: <frame-1@mhtml.blink>\r\n\
+            Content-Transfer-Encoding: quoted-printable\r\n\
+            Content-Location: https://example.com/article\r\n\
+            \r\n\
+            <html><body><p class=3D\"text\">Hello</p></body></html>\r\n\
+            ------MultipartBoundary--abc----\r\n\
+            Content-Type: text/css\r\n\
+            Content-Location: https://example.com/style.css\r\n\
+            \r\n\
+            p { color: red; }\r\n\
+            ------MultipartBoundary--abc------\r\n";
+
+        let capture = parse_mhtml(mhtml.as_bytes()).unwrap();
+        assert_eq!(capture.url, "https://example.com/article");
+        assert_eq!(
+            capture.captured,
+            Some(OffsetDateTime::from_unix_timestamp(1673352000).unwrap())
+        );
+        assert!(String::from_utf8(capture.html)
+            .unwrap()
+            .contains(r#"<p class="text">Hello</p>"#));
+    }
+
+    #[test]
+    fn singlefile() {
+        let html = "<!DOCTYPE html> <html lang=\"en\"><!--\n Page saved with SingleFile \n url: https://example.com/post \n saved date: Tue Jan 10 2023 13:00:00 GMT+0100 (Central European Standard Time)\n--><head><title>Post</title></head></html>";
+        let capture = parse_singlefile(html.as_bytes().to_vec()).unwrap();
+        assert_eq!(capture.url, "https://example.com/post");
+        assert_eq!(
+            capture.captured,
+            Some(OffsetDateTime::from_unix_timestamp(1673352000).unwrap())
+        );
+
+        assert_eq!(
+            parse_singlefile(b"<html><body>Not saved</body></html>".to_vec()),
+            None
+        );
+    }
+
+    #[test]
+    fn rescan_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        let html = format!(
+            "<html><!--\n Page saved with SingleFile \n url: https://example.com/post \n--><head><title>Post</title></head><body><article><p>{}</p></article></body></html>",
+            "Some words in the saved page. ".repeat(20)
+        );
+        std::fs::write(dir.path().join("post.html"), html).unwrap();
+
+        let scanner = WebArchiveScanner {
+            source_id: 1,
+            location: dir.path().display().to_string(),
+            config: WebArchiveConfig::default(),
+        };
+        let scan = || test_scan(&scanner).into_iter().next().unwrap();
+
+        let mut item = scan();
+        let result = scanner
+            .read(None, ItemCompareStrategy::MTimeAndContent, &mut item)
+            .unwrap();
+        assert!(matches!(result, SourceScannerReadResult::Found));
+
+        let existing = test_found_item(
+            item.hash.as_deref().unwrap(),
+            item.content.as_deref().unwrap_or_default(),
+        );
+
+        // Scanning the same archive again doesn't extract the page again.
+        let mut rescanned = scan();
+        let result = scanner
+            .read(
+                Some(&existing),
+                ItemCompareStrategy::MTimeAndContent,
+                &mut rescanned,
+            )
+            .unwrap();
+        assert!(matches!(result, SourceScannerReadResult::Unchanged));
+        assert!(rescanned.content.is_none());
+
+        let result = scanner
+            .read(Some(&existing), ItemCompareStrategy::Force, &mut rescanned)
+            .unwrap();
+        assert!(matches!(result, SourceScannerReadResult::Found));
+    }
+
+    #[test]
+    fn keeps_latest_capture_and_skips_damaged_archives() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let resource = |date: &str, html: &[u8]| {
+            warc_record(
+                &format!("WARC-Type: resource\r\nWARC-Target-URI: https://example.com/one\r\nWARC-Date: {date}\r\nContent-Type: text/html\r\n"),
+                html,
+            )
+        };
+        let mut warc = resource("2023-01-10T12:00:00Z", b"<p>old</p>");
+        warc.extend(resource("2023-01-11T12:00:00Z", b"<p>new</p>"));
+        std::fs::write(dir.path().join("one.warc"), warc).unwrap();
+
+        // This record claims to be longer than what is in the file.
+        let mut damaged = resource("2023-01-12T12:00:00Z", b"<p>newest</p>");
+        damaged.truncate(damaged.len() - 10);
+        std::fs::write(dir.path().join("damaged.warc"), damaged).unwrap();
+
+        let html = "<html><!--\n Page saved with SingleFile \n url: https://example.com/post \n--><body></body></html>";
+        std::fs::write(dir.path().join("post.html"), html).unwrap();
+
+        let scanner = WebArchiveScanner {
+            source_id: 1,
+            location: dir.path().display().to_string(),
+            config: WebArchiveConfig::default(),
+        };
+        let mut items = test_scan(&scanner);
+        items.sort_by(|a, b| a.external_id.cmp(&b.external_id));
+        let urls = items
+            .iter()
+            .map(|item| item.external_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            urls,
+            ["https://example.com/one", "https://example.com/post"]
+        );
+
+        let html = zstd::decode_all(items[0].raw_content.as_deref().unwrap()).unwrap();
+        assert_eq!(html, b"<p>new</p>");
+    }
+}
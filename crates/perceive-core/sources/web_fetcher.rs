@@ -0,0 +1,226 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use ahash::HashMap;
+use once_cell::sync::OnceCell;
+use parking_lot::{Condvar, Mutex};
+use reqwest::{blocking::Client, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+
+use super::robots::RobotsTxt;
+
+pub const USER_AGENT: &str = "perceive-search";
+
+/// How many redirects to follow when fetching robots.txt. Sites often redirect it to https or
+/// to a `www.` host, even when pages aren't fetched with redirects.
+const ROBOTS_MAX_REDIRECTS: usize = 5;
+
+/// Settings for how politely a source fetches web pages.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FetchConfig {
+    /// The most requests to run at once against a single host.
+    pub max_per_host: usize,
+    /// The minimum time between starting requests to the same host, in milliseconds.
+    pub host_delay_ms: u64,
+    /// Skip pages that the site's robots.txt disallows.
+    pub respect_robots_txt: bool,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        Self {
+            max_per_host: 2,
+            host_delay_ms: 1000,
+            respect_robots_txt: true,
+        }
+    }
+}
+
+#[derive(Default)]
+struct HostState {
+    active: usize,
+    next_start: Option<Instant>,
+}
+
+/// An HTTP client shared by the read threads of a source, which limits how hard each host
+/// is hit and checks robots.txt before fetching.
+pub struct WebFetcher {
+    client: Client,
+    robots_client: Client,
+    config: FetchConfig,
+    hosts: Mutex<HashMap<String, HostState>>,
+    host_released: Condvar,
+    robots: Mutex<HashMap<String, Arc<OnceCell<RobotsTxt>>>>,
+}
+
+/// Holds one of a host's request slots until dropped.
+pub struct HostPermit<'a> {
+    fetcher: &'a WebFetcher,
+    host: String,
+}
+
+impl<'a> Drop for HostPermit<'a> {
+    fn drop(&mut self) {
+        let mut hosts = self.fetcher.hosts.lock();
+        if let Some(state) = hosts.get_mut(&self.host) {
+            state.active -= 1;
+        }
+        self.fetcher.host_released.notify_all();
+    }
+}
+
+impl WebFetcher {
+    pub fn new(config: FetchConfig, follow_redirects: bool) -> Result<WebFetcher, eyre::Report> {
+        let builder = || {
+            Client::builder()
+                .user_agent(USER_AGENT)
+                .gzip(true)
+                .timeout(Duration::from_secs(30))
+        };
+
+        let client = if follow_redirects {
+            builder().build()?
+        } else {
+            builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .build()?
+        };
+
+        let robots_client = builder()
+            .redirect(reqwest::redirect::Policy::limited(ROBOTS_MAX_REDIRECTS))
+            .build()?;
+
+        Ok(WebFetcher {
+            client,
+            robots_client,
+            config,
+            hosts: Mutex::new(HashMap::default()),
+            host_released: Condvar::new(),
+            robots: Mutex::new(HashMap::default()),
+        })
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Wait until a request to the URL's host is allowed to start. The request should be
+    /// finished before the returned permit is dropped.
+    pub fn acquire(&self, url: &Url) -> HostPermit<'_> {
+        let host = url.host_str().unwrap_or_default().to_string();
+        let delay = Duration::from_millis(self.config.host_delay_ms);
+        let max_per_host = self.config.max_per_host.max(1);
+
+        let mut hosts = self.hosts.lock();
+        loop {
+            let now = Instant::now();
+            let state = hosts.entry(host.clone()).or_default();
+
+            let ready_at = state.next_start.filter(|t| *t > now);
+            let full = state.active >= max_per_host;
+            match (full, ready_at) {
+                (false, None) => {
+                    state.active += 1;
+                    state.next_start = Some(now + delay);
+                    break;
+                }
+                // Only waiting on the delay, so sleep until then.
+                (false, Some(ready_at)) => {
+                    self.host_released.wait_until(&mut hosts, ready_at);
+                }
+                // Waiting for another request to this host to finish.
+                (true, _) => {
+                    self.host_released.wait(&mut hosts);
+                }
+            }
+        }
+
+        HostPermit {
+            fetcher: self,
+            host,
+        }
+    }
+
+    /// Check if the URL may be fetched, according to the site's robots.txt. The robots.txt for
+    /// each site is fetched once and cached for the lifetime of the fetcher.
+    pub fn allowed(&self, url: &Url) -> bool {
+        if !self.config.respect_robots_txt {
+            return true;
+        }
+
+        let origin = url.origin().ascii_serialization();
+        let cell = self
+            .robots
+            .lock()
+            .entry(origin.clone())
+            .or_default()
+            .clone();
+
+        let robots = cell.get_or_init(|| self.fetch_robots(&origin, url));
+
+        let path = match url.query() {
+            Some(query) => format!("{}?{query}", url.path()),
+            None => url.path().to_string(),
+        };
+
+        robots.is_allowed(&path)
+    }
+
+    fn fetch_robots(&self, origin: &str, url: &Url) -> RobotsTxt {
+        let _permit = self.acquire(url);
+        let response = self
+            .robots_client
+            .get(format!("{origin}/robots.txt"))
+            .send();
+
+        // If the file can't be read for any reason, assume everything is allowed.
+        match response {
+            Ok(response) if response.status() == StatusCode::OK => response
+                .text()
+                .map(|text| RobotsTxt::parse(&text, USER_AGENT))
+                .unwrap_or_else(|_| RobotsTxt::allow_all()),
+            _ => RobotsTxt::allow_all(),
+        }
+    }
+}
+
+/// Reorder a list of URLs so that consecutive entries are from different hosts where possible,
+/// so that the read threads aren't all waiting on the same host.
+pub fn interleave_by_host<T>(items: Vec<T>, url: impl Fn(&T) -> &str) -> Vec<T> {
+    let mut by_host: HashMap<String, Vec<T>> = HashMap::default();
+    let mut hosts = Vec::new();
+    for item in items {
+        let host = Url::parse(url(&item))
+            .ok()
+            .and_then(|u| u.host_str().map(String::from))
+            .unwrap_or_default();
+
+        let entries = by_host.entry(host.clone()).or_insert_with(|| {
+            hosts.push(host);
+            Vec::new()
+        });
+        entries.push(item);
+    }
+
+    let mut queues = hosts
+        .into_iter()
+        .filter_map(|host| by_host.remove(&host))
+        .map(|entries| entries.into_iter())
+        .collect::<Vec<_>>();
+
+    let mut output = Vec::new();
+    while !queues.is_empty() {
+        queues.retain_mut(|queue| match queue.next() {
+            Some(item) => {
+                output.push(item);
+                true
+            }
+            None => false,
+        });
+    }
+
+    output
+}
@@ -0,0 +1,90 @@
+use ahash::HashSet;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::db::{Database, DbError};
+
+/// The color given to tags that are created automatically from a source.
+pub const DEFAULT_TAG_COLOR: &str = "gray";
+
+#[derive(Debug, Clone)]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub color: String,
+    /// The number of items with this tag.
+    pub items: u64,
+}
+
+pub fn list_tags(database: &Database) -> Result<Vec<Tag>, DbError> {
+    let conn = database.read_pool.get()?;
+    let mut stmt = conn.prepare_cached(
+        r##"SELECT id, name, description, color, COUNT(item_tags.item_id)
+        FROM tags
+        LEFT JOIN item_tags ON item_tags.tag_id = tags.id
+        GROUP BY tags.id
+        ORDER BY name"##,
+    )?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(Tag {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                description: row.get(2)?,
+                color: row.get(3)?,
+                items: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(rows)
+}
+
+/// Get the IDs of all the items that have the named tag.
+pub fn items_with_tag(database: &Database, name: &str) -> Result<HashSet<i64>, DbError> {
+    let conn = database.read_pool.get()?;
+    let mut stmt = conn.prepare_cached(
+        r##"SELECT item_id FROM item_tags
+        JOIN tags ON tags.id = item_tags.tag_id
+        WHERE tags.name = ?"##,
+    )?;
+
+    let ids = stmt
+        .query_map([name], |row| row.get(0))?
+        .collect::<Result<HashSet<i64>, _>>()?;
+
+    Ok(ids)
+}
+
+/// Replace an item's tags with the named tags, creating any tags that don't exist yet.
+pub(crate) fn set_item_tags(
+    conn: &Connection,
+    item_id: i64,
+    tags: &[String],
+) -> Result<(), rusqlite::Error> {
+    let mut clear_stmt = conn.prepare_cached("DELETE FROM item_tags WHERE item_id = ?")?;
+    let mut find_stmt = conn.prepare_cached("SELECT id FROM tags WHERE name = ? LIMIT 1")?;
+    let mut create_stmt = conn.prepare_cached("INSERT INTO tags (name, color) VALUES (?, ?)")?;
+    let mut add_stmt =
+        conn.prepare_cached("INSERT OR IGNORE INTO item_tags (item_id, tag_id) VALUES (?, ?)")?;
+
+    clear_stmt.execute([item_id])?;
+
+    for name in tags {
+        let existing = find_stmt
+            .query_row([name], |row| row.get::<_, i64>(0))
+            .optional()?;
+        let tag_id = match existing {
+            Some(id) => id,
+            None => {
+                create_stmt.execute(params![name, DEFAULT_TAG_COLOR])?;
+                conn.last_insert_rowid()
+            }
+        };
+
+        add_stmt.execute([item_id, tag_id])?;
+    }
+
+    Ok(())
+}
@@ -2,10 +2,11 @@ use std::{ops::Deref, sync::Arc};
 
 use arc_swap::{access::Map, ArcSwap, ArcSwapOption, Guard};
 use perceive_core::{
+    cancel::CancellationToken,
     db::Database,
     model::{Model, SentenceEmbeddingsModelType},
     search::Searcher,
-    sources::Source,
+    sources::{pipeline::ScanEvents, Source},
 };
 use thiserror::Error;
 
@@ -22,6 +23,10 @@ pub struct AppState {
     pub model_version: u32,
     pub sources: ArcSwap<Vec<Source>>,
     pub searcher: AsyncBuilder<Searcher>,
+    /// Cancels the scheduler's current run of scans.
+    pub scan_cancel: ArcSwap<CancellationToken>,
+    /// Events from the scheduler's scans.
+    pub scan_events: ScanEvents,
 }
 
 impl Default for AppState {
@@ -36,6 +41,8 @@ impl Default for AppState {
             model_version: 0,
             sources: ArcSwap::from_pointee(Vec::new()),
             searcher: AsyncBuilder::default(),
+            scan_cancel: ArcSwap::from_pointee(CancellationToken::new()),
+            scan_events: ScanEvents::new(),
         }
     }
 }
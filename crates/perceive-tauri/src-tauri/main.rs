@@ -3,17 +3,37 @@
     windows_subsystem = "windows"
 )]
 
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use app_state::AppState;
 use eyre::eyre;
 use parking_lot::Mutex;
-use perceive_core::{db::Database, search::SearchItem, sources::Source, Item};
+use perceive_core::{
+    cancel::CancellationToken,
+    db::Database,
+    search::SearchItem,
+    sources::{
+        pipeline::{watch_source, ScanEvent, ScanStage, ScanStats},
+        scheduler::run_due_sources,
+        Source, SourceConfig,
+    },
+    Item,
+};
 use serde::{Deserialize, Serialize};
-use tauri::{Manager, State};
+use tauri::{AppHandle, Manager, State};
 
 pub mod app_state;
 
+/// How often to check for sources that are due to be reindexed.
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The most often that the item counts of a running scan are sent to the UI.
+const SCAN_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(tag = "status")]
 pub enum LoadState {
@@ -22,6 +42,113 @@ pub enum LoadState {
     Error { error: String },
 }
 
+/// What has happened to the items in a source's current scan. This is sent to the UI in place
+/// of an event for every item, which would flood it on large sources.
+#[derive(Serialize, Clone, Debug, Default)]
+struct ScanProgress {
+    source_id: i64,
+    discovered: u64,
+    unchanged: u64,
+    fetched: u64,
+    skipped: u64,
+    embedded: u64,
+    written: u64,
+    errored: u64,
+}
+
+impl ScanProgress {
+    fn add(&mut self, event: &ScanEvent) {
+        let count = match event {
+            ScanEvent::Discovered { .. } => &mut self.discovered,
+            ScanEvent::Unchanged { .. } => &mut self.unchanged,
+            ScanEvent::Fetched { .. } => &mut self.fetched,
+            ScanEvent::Skipped { .. } => &mut self.skipped,
+            ScanEvent::Embedded { .. } => &mut self.embedded,
+            ScanEvent::Written { .. } => &mut self.written,
+            ScanEvent::Errored { .. } => &mut self.errored,
+            ScanEvent::StageFinished { .. } => return,
+        };
+        *count += 1;
+    }
+}
+
+fn event_source_id(event: &ScanEvent) -> i64 {
+    match event {
+        ScanEvent::Discovered { source_id, .. }
+        | ScanEvent::Unchanged { source_id, .. }
+        | ScanEvent::Fetched { source_id, .. }
+        | ScanEvent::Skipped { source_id, .. }
+        | ScanEvent::Embedded { source_id, .. }
+        | ScanEvent::Written { source_id, .. }
+        | ScanEvent::Errored { source_id, .. }
+        | ScanEvent::StageFinished { source_id, .. } => *source_id,
+    }
+}
+
+/// Forward the events from each scan to the UI. Stage changes are sent as they happen, and the
+/// per-item events are rolled up into counts that are sent at most every
+/// [SCAN_PROGRESS_INTERVAL].
+fn forward_scan_events(app: AppHandle, events: flume::Receiver<ScanEvent>) {
+    let mut progress: HashMap<i64, ScanProgress> = HashMap::new();
+    let mut pending = false;
+    let mut last_sent = Instant::now();
+
+    let send_progress = |progress: &HashMap<i64, ScanProgress>| {
+        for p in progress.values() {
+            app.emit_all("scan_progress", p).ok();
+        }
+    };
+
+    loop {
+        let event = match events.recv_timeout(SCAN_PROGRESS_INTERVAL) {
+            Ok(event) => Some(event),
+            Err(flume::RecvTimeoutError::Timeout) => None,
+            Err(flume::RecvTimeoutError::Disconnected) => break,
+        };
+
+        match event {
+            Some(event @ ScanEvent::StageFinished { .. }) => {
+                // Bring the counts up to date before the stage change.
+                if pending {
+                    send_progress(&progress);
+                    pending = false;
+                    last_sent = Instant::now();
+                }
+
+                if let ScanEvent::StageFinished {
+                    source_id,
+                    stage: ScanStage::Write,
+                    ..
+                } = event
+                {
+                    // The scan is done, so start over with the next one.
+                    progress.remove(&source_id);
+                }
+
+                app.emit_all("scan_event", event).ok();
+            }
+            Some(event) => {
+                let source_id = event_source_id(&event);
+                progress
+                    .entry(source_id)
+                    .or_insert_with(|| ScanProgress {
+                        source_id,
+                        ..Default::default()
+                    })
+                    .add(&event);
+                pending = true;
+            }
+            None => {}
+        }
+
+        if pending && last_sent.elapsed() >= SCAN_PROGRESS_INTERVAL {
+            send_progress(&progress);
+            pending = false;
+            last_sent = Instant::now();
+        }
+    }
+}
+
 #[tauri::command]
 fn load_status(status: State<Arc<Mutex<LoadState>>>) -> LoadState {
     let value = status.lock();
@@ -54,6 +181,113 @@ fn search(query: String, db: State<Database>, state: State<AppState>) -> Result<
     Ok(results)
 }
 
+#[tauri::command]
+fn cancel_scan(state: State<AppState>) {
+    state.scan_cancel.load().cancel();
+}
+
+/// Periodically reindex any sources that are due, and rebuild the search when they change.
+fn run_scheduler(app: AppHandle, database: Database) {
+    loop {
+        std::thread::sleep(SCHEDULER_INTERVAL);
+
+        let state = app.state::<AppState>();
+        let Ok(model) = state.get_model() else {
+            // Still loading
+            continue;
+        };
+
+        let cancel = CancellationToken::new();
+        state.scan_cancel.store(Arc::new(cancel.clone()));
+
+        let mut sources = state.sources.load().to_vec();
+        let results = run_due_sources(
+            &database,
+            &model,
+            state.model_id,
+            state.model_version,
+            &mut sources,
+            &state.scan_events,
+            &cancel,
+        );
+
+        if results.is_empty() {
+            continue;
+        }
+
+        // Rebuilding the searcher takes a while, so hide the removed items from the current one
+        // in the meantime.
+        let searcher = state.get_searcher().ok();
+        for (index, result) in &results {
+            match (result, &searcher) {
+                (Ok(result), Some(searcher)) => {
+                    searcher.remove_items(sources[*index].id, &result.removed_items);
+                }
+                (Ok(_), None) => {}
+                (Err(e), _) => {
+                    eprintln!("Failed to index source {}: {e}", sources[*index].name);
+                }
+            }
+        }
+
+        // The sources may have been edited while they were being scanned, so only replace the
+        // ones that were scanned.
+        let scanned = results
+            .iter()
+            .map(|(index, _)| &sources[*index])
+            .collect::<Vec<_>>();
+        state.sources.rcu(|current| {
+            current
+                .iter()
+                .map(|source| {
+                    let scanned = scanned.iter().find(|s| s.id == source.id).copied();
+                    scanned.unwrap_or(source).clone()
+                })
+                .collect::<Vec<_>>()
+        });
+
+        app.emit_all("sources", state.sources.load().to_vec()).ok();
+        state.build_searcher(database.clone());
+    }
+}
+
+/// Watch each filesystem source, so that changes to its files show up in the search without
+/// waiting for the next scheduled scan.
+fn start_watches(app: &AppHandle, database: &Database) {
+    let state = app.state::<AppState>();
+    for source in state.sources.load().iter() {
+        if !matches!(source.config, SourceConfig::Fs(_)) {
+            continue;
+        }
+
+        let app = app.clone();
+        let database = database.clone();
+        let source = source.clone();
+        std::thread::spawn(move || {
+            let state = app.state::<AppState>();
+            let Ok(model) = state.get_model() else {
+                return;
+            };
+
+            let stats = ScanStats::with_events(state.scan_events.clone());
+            let result = watch_source(
+                &stats,
+                &database,
+                &model,
+                state.model_id,
+                state.model_version,
+                &source,
+                || state.get_searcher().ok(),
+                &CancellationToken::new(),
+            );
+
+            if let Err(e) = result {
+                eprintln!("Failed to watch source {}: {e}", source.name);
+            }
+        });
+    }
+}
+
 fn main() {
     tauri::Builder::default()
         .manage(AppState::default())
@@ -65,6 +299,14 @@ fn main() {
             app_state.sources.store(Arc::new(sources));
             app.manage(database.clone());
 
+            let scan_events = app_state.scan_events.subscribe();
+            let events_app = app.handle();
+            std::thread::spawn(move || forward_scan_events(events_app, scan_events));
+
+            let scheduler_app = app.handle();
+            let scheduler_db = database.clone();
+            std::thread::spawn(move || run_scheduler(scheduler_app, scheduler_db));
+
             let load_status = Arc::new(Mutex::new(LoadState::Loading));
             app.manage(load_status.clone());
 
@@ -75,8 +317,9 @@ fn main() {
                 .model
                 .rebuild(move || perceive_core::model::Model::new_pretrained(model_type));
 
-            let searcher_rx = app_state.build_searcher(database);
+            let searcher_rx = app_state.build_searcher(database.clone());
 
+            let watch_app = app.handle();
             std::thread::spawn(move || {
                 let result = match (model_rx.recv(), searcher_rx.recv()) {
                     (Ok(Ok(_)), Ok(Ok(_))) => LoadState::Loaded,
@@ -94,6 +337,10 @@ fn main() {
                     },
                 };
 
+                if matches!(result, LoadState::Loaded) {
+                    start_watches(&watch_app, &database);
+                }
+
                 {
                     let mut value = load_status.lock();
                     *value = result.clone();
@@ -103,7 +350,12 @@ fn main() {
 
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![load_status, get_sources, search])
+        .invoke_handler(tauri::generate_handler![
+            load_status,
+            get_sources,
+            search,
+            cancel_scan
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }